@@ -0,0 +1,91 @@
+//! Compares loading an image onto every tensix one tile at a time with [`parallel::load`].
+//!
+//! Needs hardware, run with `cargo bench --bench parallel_load`. The image is never started.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use ttx_rs::{
+    chip::{self, noc::NocId, Chip},
+    kernel::{KernelBytes, KernelData, Verify, VerifyPolicy},
+    loader,
+    parallel::{self, LoadJob, ParallelOptions},
+};
+
+const IMAGE_ADDR: u32 = 0x20000;
+const IMAGE_LEN: usize = 256 * 1024;
+const RUNS: u32 = 5;
+
+fn image() -> KernelData {
+    let writes = vec![KernelBytes::new(
+        IMAGE_ADDR,
+        (0..IMAGE_LEN).map(|v| v as u8).collect(),
+        false,
+        0,
+    )];
+    KernelData::new(writes, HashMap::new())
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let mut chips = chip::scan()
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect::<Vec<Chip>>();
+    if chips.is_empty() {
+        println!("no chips found, skipping");
+        return;
+    }
+
+    let data = image();
+    let verify = Verify {
+        policy: VerifyPolicy::None,
+        retries: 0,
+        ..Verify::default()
+    };
+    let options = ParallelOptions::new().verify(verify).start(false);
+
+    let sequential = time(|| {
+        for chip in chips.iter_mut() {
+            chip.deassert_riscv_reset();
+            chip.go_busy();
+            for tile in chip.tensix_tiles() {
+                loader::stop(chip, tile);
+                data.load_with(chip, NocId::Noc1, tile, verify).unwrap();
+            }
+        }
+    });
+    println!("sequential: {sequential:?} for {} chip(s)", chips.len());
+
+    for workers in [1, 2, 4, 8] {
+        let options = options.clone().workers(workers);
+        let elapsed = time(|| {
+            let jobs = chips
+                .iter_mut()
+                .map(|chip| {
+                    let tiles = chip.tensix_tiles();
+                    LoadJob {
+                        chip,
+                        data: &data,
+                        tiles,
+                    }
+                })
+                .collect();
+            parallel::load(jobs, &options).unwrap();
+        });
+        println!(
+            "parallel, {workers} worker(s) per chip: {elapsed:?} ({:.2}x)",
+            sequential.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Host side allocation of device memory.
+//!
+//! Device memory is handed out by a [`RangeAllocator`], a first fit allocator over an address
+//! range that also tracks the regions that are not its to give out, such as firmware and the
+//! segments of a loaded kernel. [`L1Allocator`] wraps one for the L1 of a tile and
+//! [`DramAllocator`] one for each DRAM channel.
+
+use std::collections::BTreeMap;
+
+use crate::kernel::VerifyError;
+
+pub mod dram;
+pub mod l1;
+
+pub use dram::{DramAllocator, InterleavedBuffer};
+pub use l1::L1Allocator;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AllocError {
+    #[error(
+        "no room for {name}: {size} bytes aligned to {align}, the largest free block is {largest} bytes"
+    )]
+    OutOfMemory {
+        name: String,
+        size: u64,
+        align: u64,
+        largest: u64,
+    },
+
+    #[error("{0} is already allocated")]
+    Exists(String),
+
+    #[error("{0} is not allocated")]
+    NotFound(String),
+
+    #[error("alignment {0} is not a power of two")]
+    Alignment(u64),
+
+    #[error("page size {0} is not a non-zero multiple of {min}", min = dram::MIN_ALIGN)]
+    PageSize(u64),
+
+    #[error("there are no DRAM channels to allocate {0} on")]
+    NoChannels(String),
+
+    #[error("0x{start:x}..0x{end:x} overlaps {name} at 0x{block_start:x}..0x{block_end:x}")]
+    Overlap {
+        start: u64,
+        end: u64,
+        name: String,
+        block_start: u64,
+        block_end: u64,
+    },
+
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// Never handed out, e.g. firmware or mailboxes
+    Reserved,
+    /// A segment of the loaded kernel, replaced when another kernel is loaded
+    Kernel,
+    /// An allocation made by the host
+    Buffer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub name: String,
+    pub kind: BlockKind,
+    pub addr: u64,
+    pub size: u64,
+}
+
+impl Block {
+    pub fn end(&self) -> u64 {
+        self.addr + self.size
+    }
+}
+
+/// First fit allocation of `base..end`.
+#[derive(Clone, Debug)]
+pub struct RangeAllocator {
+    pub base: u64,
+    pub end: u64,
+    blocks: BTreeMap<u64, Block>,
+}
+
+impl RangeAllocator {
+    pub fn new(base: u64, end: u64) -> Self {
+        Self {
+            base,
+            end,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Every block in address order.
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    /// The allocation called `name`.
+    pub fn get(&self, name: &str) -> Option<&Block> {
+        self.blocks
+            .values()
+            .find(|v| v.kind == BlockKind::Buffer && v.name == name)
+    }
+
+    /// Blocks of `kind` overlapping `start..end`.
+    pub fn overlapping(
+        &self,
+        start: u64,
+        end: u64,
+        kind: Option<BlockKind>,
+    ) -> impl Iterator<Item = &Block> {
+        self.blocks.values().filter(move |v| {
+            v.addr < end && start < v.end() && kind.map(|kind| v.kind == kind).unwrap_or(true)
+        })
+    }
+
+    /// Add a block at a fixed address, it may not overlap any other.
+    pub fn insert(&mut self, block: Block) -> Result<(), AllocError> {
+        let (start, end) = (block.addr, block.end().max(block.addr + 1));
+        if let Some(other) = self.overlapping(start, end, None).next() {
+            return Err(AllocError::Overlap {
+                start,
+                end,
+                name: other.name.clone(),
+                block_start: other.addr,
+                block_end: other.end(),
+            });
+        }
+
+        self.blocks.insert(block.addr, block);
+        Ok(())
+    }
+
+    /// Unused ranges in address order.
+    pub fn gaps(&self) -> Vec<(u64, u64)> {
+        let mut gaps = Vec::new();
+        let mut cursor = self.base;
+        for block in self.blocks.values() {
+            if block.addr > cursor {
+                gaps.push((cursor, block.addr.min(self.end)));
+            }
+            cursor = cursor.max(block.end());
+        }
+        if cursor < self.end {
+            gaps.push((cursor, self.end));
+        }
+
+        gaps.retain(|(start, end)| start < end);
+        gaps
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.gaps().iter().map(|(start, end)| end - start).sum()
+    }
+
+    pub fn largest_free(&self) -> u64 {
+        self.gaps()
+            .iter()
+            .map(|(start, end)| end - start)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The lowest address from `from` up where `size` bytes aligned to `align` are free.
+    pub fn fit(&self, from: u64, size: u64, align: u64) -> Option<u64> {
+        self.gaps().into_iter().find_map(|(start, end)| {
+            let addr = start.max(from).next_multiple_of(align);
+            (addr + size <= end).then_some(addr)
+        })
+    }
+
+    /// Allocate `size` bytes at the lowest address aligned to `align`.
+    pub fn alloc(&mut self, name: &str, size: u64, align: u64) -> Result<u64, AllocError> {
+        if !align.is_power_of_two() {
+            return Err(AllocError::Alignment(align));
+        }
+        if self.get(name).is_some() {
+            return Err(AllocError::Exists(name.to_string()));
+        }
+
+        // Sizes are rounded up so that the next block stays aligned
+        let size = size.max(1).next_multiple_of(align);
+        let Some(addr) = self.fit(self.base, size, align) else {
+            return Err(AllocError::OutOfMemory {
+                name: name.to_string(),
+                size,
+                align,
+                largest: self.largest_free(),
+            });
+        };
+
+        self.blocks.insert(
+            addr,
+            Block {
+                name: name.to_string(),
+                kind: BlockKind::Buffer,
+                addr,
+                size,
+            },
+        );
+        Ok(addr)
+    }
+
+    pub fn free(&mut self, name: &str) -> Result<Block, AllocError> {
+        let addr = self
+            .get(name)
+            .ok_or_else(|| AllocError::NotFound(name.to_string()))?
+            .addr;
+        Ok(self.blocks.remove(&addr).unwrap())
+    }
+
+    /// Drop every block of `kind`.
+    pub fn clear(&mut self, kind: BlockKind) {
+        self.blocks.retain(|_, v| v.kind != kind);
+    }
+}
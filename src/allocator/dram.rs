@@ -0,0 +1,252 @@
+//! DRAM buffers, either within one channel or interleaved across all of them.
+//!
+//! An [`InterleavedBuffer`] is split into pages handed out round-robin, page `i` lives on channel
+//! `i % channels`. Every channel holds its share at the same address, so a kernel only needs the
+//! base address, the page size and the channel tiles to find any page.
+
+use super::{AllocError, Block, BlockKind, RangeAllocator};
+use crate::{
+    chip::noc::{NocId, Tile},
+    kernel::{read_aligned, write_aligned},
+    Chip,
+};
+
+/// Buffers are at least aligned to this, the widest NoC access a DRAM tile needs
+pub const MIN_ALIGN: u64 = 32;
+
+#[derive(Clone, Debug)]
+pub struct DramAllocator {
+    /// The tile each channel is accessed through
+    pub tiles: Vec<Tile>,
+    channels: Vec<RangeAllocator>,
+}
+
+impl DramAllocator {
+    /// `tiles` are the channels, each `size` bytes.
+    pub fn new(tiles: Vec<Tile>, size: u64) -> Self {
+        let channels = vec![RangeAllocator::new(0, size); tiles.len()];
+        Self { tiles, channels }
+    }
+
+    pub fn for_chip(chip: &Chip) -> Self {
+        let tiles = (0..chip.dram_count()).map(|v| chip.dram(v)[0]).collect();
+        Self::new(tiles, chip.dram_size())
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn blocks(&self, channel: usize) -> impl Iterator<Item = &Block> {
+        self.channels[channel].blocks()
+    }
+
+    pub fn free_bytes(&self, channel: usize) -> u64 {
+        self.channels[channel].free_bytes()
+    }
+
+    fn align(&self, align: u64) -> u64 {
+        self.tiles
+            .iter()
+            .map(|v| (v.align_read as u64).max(v.align_write as u64))
+            .fold(align.max(MIN_ALIGN), u64::max)
+    }
+
+    /// Keep `addr..addr + size` of `channel` from ever being allocated.
+    pub fn reserve(
+        &mut self,
+        channel: usize,
+        name: &str,
+        addr: u64,
+        size: u64,
+    ) -> Result<(), AllocError> {
+        self.channels[channel].insert(Block {
+            name: name.to_string(),
+            kind: BlockKind::Reserved,
+            addr,
+            size,
+        })
+    }
+
+    /// Allocate `size` bytes on a single channel.
+    pub fn alloc(
+        &mut self,
+        channel: usize,
+        name: &str,
+        size: u64,
+        align: u64,
+    ) -> Result<u64, AllocError> {
+        let align = self.align(align);
+        self.channels[channel].alloc(name, size, align)
+    }
+
+    /// Address of `name` on `channel`.
+    pub fn get(&self, channel: usize, name: &str) -> Option<u64> {
+        self.channels[channel].get(name).map(|v| v.addr)
+    }
+
+    /// Free `name` on every channel that has it.
+    pub fn free(&mut self, name: &str) -> Result<(), AllocError> {
+        let mut found = false;
+        for channel in &mut self.channels {
+            found |= channel.free(name).is_ok();
+        }
+
+        if found {
+            Ok(())
+        } else {
+            Err(AllocError::NotFound(name.to_string()))
+        }
+    }
+
+    /// Allocate `size` bytes interleaved across every channel in pages of `page_size`.
+    pub fn interleaved(
+        &mut self,
+        name: &str,
+        size: u64,
+        page_size: u64,
+    ) -> Result<InterleavedBuffer, AllocError> {
+        // Every page starts aligned for the NoC, pages needn't be a power of two (e.g. bfp8 tiles)
+        if page_size == 0 || !page_size.is_multiple_of(MIN_ALIGN) {
+            return Err(AllocError::PageSize(page_size));
+        }
+        if self.channels.is_empty() {
+            return Err(AllocError::NoChannels(name.to_string()));
+        }
+        let align = self.align(page_size.next_power_of_two());
+        if self.channels.iter().any(|v| v.get(name).is_some()) {
+            return Err(AllocError::Exists(name.to_string()));
+        }
+
+        let pages = size.div_ceil(page_size).max(1);
+        let per_channel = pages.div_ceil(self.channels.len() as u64) * page_size;
+
+        // Find the lowest address free on every channel
+        let mut addr = 0;
+        loop {
+            let fits = self
+                .channels
+                .iter()
+                .map(|v| v.fit(addr, per_channel, align))
+                .collect::<Option<Vec<_>>>();
+            let Some(fits) = fits else {
+                return Err(AllocError::OutOfMemory {
+                    name: name.to_string(),
+                    size: per_channel,
+                    align,
+                    largest: self
+                        .channels
+                        .iter()
+                        .map(|v| v.largest_free())
+                        .min()
+                        .unwrap_or(0),
+                });
+            };
+
+            let highest = fits.into_iter().max().unwrap_or(addr);
+            if highest == addr {
+                break;
+            }
+            addr = highest;
+        }
+
+        for channel in &mut self.channels {
+            channel.insert(Block {
+                name: name.to_string(),
+                kind: BlockKind::Buffer,
+                addr,
+                size: per_channel,
+            })?;
+        }
+        tracing::debug!(
+            "allocated {name} at 0x{addr:x}: {pages} pages of {page_size} bytes across {} channels",
+            self.channels.len()
+        );
+
+        Ok(InterleavedBuffer {
+            name: name.to_string(),
+            addr,
+            size,
+            page_size,
+            tiles: self.tiles.clone(),
+        })
+    }
+}
+
+/// A DRAM buffer striped page by page across channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterleavedBuffer {
+    pub name: String,
+    /// Address of the buffer on every channel
+    pub addr: u64,
+    pub size: u64,
+    pub page_size: u64,
+    /// The tile of each channel
+    pub tiles: Vec<Tile>,
+}
+
+impl InterleavedBuffer {
+    pub fn pages(&self) -> u64 {
+        self.size.div_ceil(self.page_size)
+    }
+
+    /// Index of the channel holding `page`.
+    pub fn channel(&self, page: u64) -> usize {
+        (page % self.tiles.len() as u64) as usize
+    }
+
+    /// Where `page` lives.
+    pub fn page_address(&self, page: u64) -> (Tile, u64) {
+        let channels = self.tiles.len() as u64;
+        let addr = self.addr + (page / channels) * self.page_size;
+        (self.tiles[self.channel(page)], addr)
+    }
+
+    /// `[addr, page_size, channels]`, what a kernel needs besides the channel tiles to find a
+    /// page.
+    pub fn args(&self) -> [u32; 3] {
+        [
+            self.addr as u32,
+            self.page_size as u32,
+            self.tiles.len() as u32,
+        ]
+    }
+
+    /// Split `data` into pages, `write` is called with each page and where it goes.
+    pub fn scatter(&self, data: &[u8], write: &mut dyn FnMut(Tile, u64, &[u8])) {
+        assert!(
+            data.len() as u64 <= self.size,
+            "{} bytes do not fit in {} ({} bytes)",
+            data.len(),
+            self.name,
+            self.size
+        );
+
+        for (page, chunk) in data.chunks(self.page_size as usize).enumerate() {
+            let (tile, addr) = self.page_address(page as u64);
+            write(tile, addr, chunk);
+        }
+    }
+
+    /// Gather the whole buffer, `read` fills each page from where it lives.
+    pub fn gather(&self, read: &mut dyn FnMut(Tile, u64, &mut [u8])) -> Vec<u8> {
+        let mut data = vec![0; self.size as usize];
+        for (page, chunk) in data.chunks_mut(self.page_size as usize).enumerate() {
+            let (tile, addr) = self.page_address(page as u64);
+            read(tile, addr, chunk);
+        }
+        data
+    }
+
+    pub fn write_all(&self, chip: &mut Chip, noc_id: NocId, data: &[u8]) {
+        self.scatter(data, &mut |tile, addr, page| {
+            write_aligned(chip, noc_id, tile, addr, page)
+        });
+    }
+
+    pub fn read_all(&self, chip: &mut Chip, noc_id: NocId) -> Vec<u8> {
+        self.gather(&mut |tile, addr, page| {
+            page.copy_from_slice(&read_aligned(chip, noc_id, tile, addr, page.len()))
+        })
+    }
+}
@@ -0,0 +1,196 @@
+//! Buffers in the L1 of a tile.
+//!
+//! Everything below the loaded kernel's `__firmware_end` is firmware, and the kernel's segments
+//! (including the `.data`/`.bss` holding its mailboxes) are never handed out. Loading a kernel
+//! through [`L1Allocator::load`] refuses to write over live buffers.
+
+use super::{AllocError, Block, BlockKind, RangeAllocator};
+use crate::{
+    chip::noc::{NocId, Tile},
+    kernel::{Kernel, KernelData, Verify},
+    Chip,
+};
+
+/// Buffers are at least aligned to this, the widest NoC access any tile needs
+pub const MIN_ALIGN: u64 = 16;
+
+#[derive(Clone, Debug)]
+pub struct L1Allocator {
+    pub tile: Tile,
+    ranges: RangeAllocator,
+}
+
+impl L1Allocator {
+    /// An empty L1 of `size` bytes.
+    pub fn new(tile: Tile, size: u64) -> Self {
+        Self {
+            tile,
+            ranges: RangeAllocator::new(0, size),
+        }
+    }
+
+    pub fn for_chip(chip: &Chip, tile: Tile) -> Self {
+        Self::new(tile, chip.tensix_l1())
+    }
+
+    /// Seeded with `data` as the loaded kernel.
+    pub fn for_kernel(chip: &Chip, tile: Tile, data: &KernelData) -> Self {
+        let mut allocator = Self::for_chip(chip, tile);
+        allocator.set_kernel(data).unwrap();
+        allocator
+    }
+
+    pub fn size(&self) -> u64 {
+        self.ranges.end
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.ranges.blocks()
+    }
+
+    /// Live buffers in address order.
+    pub fn buffers(&self) -> impl Iterator<Item = &Block> {
+        self.blocks().filter(|v| v.kind == BlockKind::Buffer)
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.ranges.free_bytes()
+    }
+
+    pub fn largest_free(&self) -> u64 {
+        self.ranges.largest_free()
+    }
+
+    /// Keep `addr..addr + size` from ever being allocated.
+    pub fn reserve(&mut self, name: &str, addr: u64, size: u64) -> Result<(), AllocError> {
+        self.ranges.insert(Block {
+            name: name.to_string(),
+            kind: BlockKind::Reserved,
+            addr,
+            size,
+        })
+    }
+
+    /// The ranges `data` occupies: the firmware below `__firmware_end` and each segment.
+    fn kernel_ranges(data: &KernelData) -> Vec<(u64, u64)> {
+        let mut ranges = data
+            .writes
+            .iter()
+            .map(|v| {
+                let start = v.addr as u64;
+                (start, start + v.len() as u64 + v.zeroed as u64)
+            })
+            .chain(data.bin.data_start.map(|end| (0, end)))
+            .filter(|(start, end)| start < end)
+            .collect::<Vec<_>>();
+        ranges.sort();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    /// Check that loading `data` would not write over a live buffer.
+    pub fn check_kernel(&self, data: &KernelData) -> Result<(), AllocError> {
+        for (start, end) in Self::kernel_ranges(data) {
+            if let Some(block) = self
+                .ranges
+                .overlapping(start, end, Some(BlockKind::Buffer))
+                .next()
+            {
+                return Err(AllocError::Overlap {
+                    start,
+                    end,
+                    name: block.name.clone(),
+                    block_start: block.addr,
+                    block_end: block.end(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make `data` the loaded kernel, replacing the regions of the previous one.
+    pub fn set_kernel(&mut self, data: &KernelData) -> Result<(), AllocError> {
+        self.check_kernel(data)?;
+
+        let previous = self.ranges.clone();
+        self.ranges.clear(BlockKind::Kernel);
+        for (start, end) in Self::kernel_ranges(data) {
+            let block = Block {
+                name: if start == 0 { "firmware" } else { "kernel" }.to_string(),
+                kind: BlockKind::Kernel,
+                addr: start,
+                size: end - start,
+            };
+            if let Err(err) = self.ranges.insert(block) {
+                self.ranges = previous;
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load `data` onto the tile unless it would write over a live buffer.
+    pub fn load(
+        &mut self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        data: &KernelData,
+        verify: Verify,
+    ) -> Result<(), AllocError> {
+        self.set_kernel(data)?;
+        data.load_with(chip, noc_id, self.tile, verify)?;
+        Ok(())
+    }
+
+    /// Allocate `size` bytes, `align` is raised to at least [`MIN_ALIGN`] and the tile's NoC
+    /// alignment.
+    pub fn alloc(&mut self, name: &str, size: u64, align: u64) -> Result<u64, AllocError> {
+        let align = align
+            .max(MIN_ALIGN)
+            .max(self.tile.align_read as u64)
+            .max(self.tile.align_write as u64);
+        let addr = self.ranges.alloc(name, size, align)?;
+        tracing::debug!(
+            "{:?}: allocated {name} at 0x{addr:x} ({size} bytes)",
+            self.tile.addr.n0
+        );
+        Ok(addr)
+    }
+
+    pub fn free(&mut self, name: &str) -> Result<(), AllocError> {
+        self.ranges.free(name)?;
+        Ok(())
+    }
+
+    /// Address of the buffer `name`.
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.ranges.get(name).map(|v| v.addr)
+    }
+
+    /// The addresses of `names` in order, to pass to a kernel through its runtime args.
+    pub fn args<const N: usize>(&self, names: [&str; N]) -> Result<[u32; N], AllocError> {
+        let mut args = [0; N];
+        for (arg, name) in args.iter_mut().zip(names) {
+            *arg = self
+                .get(name)
+                .ok_or_else(|| AllocError::NotFound(name.to_string()))? as u32;
+        }
+        Ok(args)
+    }
+}
+
+impl Kernel {
+    /// An allocator for the kernel's tile with the kernel's regions taken.
+    pub fn l1_allocator(&self) -> L1Allocator {
+        L1Allocator::for_kernel(&self.device, self.core, &self.data)
+    }
+}
@@ -1,16 +1,20 @@
 use std::sync::{atomic::AtomicBool, Mutex};
 
 use blackhole::Blackhole;
+use dram::{DramChannel, PortTable};
 use grayskull::Grayskull;
 use luwen::{luwen_core::Arch, ttkmd_if::PciDevice};
 use noc::{NocAddress, NocId, NocInterface, Tile};
 use wormhole::Wormhole;
 
-use crate::kernel::{Kernel, KernelData};
+use crate::kernel::{Kernel, KernelData, KernelOutcome, Verify};
 pub use crate::loader;
+use crate::parallel::{self, LoadErrors, LoadJob, ParallelOptions};
+use crate::program::{CoreProgram, Program, ProgramError, ProgramHandle};
 
 pub mod blackhole;
 pub mod dma;
+pub mod dram;
 pub mod field;
 pub mod grayskull;
 pub mod noc;
@@ -129,6 +133,39 @@ impl Chip {
         }
     }
 
+    pub fn tensix_tiles(&self) -> Vec<Tile> {
+        (0..self.tensix_count()).map(|v| self.tensix(v)).collect()
+    }
+
+    /// The current AI clock in MHz, the rate of the tensix wall clock.
+    pub fn aiclk(&mut self) -> Result<u32, String> {
+        match self {
+            Chip::Grayskull(grayskull) => grayskull
+                .send_arc_msg(grayskull::ArcMsg::GetAiclk)
+                .map(|v| v.arg())
+                .map_err(|v| v.to_string()),
+            Chip::Wormhole(wormhole) => wormhole
+                .send_arc_msg(wormhole::ArcMsg::GetAiclk)
+                .map(|v| v.arg())
+                .map_err(|v| v.to_string()),
+            Chip::Blackhole(blackhole) => blackhole
+                .telemetry
+                .read(&mut blackhole.interface, blackhole.endpoints.arc.into())
+                .map_err(|v| v.to_string())?
+                .aiclk()
+                .ok_or_else(|| "AICLK missing from telemetry".to_string()),
+        }
+    }
+
+    /// Whether tensix rectangles can be multicast to using their NOC0 coordinates.
+    /// Blackhole's translated coordinates have their own multicast rules.
+    pub fn rect_multicast(&self) -> bool {
+        match self {
+            Chip::Grayskull(_) | Chip::Wormhole(_) => true,
+            Chip::Blackhole(blackhole) => !blackhole.endpoints.use_translated_multicast,
+        }
+    }
+
     pub fn tensix_l1(&self) -> u64 {
         match self {
             Chip::Grayskull(grayskull) => grayskull.endpoints.tensix_l1_size,
@@ -169,6 +206,35 @@ impl Chip {
         }
     }
 
+    /// Size of the NoC grid, `None` when tiles are addressed through translated coordinates.
+    pub fn noc_grid(&self) -> Option<(u8, u8)> {
+        match self {
+            Chip::Grayskull(_) => Some((grayskull::GRID_SIZE_X, grayskull::GRID_SIZE_Y)),
+            Chip::Wormhole(_) => Some((wormhole::GRID_SIZE_X, wormhole::GRID_SIZE_Y)),
+            Chip::Blackhole(blackhole) => (!blackhole.endpoints.use_translated_multicast)
+                .then_some((blackhole::GRID_SIZE_X, blackhole::GRID_SIZE_Y)),
+        }
+    }
+
+    pub fn dram_channel(&self, index: usize) -> DramChannel {
+        DramChannel {
+            index,
+            ports: self.dram(index).to_vec(),
+            grid: self.noc_grid(),
+        }
+    }
+
+    pub fn dram_channels(&self) -> Vec<DramChannel> {
+        (0..self.dram_count())
+            .map(|v| self.dram_channel(v))
+            .collect()
+    }
+
+    /// The nearest port of every DRAM channel for each tensix on `noc_id`.
+    pub fn dram_port_table(&self, noc_id: NocId) -> PortTable {
+        PortTable::new(&self.dram_channels(), noc_id, &self.tensix_tiles())
+    }
+
     pub fn pcie(&self) -> Tile {
         match self {
             Chip::Grayskull(grayskull) => grayskull.endpoints.pci,
@@ -294,106 +360,84 @@ impl Chip {
         noc_id: NocId,
         tile: Tile,
         wait: bool,
-    ) -> Kernel {
-        self.load_kernels(&mut data, Some(vec![tile]), wait);
+    ) -> Result<Kernel, LoadErrors> {
+        self.load_kernels(&mut data, Some(vec![tile]), wait, Verify::default())?;
 
-        Kernel::new(self.dupe().unwrap(), noc_id, tile, data)
+        Ok(Kernel::new(self.dupe().unwrap(), noc_id, tile, data))
     }
 
-    pub fn load_kernels(&mut self, data: &mut KernelData, tiles: Option<Vec<Tile>>, wait: bool) {
-        tracing::debug!("{}[{}]: stopping cores", self.arch(), self.id());
-
-        if let Some(tiles) = &tiles {
-            for tile in tiles {
-                tracing::trace!("{}[{}]: stopping tile {:?}", self.arch(), self.id(), tile);
-                loader::stop(self, *tile);
-            }
-        } else {
-            tracing::trace!("{}[{}]: stopping all tiles", self.arch(), self.id());
-            loader::stop_all(self);
-        }
-
-        tracing::debug!("{}[{}]: deasserting riscv reset", self.arch(), self.id());
-        self.deassert_riscv_reset();
-
-        tracing::debug!("{}[{}]: go busy", self.arch(), self.id());
-        self.go_busy();
-
-        tracing::debug!("{}[{}]: loading binary", self.arch(), self.id());
+    /// Load a separate program onto each RISC of `tile`, see [`CoreProgram`].
+    pub fn load_program(
+        &mut self,
+        program: &CoreProgram,
+        noc_id: NocId,
+        tile: Tile,
+        verify: Verify,
+    ) -> Result<Kernel, ProgramError> {
+        program.load(self, noc_id, tile, verify)
+    }
 
-        if let Some(tiles) = &tiles {
-            for tile in tiles {
-                tracing::trace!(
-                    "{}[{}]: loading binary to tile {:?}",
-                    self.arch(),
-                    self.id(),
-                    tile
-                );
-                data.load(self, noc::NocId::Noc1, *tile);
-            }
-        } else {
-            tracing::trace!(
-                "{}[{}]: loading binary to all tensix",
-                self.arch(),
-                self.id()
-            );
-            data.load_all(self, noc::NocId::Noc1);
-        }
+    /// Load and start each group of `program`, see [`Program`].
+    pub fn launch(
+        &mut self,
+        program: &Program,
+        noc_id: NocId,
+    ) -> Result<ProgramHandle, ProgramError> {
+        program.launch(self, noc_id)
+    }
 
-        tracing::debug!("{}[{}]: starting tensix", self.arch(), self.id());
+    /// Load and start `data` on `tiles` (all tensix if None), checking the segments with `verify`.
+    /// If `wait` is set the outcome of each tile is returned once they have all finished.
+    pub fn load_kernels(
+        &mut self,
+        data: &mut KernelData,
+        tiles: Option<Vec<Tile>>,
+        wait: bool,
+        verify: Verify,
+    ) -> Result<Vec<(Tile, KernelOutcome)>, LoadErrors> {
+        self.load_kernels_inner(data, tiles, wait, None, verify)
+    }
 
-        if let Some(tiles) = &tiles {
-            for tile in tiles {
-                tracing::trace!("{}[{}]: starting tile {:?}", self.arch(), self.id(), tile);
-                loader::start(self, tile.addr, true, true);
-            }
-        } else {
-            tracing::trace!("{}[{}]: starting all tensix", self.arch(), self.id());
-            loader::start_all(self, true, true);
-        }
+    /// Like [`Chip::load_kernels`] but waiting at most `timeout` for all tiles to finish.
+    pub fn load_kernels_timeout(
+        &mut self,
+        data: &mut KernelData,
+        tiles: Option<Vec<Tile>>,
+        timeout: std::time::Duration,
+        verify: Verify,
+    ) -> Result<Vec<(Tile, KernelOutcome)>, LoadErrors> {
+        self.load_kernels_inner(data, tiles, true, Some(timeout), verify)
+    }
 
-        let all_tensix = (0..self.tensix_count()).map(|v| self.tensix(v)).collect();
-        let all_tiles = if let Some(tiles) = &tiles {
-            tiles
-        } else {
-            &all_tensix
+    fn load_kernels_inner(
+        &mut self,
+        data: &mut KernelData,
+        tiles: Option<Vec<Tile>>,
+        wait: bool,
+        timeout: Option<std::time::Duration>,
+        verify: Verify,
+    ) -> Result<Vec<(Tile, KernelOutcome)>, LoadErrors> {
+        let all_tiles = match &tiles {
+            Some(tiles) => tiles.clone(),
+            None => self.tensix_tiles(),
         };
 
-        if data.bin.start_sync.is_some() {
-            tracing::debug!("{}[{}]: waiting for tensix start", self.arch(), self.id());
-
-            for (core_id, tile) in all_tiles.iter().enumerate() {
-                tracing::trace!(
-                    "{}[{}]: waiting for fw start on {:?}",
-                    self.arch(),
-                    self.id(),
-                    tile
-                );
-
-                if let Some(id) = data.sym_table.get("CORE_ID") {
-                    self.noc_write32(noc::NocId::Noc1, *tile, *id, core_id as u32);
-                }
-
-                data.bin.print_state(self, noc::NocId::Noc1, tile.addr);
-                while !data.bin.start_sync(self, noc::NocId::Noc1, tile.addr) {
-                    if !data.bin.all_complete(self, noc::NocId::Noc1, tile.addr) {
-                        data.bin.print_state_diff(self, noc::NocId::Noc1, tile.addr);
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
-
-                tracing::trace!("{}[{}]: fw started on {:?}", self.arch(), self.id(), tile);
-            }
-        } else {
-            tracing::debug!(
-                "{}[{}]: no start sync point found in elf; not waiting for start",
-                self.arch(),
-                self.id()
-            );
-            for tile in all_tiles {
-                data.bin.print_state(self, noc::NocId::Noc1, tile.addr);
-            }
-        }
+        tracing::debug!(
+            "{}[{}]: loading binary to {} tiles",
+            self.arch(),
+            self.id(),
+            all_tiles.len()
+        );
+        // The wait below polls over NoC 1, so the tiles are loaded over it too
+        let options = ParallelOptions::new().noc_id(NocId::Noc1).verify(verify);
+        parallel::load(
+            vec![LoadJob {
+                chip: self,
+                data,
+                tiles: all_tiles.clone(),
+            }],
+            &options,
+        )?;
 
         if !wait {
             tracing::debug!(
@@ -402,25 +446,34 @@ impl Chip {
                 self.id()
             );
 
-            return;
+            return Ok(Vec::new());
         }
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
 
         tracing::debug!(
             "{}[{}]: waiting for kernel to complete",
             self.arch(),
             self.id()
         );
-        for tile in all_tiles {
+        let mut outcomes = Vec::with_capacity(all_tiles.len());
+        for tile in &all_tiles {
             tracing::trace!(
                 "{}[{}]: waiting for kernel to complete on {:?}",
                 self.arch(),
                 self.id(),
                 tile
             );
-            data.bin.wait(self, noc::NocId::Noc1, tile.addr);
+            let outcome = data.wait_until(self, NocId::Noc1, *tile, deadline, None);
+            outcomes.push((*tile, outcome));
         }
 
-        self.stop_tile(tiles);
+        // Finished tiles were stopped by the wait, the others are left running so that they can
+        // be inspected
+        if outcomes.iter().all(|(_, outcome)| outcome.is_finished()) {
+            self.stop_tile(tiles);
+        }
+
+        Ok(outcomes)
     }
 
     pub fn stop_tile(&mut self, tiles: Option<Vec<Tile>>) {
@@ -507,4 +560,19 @@ impl NocInterface for Chip {
             Chip::Blackhole(blackhole) => blackhole.noc_broadcast32(noc_id, addr, value),
         }
     }
+
+    fn noc_multicast(
+        &mut self,
+        noc_id: noc::NocId,
+        start: (u8, u8),
+        end: (u8, u8),
+        addr: u64,
+        data: &[u8],
+    ) {
+        match self {
+            Chip::Grayskull(grayskull) => grayskull.noc_multicast(noc_id, start, end, addr, data),
+            Chip::Wormhole(wormhole) => wormhole.noc_multicast(noc_id, start, end, addr, data),
+            Chip::Blackhole(blackhole) => blackhole.noc_multicast(noc_id, start, end, addr, data),
+        }
+    }
 }
@@ -7,8 +7,8 @@ use super::Blackhole;
 const PHYS_TO_NOC0_X: &[u32] = &[0, 1, 16, 2, 15, 3, 14, 4, 13, 5, 12, 6, 11, 7, 10, 8, 9];
 const PHYS_TO_NOC0_Y: &[u32] = &[0, 1, 11, 2, 10, 3, 9, 4, 8, 5, 7, 6];
 
-const GRID_SIZE_X: u8 = 17;
-const GRID_SIZE_Y: u8 = 12;
+pub(crate) const GRID_SIZE_X: u8 = 17;
+pub(crate) const GRID_SIZE_Y: u8 = 12;
 
 const NUM_TENSIX_ROWS: u32 = 10;
 const NUM_TENSIX_COLS: u32 = 14;
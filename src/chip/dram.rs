@@ -0,0 +1,101 @@
+//! DRAM channels and the NoC ports they are reached through.
+//!
+//! Wormhole and Blackhole channels each have three ports on the NoC, all reaching the same memory.
+//! Traffic only flows one way around each NoC's torus (east and south on NoC 0, west and north on
+//! NoC 1, which is why NoC 1 coordinates are flipped), so the port with the fewest hops from a
+//! tensix differs per core and per NoC.
+
+use super::noc::{NocId, Tile};
+
+/// Hops from `from` to `to` on `noc_id` within a `grid` sized torus.
+pub fn hops(grid: (u8, u8), noc_id: NocId, from: Tile, to: Tile) -> u32 {
+    let (from_x, from_y) = from.addr.get(noc_id);
+    let (to_x, to_y) = to.addr.get(noc_id);
+
+    let dx = (to_x as i32 - from_x as i32).rem_euclid(grid.0 as i32);
+    let dy = (to_y as i32 - from_y as i32).rem_euclid(grid.1 as i32);
+    (dx + dy) as u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DramChannel {
+    pub index: usize,
+    pub ports: Vec<Tile>,
+    /// Size of the NoC grid, unknown when the chip uses translated coordinates which say nothing
+    /// about where a tile physically is
+    pub grid: Option<(u8, u8)>,
+}
+
+impl DramChannel {
+    /// Hops from `from` to each port, `None` if the grid is unknown.
+    pub fn distances(&self, noc_id: NocId, from: Tile) -> Option<Vec<u32>> {
+        let grid = self.grid?;
+        Some(
+            self.ports
+                .iter()
+                .map(|port| hops(grid, noc_id, from, *port))
+                .collect(),
+        )
+    }
+
+    /// Index of the port closest to `from`, the first port wins ties and is used when the grid is
+    /// unknown.
+    pub fn nearest_port(&self, noc_id: NocId, from: Tile) -> usize {
+        self.distances(noc_id, from)
+            .and_then(|distances| {
+                distances
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(index, distance)| (**distance, *index))
+                    .map(|(index, _)| index)
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn nearest(&self, noc_id: NocId, from: Tile) -> Tile {
+        self.ports[self.nearest_port(noc_id, from)]
+    }
+}
+
+/// The preferred port of every channel for each of a set of cores.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortTable {
+    pub noc_id: NocId,
+    /// Per core, the port to use for each channel
+    pub cores: Vec<(Tile, Vec<Tile>)>,
+}
+
+impl PortTable {
+    pub fn new(channels: &[DramChannel], noc_id: NocId, cores: &[Tile]) -> Self {
+        let cores = cores
+            .iter()
+            .map(|core| {
+                let ports = channels
+                    .iter()
+                    .map(|channel| channel.nearest(noc_id, *core))
+                    .collect();
+                (*core, ports)
+            })
+            .collect();
+
+        Self { noc_id, cores }
+    }
+
+    pub fn ports(&self, core: Tile) -> Option<&[Tile]> {
+        self.cores
+            .iter()
+            .find(|(tile, _)| *tile == core)
+            .map(|(_, ports)| ports.as_slice())
+    }
+
+    /// The ports of `core` packed like [`super::noc::NocAddress`] into `u32`s, one per channel,
+    /// to be written into the core's runtime args.
+    pub fn args(&self, core: Tile) -> Option<Vec<u32>> {
+        Some(
+            self.ports(core)?
+                .iter()
+                .map(|port| port.addr.into())
+                .collect(),
+        )
+    }
+}
@@ -10,6 +10,7 @@ mod noc_endpoints;
 mod pci_noc;
 
 pub use arc::ArcMsg;
+pub(crate) use noc_endpoints::{GRID_SIZE_X, GRID_SIZE_Y};
 
 pub struct Grayskull {
     pub interface: PciNoc,
@@ -135,4 +136,25 @@ impl NocInterface for Grayskull {
         )
         .unwrap()
     }
+
+    fn noc_multicast(
+        &mut self,
+        noc_id: super::noc::NocId,
+        start: (u8, u8),
+        end: (u8, u8),
+        addr: u64,
+        data: &[u8],
+    ) {
+        super::noc::noc_multicast(
+            &mut self.interface.device,
+            &self.interface.tlb,
+            luwen::ttkmd_if::tlb::Ordering::STRICT,
+            noc_id,
+            start,
+            end,
+            addr,
+            data,
+        )
+        .unwrap()
+    }
 }
@@ -14,8 +14,8 @@ const DRAM_LOCATIONS: &[(u8, u8)] = &[
 ];
 const ARC_LOCATION: (u8, u8) = (0, 2);
 const PCI_LOCATION: (u8, u8) = (0, 4);
-const GRID_SIZE_X: u8 = 13;
-const GRID_SIZE_Y: u8 = 12;
+pub(crate) const GRID_SIZE_X: u8 = 13;
+pub(crate) const GRID_SIZE_Y: u8 = 12;
 const NUM_TENSIX_X: u8 = GRID_SIZE_X - 1;
 const NUM_TENSIX_Y: u8 = GRID_SIZE_Y - 2;
 
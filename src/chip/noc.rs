@@ -63,6 +63,16 @@ impl Into<u32> for Tile {
 }
 
 impl Tile {
+    /// A tile at `n0` on NOC0 and `n1` on NOC1, reads and writes aligned to `align_read` and
+    /// `align_write` bytes.
+    pub fn new(n0: (u8, u8), n1: (u8, u8), align_read: u8, align_write: u8) -> Self {
+        Self {
+            addr: NocAddress { n0, n1 },
+            align_read,
+            align_write,
+        }
+    }
+
     pub fn get(&self, noc_id: NocId) -> (u8, u8) {
         self.addr.get(noc_id)
     }
@@ -275,4 +285,14 @@ pub trait NocInterface {
 
     fn noc_broadcast(&mut self, noc_id: NocId, addr: u64, data: &[u8]);
     fn noc_broadcast32(&mut self, noc_id: NocId, addr: u64, value: u32);
+
+    /// Write to every tile in the rectangle from `start` to `end` (in `noc_id` coordinates)
+    fn noc_multicast(
+        &mut self,
+        noc_id: NocId,
+        start: (u8, u8),
+        end: (u8, u8),
+        addr: u64,
+        data: &[u8],
+    );
 }
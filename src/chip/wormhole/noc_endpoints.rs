@@ -29,8 +29,8 @@ const ETH_LOCATIONS: &[(u8, u8)] = &[
 const ARC_LOCATION: (u8, u8) = (0, 10);
 const PCI_LOCATION: (u8, u8) = (0, 3);
 
-const GRID_SIZE_X: u8 = 10;
-const GRID_SIZE_Y: u8 = 12;
+pub(crate) const GRID_SIZE_X: u8 = 10;
+pub(crate) const GRID_SIZE_Y: u8 = 12;
 const NUM_TENSIX_X: u8 = GRID_SIZE_X - 2;
 const NUM_TENSIX_Y: u8 = GRID_SIZE_Y - 2;
 
@@ -0,0 +1,390 @@
+//! Async waits on kernels, enabled with the `async` feature.
+//!
+//! A [`Driver`] owns every kernel being waited on and polls their status from a single thread,
+//! completing the matching futures as kernels finish. The futures only rely on their waker so they
+//! can be awaited from any executor. Dropping a future before it completes cancels the wait and
+//! puts the kernel's cores back into reset with [`loader::stop`].
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    kernel::{Kernel, KernelOutcome, WaitState, WAIT_POLL_INTERVAL},
+    loader,
+    program::{CoreOutcome, ProgramHandle},
+};
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    finished: bool,
+    cancelled: bool,
+}
+
+type SharedSlot<T> = Arc<Mutex<Slot<T>>>;
+
+fn finish<T>(slot: &SharedSlot<T>, value: T) {
+    let waker = {
+        let mut slot = slot.lock().unwrap();
+        slot.value = Some(value);
+        slot.finished = true;
+        slot.waker.take()
+    };
+
+    // Woken without holding the lock, the waker may poll the future straight away
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+trait Task: Send {
+    /// Poll the hardware once, returns true once the task is done with
+    fn step(&mut self) -> bool;
+}
+
+struct KernelTask {
+    kernel: Option<Kernel>,
+    wait: WaitState,
+    slot: SharedSlot<(Kernel, KernelOutcome)>,
+}
+
+impl Task for KernelTask {
+    fn step(&mut self) -> bool {
+        let Some(kernel) = self.kernel.as_mut() else {
+            return true;
+        };
+
+        if self.slot.lock().unwrap().cancelled {
+            tracing::debug!("{:?}: wait cancelled, stopping core", kernel.core);
+            loader::stop(&mut kernel.device, kernel.core);
+            return true;
+        }
+
+        let Kernel {
+            device,
+            noc_id,
+            core,
+            data,
+            ..
+        } = kernel;
+        match data.poll_wait(device, *noc_id, *core, &mut self.wait) {
+            Some(outcome) => {
+                let mut kernel = self.kernel.take().unwrap();
+                kernel.outcome = Some(outcome.clone());
+                finish(&self.slot, (kernel, outcome));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct FnTask<T, P, C> {
+    poll: P,
+    cancel: Option<C>,
+    slot: SharedSlot<T>,
+}
+
+impl<T, P, C> Task for FnTask<T, P, C>
+where
+    T: Send,
+    P: FnMut() -> Option<T> + Send,
+    C: FnOnce() + Send,
+{
+    fn step(&mut self) -> bool {
+        let Some(cancel) = self.cancel.take() else {
+            return true;
+        };
+
+        if self.slot.lock().unwrap().cancelled {
+            cancel();
+            return true;
+        }
+
+        match (self.poll)() {
+            Some(value) => {
+                finish(&self.slot, value);
+                true
+            }
+            None => {
+                self.cancel = Some(cancel);
+                false
+            }
+        }
+    }
+}
+
+struct ProgramTask {
+    handle: Option<ProgramHandle>,
+    /// (group, core index, wait state, outcome) of every core
+    cores: Vec<(usize, usize, WaitState, Option<KernelOutcome>)>,
+    slot: SharedSlot<(ProgramHandle, Vec<CoreOutcome>)>,
+}
+
+impl Task for ProgramTask {
+    fn step(&mut self) -> bool {
+        let Some(handle) = self.handle.as_mut() else {
+            return true;
+        };
+
+        if self.slot.lock().unwrap().cancelled {
+            tracing::debug!("program wait cancelled, stopping cores");
+            for (cores, _) in &handle.groups {
+                for tile in cores.tiles() {
+                    loader::stop(&mut handle.device, *tile);
+                }
+            }
+            return true;
+        }
+
+        for (group, index, wait, outcome) in self.cores.iter_mut() {
+            if outcome.is_some() {
+                continue;
+            }
+
+            let (cores, data) = &mut handle.groups[*group];
+            let tile = cores.tiles()[*index];
+            *outcome = data.poll_wait(&mut handle.device, handle.noc_id, tile, wait);
+        }
+
+        if self.cores.iter().any(|v| v.3.is_none()) {
+            return false;
+        }
+
+        let mut handle = self.handle.take().unwrap();
+        handle.outcomes = self
+            .cores
+            .drain(..)
+            .map(|(group, index, _, outcome)| CoreOutcome {
+                group,
+                tile: handle.groups[group].0.tiles()[index],
+                outcome: outcome.unwrap(),
+            })
+            .collect();
+        let outcomes = handle.outcomes.clone();
+        finish(&self.slot, (handle, outcomes));
+
+        true
+    }
+}
+
+/// The result of an async wait. Dropping it before it completes cancels the wait and stops the
+/// cores being waited on.
+pub struct AsyncWait<T> {
+    slot: SharedSlot<T>,
+    // Keeps the driver polling for as long as someone is waiting
+    _driver: Driver,
+}
+
+impl<T> AsyncWait<T> {
+    /// Stop waiting and put the cores back into reset.
+    pub fn cancel(self) {}
+}
+
+impl<T> Future for AsyncWait<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for AsyncWait<T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.lock().unwrap();
+        if !slot.finished {
+            slot.cancelled = true;
+        }
+    }
+}
+
+struct DriverInner {
+    tasks: Mutex<Vec<Box<dyn Task>>>,
+    added: Condvar,
+}
+
+/// Polls the status of every kernel being waited on.
+///
+/// A driver made with [`Driver::new`] does nothing on its own, [`Driver::poll`] has to be called
+/// periodically (e.g. from an executor's timer). [`Driver::spawn`] polls from a background thread.
+#[derive(Clone)]
+pub struct Driver {
+    inner: Arc<DriverInner>,
+}
+
+impl Default for Driver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Driver {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(DriverInner {
+                tasks: Mutex::new(Vec::new()),
+                added: Condvar::new(),
+            }),
+        }
+    }
+
+    /// A driver polling every `interval` from its own thread. The thread exits once the driver and
+    /// all of its waits have been dropped and any cancelled waits have stopped their cores.
+    pub fn spawn(interval: Duration) -> Self {
+        let driver = Self::new();
+        let thread = driver.clone();
+        std::thread::Builder::new()
+            .name("ttx-driver".to_string())
+            .spawn(move || thread.run(interval))
+            .unwrap();
+
+        driver
+    }
+
+    /// The driver used by [`Kernel::wait_async`] and [`ProgramHandle::wait_async`].
+    pub fn global() -> &'static Driver {
+        static DRIVER: OnceLock<Driver> = OnceLock::new();
+        DRIVER.get_or_init(|| Self::spawn(WAIT_POLL_INTERVAL))
+    }
+
+    fn run(self, interval: Duration) {
+        loop {
+            let start = Instant::now();
+            if self.poll() > 0 {
+                std::thread::sleep(interval.saturating_sub(start.elapsed()));
+                continue;
+            }
+
+            // Only this thread is left and there is nothing more to poll
+            if Arc::strong_count(&self.inner) == 1 {
+                break;
+            }
+
+            // Sleep until a wait is added, checking back in case the driver was dropped
+            let tasks = self.inner.tasks.lock().unwrap();
+            if tasks.is_empty() {
+                let _ = self
+                    .inner
+                    .added
+                    .wait_timeout(tasks, Duration::from_millis(100));
+            }
+        }
+    }
+
+    /// Poll every kernel once, returns how many are still running.
+    pub fn poll(&self) -> usize {
+        let mut tasks = std::mem::take(&mut *self.inner.tasks.lock().unwrap());
+        tasks.retain_mut(|task| !task.step());
+
+        let mut pending = self.inner.tasks.lock().unwrap();
+        // Waits added while polling go after the ones that were already there
+        tasks.append(&mut pending);
+        *pending = tasks;
+
+        pending.len()
+    }
+
+    fn push<T>(&self, task: impl FnOnce(SharedSlot<T>) -> Box<dyn Task>) -> AsyncWait<T> {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            waker: None,
+            finished: false,
+            cancelled: false,
+        }));
+
+        self.inner.tasks.lock().unwrap().push(task(slot.clone()));
+        self.inner.added.notify_all();
+
+        AsyncWait {
+            slot,
+            _driver: self.clone(),
+        }
+    }
+
+    /// Wait on `kernel`, see [`Kernel::wait_for`] for `timeout` and `hang_polls`.
+    pub fn wait_kernel(
+        &self,
+        kernel: Kernel,
+        timeout: Option<Duration>,
+        hang_polls: Option<usize>,
+    ) -> AsyncWait<(Kernel, KernelOutcome)> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.push(|slot| {
+            Box::new(KernelTask {
+                kernel: Some(kernel),
+                wait: WaitState::new(deadline, hang_polls),
+                slot,
+            })
+        })
+    }
+
+    /// Wait on anything that can be polled. `poll` is called by the driver until it returns a value,
+    /// `cancel` is called instead if the wait is dropped before then.
+    pub fn wait_with<T: Send + 'static>(
+        &self,
+        poll: impl FnMut() -> Option<T> + Send + 'static,
+        cancel: impl FnOnce() + Send + 'static,
+    ) -> AsyncWait<T> {
+        self.push(|slot| {
+            Box::new(FnTask {
+                poll,
+                cancel: Some(cancel),
+                slot,
+            })
+        })
+    }
+
+    /// Wait on every core of `handle`, the outcomes are also left in `handle.outcomes`.
+    pub fn wait_program(
+        &self,
+        handle: ProgramHandle,
+        timeout: Option<Duration>,
+        hang_polls: Option<usize>,
+    ) -> AsyncWait<(ProgramHandle, Vec<CoreOutcome>)> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let cores = handle
+            .groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, (cores, _))| {
+                (0..cores.len())
+                    .map(move |index| (group, index, WaitState::new(deadline, hang_polls), None))
+            })
+            .collect();
+
+        self.push(|slot| {
+            Box::new(ProgramTask {
+                handle: Some(handle),
+                cores,
+                slot,
+            })
+        })
+    }
+}
+
+impl Kernel {
+    /// Wait for the kernel without blocking, polled by the global [`Driver`]. The kernel is
+    /// handed back along with its outcome.
+    pub fn wait_async(self) -> AsyncWait<(Kernel, KernelOutcome)> {
+        Driver::global().wait_kernel(self, None, None)
+    }
+}
+
+impl ProgramHandle {
+    /// Wait for every core of the program without blocking, polled by the global [`Driver`].
+    pub fn wait_async(self) -> AsyncWait<(ProgramHandle, Vec<CoreOutcome>)> {
+        Driver::global().wait_program(self, None, None)
+    }
+}
@@ -0,0 +1,538 @@
+//! A GDB remote serial protocol server for the RISCs of a tile.
+//!
+//! Each RISC is a GDB thread, numbered from 1 in [`RiscId::ALL`] order. The server runs all-stop:
+//! continuing resumes every RISC, and as soon as one halts the others are halted too. Memory is
+//! accessed over the NoC and software breakpoints are `ebreak`s written into L1, which are hidden
+//! from memory reads. The kernel's elf path is served so `gdb` can be attached without a file.
+//!
+//! ```text
+//! riscv64-unknown-elf-gdb -ex "target remote localhost:3333"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::kernel::{diagnose::DiagnoseError, Kernel, RiscId};
+
+pub mod packet;
+pub mod target;
+
+use packet::{from_hex, parse_hex, to_hex, Incoming, PacketReader};
+pub use target::{DebugTarget, KernelTarget};
+use target::{PC_REGISTER, REGISTER_COUNT};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GdbError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("can't debug the tile: {0}")]
+    Target(#[from] DiagnoseError),
+}
+
+const EBREAK: u32 = 0x0010_0073;
+const C_EBREAK: u16 = 0x9002;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0"><architecture>riscv:rv32</architecture></target>"#;
+
+/// Largest packet gdb may send or expect back, advertised in `qSupported`
+const PACKET_SIZE: usize = 0x4000;
+
+/// How often the RISCs are checked while continuing
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+pub fn thread_id(risc: RiscId) -> usize {
+    RiscId::ALL.iter().position(|v| *v == risc).unwrap() + 1
+}
+
+pub fn thread_risc(id: usize) -> Option<RiscId> {
+    RiscId::ALL.get(id.checked_sub(1)?).copied()
+}
+
+enum Action {
+    Reply(Vec<u8>),
+    Continue,
+    Step(RiscId),
+    Detach,
+    Kill,
+}
+
+fn reply(data: impl AsRef<[u8]>) -> Action {
+    Action::Reply(data.as_ref().to_vec())
+}
+
+pub struct GdbServer<T: DebugTarget> {
+    target: T,
+    elf: Option<PathBuf>,
+    /// Original bytes under each software breakpoint
+    breakpoints: BTreeMap<u64, Vec<u8>>,
+    thread: RiscId,
+    no_ack: bool,
+    last_sent: Vec<u8>,
+    last_stop: (u8, RiscId),
+}
+
+impl<T: DebugTarget> GdbServer<T> {
+    pub fn new(target: T) -> Self {
+        let thread = target.riscs().first().copied().unwrap_or(RiscId::Brisc);
+        Self {
+            target,
+            elf: None,
+            breakpoints: BTreeMap::new(),
+            thread,
+            no_ack: false,
+            last_sent: Vec::new(),
+            last_stop: (SIGTRAP, thread),
+        }
+    }
+
+    /// The elf served to gdb for symbols.
+    pub fn elf(mut self, path: impl Into<PathBuf>) -> Self {
+        self.elf = Some(path.into());
+        self
+    }
+
+    pub fn into_target(self) -> T {
+        self.target
+    }
+
+    /// Accept a single connection on `listener` and serve it until gdb detaches.
+    pub fn serve(&mut self, listener: &TcpListener) -> std::io::Result<()> {
+        let (stream, peer) = listener.accept()?;
+        tracing::info!("gdb connected from {peer}");
+        self.serve_stream(stream)
+    }
+
+    pub fn serve_stream(&mut self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(true)?;
+        self.no_ack = false;
+
+        // gdb expects the target to be stopped when it attaches
+        self.halt_all();
+
+        let mut reader = PacketReader::new();
+        let mut buf = [0; 4096];
+        let result = 'session: loop {
+            while let Some(incoming) = reader.next() {
+                let packet = match incoming {
+                    Incoming::Packet(packet) => packet,
+                    Incoming::Ack => continue,
+                    Incoming::Nack => {
+                        stream.write_all(&self.last_sent)?;
+                        continue;
+                    }
+                    Incoming::Corrupt => {
+                        stream.write_all(b"-")?;
+                        continue;
+                    }
+                    Incoming::Interrupt => {
+                        self.halt_all();
+                        let stop = self.stop_reply(SIGINT, self.thread);
+                        self.send(&mut stream, stop)?;
+                        continue;
+                    }
+                };
+                if !self.no_ack {
+                    stream.write_all(b"+")?;
+                }
+
+                tracing::trace!("gdb: {}", String::from_utf8_lossy(&packet));
+                match self.handle(&packet) {
+                    Action::Reply(data) => self.send(&mut stream, data)?,
+                    Action::Continue => {
+                        let stop = self.run(&mut stream, &mut reader)?;
+                        self.send(&mut stream, stop)?;
+                    }
+                    Action::Step(risc) => {
+                        self.target.step(risc);
+                        self.thread = risc;
+                        let stop = self.stop_reply(SIGTRAP, risc);
+                        self.send(&mut stream, stop)?;
+                    }
+                    Action::Detach => {
+                        self.send(&mut stream, "OK")?;
+                        break 'session Ok(());
+                    }
+                    Action::Kill => break 'session Ok(()),
+                }
+            }
+
+            match stream.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(len) => reader.push(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        self.detach();
+        tracing::info!("gdb disconnected");
+        result
+    }
+
+    fn send(&mut self, stream: &mut TcpStream, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+        self.last_sent = packet::encode(data.as_ref());
+        stream.write_all(&self.last_sent)
+    }
+
+    fn halt_all(&mut self) {
+        for risc in self.target.riscs() {
+            if !self.target.halted(risc) {
+                self.target.halt(risc);
+            }
+        }
+    }
+
+    /// Remove every breakpoint and let the RISCs run on without the debugger.
+    fn detach(&mut self) {
+        for (addr, original) in std::mem::take(&mut self.breakpoints) {
+            self.target.write_memory(addr, &original);
+        }
+        for risc in self.target.riscs() {
+            if self.target.halted(risc) {
+                self.target.resume(risc);
+            }
+        }
+    }
+
+    /// Resume every RISC until one halts or gdb interrupts, returns the stop reply.
+    fn run(
+        &mut self,
+        stream: &mut TcpStream,
+        reader: &mut PacketReader,
+    ) -> std::io::Result<String> {
+        let riscs = self.target.riscs();
+        for risc in &riscs {
+            self.target.resume(*risc);
+        }
+
+        stream.set_nonblocking(true)?;
+        let mut buf = [0; 64];
+        let stop = loop {
+            if let Some(risc) = riscs.iter().copied().find(|v| self.target.halted(*v)) {
+                break (SIGTRAP, risc);
+            }
+
+            // Only an interrupt means anything while running
+            if reader.any(|v| v == Incoming::Interrupt) {
+                break (SIGINT, self.thread);
+            }
+
+            match stream.read(&mut buf) {
+                Ok(0) => break (SIGINT, self.thread),
+                Ok(len) => {
+                    reader.push(&buf[..len]);
+                    continue;
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => {
+                    stream.set_nonblocking(false)?;
+                    return Err(err);
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+        stream.set_nonblocking(false)?;
+
+        self.halt_all();
+        self.thread = stop.1;
+        Ok(self.stop_reply(stop.0, stop.1))
+    }
+
+    fn stop_reply(&mut self, signal: u8, risc: RiscId) -> String {
+        self.last_stop = (signal, risc);
+        let mut reply = format!("T{signal:02x}thread:{:x};", thread_id(risc));
+        if signal == SIGTRAP {
+            let pc = self.target.read_register(risc, PC_REGISTER) as u64;
+            if self.breakpoints.contains_key(&pc) {
+                reply.push_str("swbreak:;");
+            }
+        }
+        reply
+    }
+
+    /// The end of `addr..addr + len`, if all of it is within the target's memory.
+    fn memory_end(&self, addr: u64, len: usize) -> Option<u64> {
+        let end = addr.checked_add(len as u64)?;
+        (end <= self.target.memory_size()).then_some(end)
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Option<Vec<u8>> {
+        let end = self.memory_end(addr, len)?;
+        let mut data = self.target.read_memory(addr, len);
+        // Show the original instructions under breakpoints
+        for (bp, original) in self.breakpoints.range(addr.saturating_sub(4)..end) {
+            for (offset, byte) in original.iter().enumerate() {
+                let at = bp + offset as u64;
+                if (addr..end).contains(&at) {
+                    data[(at - addr) as usize] = *byte;
+                }
+            }
+        }
+        Some(data)
+    }
+
+    fn thread_arg(&self, arg: &str) -> Option<RiscId> {
+        match arg {
+            "0" | "-1" => Some(self.thread),
+            id => thread_risc(parse_hex(id)? as usize).filter(|v| self.target.riscs().contains(v)),
+        }
+    }
+
+    fn xfer(data: &[u8], args: &str) -> Action {
+        let Some((offset, len)) = args.split_once(',') else {
+            return reply("E01");
+        };
+        let (Some(offset), Some(len)) = (parse_hex(offset), parse_hex(len)) else {
+            return reply("E01");
+        };
+
+        let start = (offset as usize).min(data.len());
+        let Some(end) = start.checked_add(len as usize) else {
+            return reply("E01");
+        };
+        let end = end.min(data.len());
+        let mut out = vec![if end == data.len() { b'l' } else { b'm' }];
+        out.extend(&data[start..end]);
+        Action::Reply(out)
+    }
+
+    fn handle(&mut self, packet: &[u8]) -> Action {
+        let packet = String::from_utf8_lossy(packet);
+        let packet = packet.as_ref();
+
+        let Some(command) = packet.chars().next() else {
+            return reply("");
+        };
+        let args = &packet[command.len_utf8()..];
+
+        match command {
+            '?' => reply(self.stop_reply(self.last_stop.0, self.last_stop.1)),
+            'q' => self.query(args),
+            'Q' if args == "StartNoAckMode" => {
+                self.no_ack = true;
+                reply("OK")
+            }
+            'H' => {
+                let Some(risc) = args.get(1..).and_then(|v| self.thread_arg(v)) else {
+                    return reply("E01");
+                };
+                self.thread = risc;
+                reply("OK")
+            }
+            'T' => match self.thread_arg(args) {
+                Some(_) => reply("OK"),
+                None => reply("E01"),
+            },
+            'g' => {
+                let data = (0..REGISTER_COUNT)
+                    .flat_map(|reg| self.target.read_register(self.thread, reg).to_le_bytes())
+                    .collect::<Vec<_>>();
+                reply(to_hex(&data))
+            }
+            'G' => {
+                let Some(data) = from_hex(args) else {
+                    return reply("E01");
+                };
+                for (reg, value) in data.chunks_exact(4).enumerate().take(REGISTER_COUNT) {
+                    let value = u32::from_le_bytes(value.try_into().unwrap());
+                    self.target.write_register(self.thread, reg, value);
+                }
+                reply("OK")
+            }
+            'p' => match parse_hex(args).map(|v| v as usize) {
+                Some(reg) if reg < REGISTER_COUNT => reply(to_hex(
+                    &self.target.read_register(self.thread, reg).to_le_bytes(),
+                )),
+                _ => reply("E01"),
+            },
+            'P' => {
+                let parsed = args.split_once('=').and_then(|(reg, value)| {
+                    let value = from_hex(value).filter(|v| v.len() == 4)?;
+                    Some((
+                        parse_hex(reg)? as usize,
+                        u32::from_le_bytes(value.try_into().ok()?),
+                    ))
+                });
+                match parsed {
+                    Some((reg, value)) if reg < REGISTER_COUNT => {
+                        self.target.write_register(self.thread, reg, value);
+                        reply("OK")
+                    }
+                    _ => reply("E01"),
+                }
+            }
+            'm' => {
+                let parsed = args
+                    .split_once(',')
+                    .and_then(|(addr, len)| Some((parse_hex(addr)?, parse_hex(len)?)));
+                // Each byte takes two characters of the reply
+                let data = parsed.and_then(|(addr, len)| {
+                    self.read_memory(addr, (len as usize).min(PACKET_SIZE / 2))
+                });
+                match data {
+                    Some(data) => reply(to_hex(&data)),
+                    None => reply("E01"),
+                }
+            }
+            'M' => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = range.split_once(',')?;
+                    let data = from_hex(data)?;
+                    (parse_hex(len)? as usize == data.len()).then_some((parse_hex(addr)?, data))
+                });
+                match parsed {
+                    Some((addr, data)) if self.memory_end(addr, data.len()).is_some() => {
+                        self.target.write_memory(addr, &data);
+                        reply("OK")
+                    }
+                    _ => reply("E01"),
+                }
+            }
+            'c' => {
+                if let Some(addr) = parse_hex(args) {
+                    self.target
+                        .write_register(self.thread, PC_REGISTER, addr as u32);
+                }
+                Action::Continue
+            }
+            's' => {
+                if let Some(addr) = parse_hex(args) {
+                    self.target
+                        .write_register(self.thread, PC_REGISTER, addr as u32);
+                }
+                Action::Step(self.thread)
+            }
+            'v' => self.v_packet(args),
+            'Z' | 'z' => self.breakpoint(command == 'Z', args),
+            'D' => Action::Detach,
+            'k' => Action::Kill,
+            _ => reply(""),
+        }
+    }
+
+    fn query(&mut self, args: &str) -> Action {
+        let (name, rest) = args.split_once(':').unwrap_or((args, ""));
+        match name {
+            "Supported" => reply(format!(
+                "PacketSize={PACKET_SIZE:x};QStartNoAckMode+;swbreak+;vContSupported+;\
+                     qXfer:features:read+;qXfer:exec-file:read+"
+            )),
+            "Attached" => reply("1"),
+            "C" => reply(format!("QC{:x}", thread_id(self.thread))),
+            "fThreadInfo" => {
+                let threads = self
+                    .target
+                    .riscs()
+                    .into_iter()
+                    .map(|risc| format!("{:x}", thread_id(risc)))
+                    .collect::<Vec<_>>();
+                reply(format!("m{}", threads.join(",")))
+            }
+            "sThreadInfo" => reply("l"),
+            _ if name.starts_with("ThreadExtraInfo,") => {
+                match self.thread_arg(&name["ThreadExtraInfo,".len()..]) {
+                    Some(risc) => reply(to_hex(risc.name().as_bytes())),
+                    None => reply("E01"),
+                }
+            }
+            "Xfer" => {
+                let mut parts = rest.splitn(4, ':');
+                match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some("features"), Some("read"), Some("target.xml"), Some(range)) => {
+                        Self::xfer(TARGET_XML.as_bytes(), range)
+                    }
+                    (Some("exec-file"), Some("read"), Some(_), Some(range)) => match &self.elf {
+                        Some(elf) => Self::xfer(elf.to_string_lossy().as_bytes(), range),
+                        None => reply("E01"),
+                    },
+                    _ => reply(""),
+                }
+            }
+            _ => reply(""),
+        }
+    }
+
+    fn v_packet(&mut self, args: &str) -> Action {
+        if args == "Cont?" {
+            return reply("vCont;c;C;s;S");
+        }
+        let Some(actions) = args.strip_prefix("Cont;") else {
+            return reply("");
+        };
+
+        // All-stop: a step on any thread takes priority, everything else continues
+        for action in actions.split(';') {
+            let (action, thread) = action.split_once(':').unwrap_or((action, "-1"));
+            if action.starts_with('s') || action.starts_with('S') {
+                let Some(risc) = self.thread_arg(thread) else {
+                    return reply("E01");
+                };
+                return Action::Step(risc);
+            }
+        }
+
+        Action::Continue
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Action {
+        let mut parts = args.split(',');
+        let (Some("0"), Some(addr), Some(kind)) = (parts.next(), parts.next(), parts.next()) else {
+            // Only software breakpoints are supported
+            return reply("");
+        };
+        let (Some(addr), Some(kind)) = (parse_hex(addr), parse_hex(kind)) else {
+            return reply("E01");
+        };
+
+        if insert {
+            if self.breakpoints.contains_key(&addr) {
+                return reply("OK");
+            }
+            let instruction = match kind {
+                2 => C_EBREAK.to_le_bytes().to_vec(),
+                _ => EBREAK.to_le_bytes().to_vec(),
+            };
+            if self.memory_end(addr, instruction.len()).is_none() {
+                return reply("E01");
+            }
+            let original = self.target.read_memory(addr, instruction.len());
+            self.target.write_memory(addr, &instruction);
+            self.breakpoints.insert(addr, original);
+        } else if let Some(original) = self.breakpoints.remove(&addr) {
+            self.target.write_memory(addr, &original);
+        }
+
+        reply("OK")
+    }
+}
+
+impl Kernel {
+    /// Serve gdb on `addr` until it detaches. The RISCs are halted while gdb is attached and left
+    /// running afterwards.
+    pub fn serve_gdb(&mut self, addr: impl ToSocketAddrs) -> Result<(), GdbError> {
+        let elf = self.data.build.as_ref().and_then(|v| v.elf.clone());
+        let device = self.device.to_string();
+        let core = self.core;
+        let mut server = GdbServer::new(KernelTarget::new(self)?);
+        if let Some(elf) = elf {
+            server = server.elf(elf);
+        }
+
+        let listener = TcpListener::bind(addr)?;
+        tracing::info!(
+            "{device}: gdb server for {core:?} listening on {}",
+            listener.local_addr()?
+        );
+        Ok(server.serve(&listener)?)
+    }
+}
@@ -0,0 +1,125 @@
+//! Framing of GDB remote serial protocol packets.
+
+pub const INTERRUPT: u8 = 0x03;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Incoming {
+    Packet(Vec<u8>),
+    Ack,
+    /// The last packet sent has to be sent again
+    Nack,
+    Interrupt,
+    /// A packet with a bad checksum
+    Corrupt,
+}
+
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, v| sum.wrapping_add(*v))
+}
+
+/// Frame `data` as `$data#xx`, escaping the characters that can't appear in a packet.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len());
+    for &byte in data {
+        if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+            body.extend([b'}', byte ^ 0x20]);
+        } else {
+            body.push(byte);
+        }
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 4);
+    out.push(b'$');
+    out.extend(&body);
+    out.extend(format!("#{:02x}", checksum(&body)).as_bytes());
+    out
+}
+
+/// Splits a byte stream into packets, acks and interrupts.
+#[derive(Default)]
+pub struct PacketReader {
+    buf: Vec<u8>,
+}
+
+impl PacketReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend(data);
+    }
+}
+
+/// Yields what has been pushed so far, until more is needed.
+impl Iterator for PacketReader {
+    type Item = Incoming;
+
+    fn next(&mut self) -> Option<Incoming> {
+        loop {
+            let (&first, _) = self.buf.split_first()?;
+            match first {
+                b'+' => {
+                    self.buf.remove(0);
+                    return Some(Incoming::Ack);
+                }
+                b'-' => {
+                    self.buf.remove(0);
+                    return Some(Incoming::Nack);
+                }
+                INTERRUPT => {
+                    self.buf.remove(0);
+                    return Some(Incoming::Interrupt);
+                }
+                b'$' => break,
+                // Line noise between packets
+                _ => {
+                    self.buf.remove(0);
+                }
+            }
+        }
+
+        let end = self.buf.iter().position(|v| *v == b'#')?;
+        if self.buf.len() < end + 3 {
+            return None;
+        }
+
+        let packet = self.buf.drain(..end + 3).collect::<Vec<_>>();
+        let body = &packet[1..end];
+        let expected = std::str::from_utf8(&packet[end + 1..])
+            .ok()
+            .and_then(|v| u8::from_str_radix(v, 16).ok());
+        if expected != Some(checksum(body)) {
+            return Some(Incoming::Corrupt);
+        }
+
+        let mut data = Vec::with_capacity(body.len());
+        let mut bytes = body.iter();
+        while let Some(&byte) = bytes.next() {
+            match byte {
+                b'}' => data.push(bytes.next().copied().unwrap_or(0) ^ 0x20),
+                byte => data.push(byte),
+            }
+        }
+
+        Some(Incoming::Packet(data))
+    }
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|v| format!("{v:02x}")).collect()
+}
+
+pub fn from_hex(data: &str) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..data.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(data.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn parse_hex(data: &str) -> Option<u64> {
+    u64::from_str_radix(data, 16).ok()
+}
@@ -0,0 +1,160 @@
+//! The cores a GDB session controls.
+//!
+//! [`KernelTarget`] drives the RISCs of a kernel's tile through the tensix debug interface, where
+//! each RISC has a small block of debug registers reached indirectly through the
+//! [`DebugRegisters`] of the chip's arch. Software breakpoints are `ebreak` instructions, which halt a RISC into
+//! debug mode.
+
+use crate::kernel::{
+    diagnose::{
+        read_halted, read_pc, risc_dbg_read, risc_dbg_write, ChipTile, DebugRegisters,
+        DiagnoseError,
+    },
+    read_aligned, write_aligned, Kernel, RiscId,
+};
+
+/// x0-x31 followed by the pc
+pub const REGISTER_COUNT: usize = 33;
+pub const PC_REGISTER: usize = 32;
+
+/// Operations a GDB session needs from the cores of a tile. Registers are numbered the way GDB
+/// numbers them for rv32.
+pub trait DebugTarget {
+    /// The RISCs exposed as threads
+    fn riscs(&self) -> Vec<RiscId>;
+
+    fn halt(&mut self, risc: RiscId);
+    fn resume(&mut self, risc: RiscId);
+    /// Execute a single instruction on a halted RISC
+    fn step(&mut self, risc: RiscId);
+    fn halted(&mut self, risc: RiscId) -> bool;
+
+    fn read_register(&mut self, risc: RiscId, reg: usize) -> u32;
+    fn write_register(&mut self, risc: RiscId, reg: usize, value: u32);
+
+    /// Memory gdb can access starts at 0 and ends here, e.g. the size of L1
+    fn memory_size(&self) -> u64;
+    fn read_memory(&mut self, addr: u64, len: usize) -> Vec<u8>;
+    fn write_memory(&mut self, addr: u64, data: &[u8]);
+}
+
+/// Debug registers of each RISC, after its status register
+const REG_COMMAND: u32 = 1;
+const REG_COMMAND_ARG_0: u32 = 2;
+const REG_COMMAND_ARG_1: u32 = 3;
+const REG_COMMAND_RETURN_VALUE: u32 = 4;
+
+const COMMAND_DEBUG_MODE: u32 = 0x8000_0000;
+const COMMAND_HALT: u32 = COMMAND_DEBUG_MODE | 0x2;
+const COMMAND_STEP: u32 = COMMAND_DEBUG_MODE | 0x3;
+const COMMAND_CONTINUE: u32 = COMMAND_DEBUG_MODE | 0x4;
+const COMMAND_READ_REGISTER: u32 = COMMAND_DEBUG_MODE | 0x8;
+const COMMAND_WRITE_REGISTER: u32 = COMMAND_DEBUG_MODE | 0x9;
+
+/// The RISCs of a kernel's tile.
+pub struct KernelTarget<'a> {
+    kernel: &'a mut Kernel,
+    layout: DebugRegisters,
+}
+
+impl<'a> KernelTarget<'a> {
+    /// Fails if the debug registers of the kernel's arch aren't known.
+    pub fn new(kernel: &'a mut Kernel) -> Result<Self, DiagnoseError> {
+        let layout = DebugRegisters::for_arch(kernel.device.arch())?;
+        Ok(Self { kernel, layout })
+    }
+
+    fn regs(&mut self) -> ChipTile<'_> {
+        ChipTile {
+            chip: &mut self.kernel.device,
+            noc_id: self.kernel.noc_id,
+            tile: self.kernel.core,
+        }
+    }
+
+    fn debug_write(&mut self, risc: RiscId, reg: u32, value: u32) {
+        let layout = self.layout;
+        risc_dbg_write(&mut self.regs(), &layout, risc, reg, value);
+    }
+
+    fn debug_read(&mut self, risc: RiscId, reg: u32) -> u32 {
+        let layout = self.layout;
+        risc_dbg_read(&mut self.regs(), &layout, risc, reg)
+    }
+
+    fn command(&mut self, risc: RiscId, command: u32) {
+        self.debug_write(risc, REG_COMMAND, command);
+    }
+}
+
+impl DebugTarget for KernelTarget<'_> {
+    fn riscs(&self) -> Vec<RiscId> {
+        RiscId::ALL.to_vec()
+    }
+
+    fn halt(&mut self, risc: RiscId) {
+        self.command(risc, COMMAND_HALT);
+    }
+
+    fn resume(&mut self, risc: RiscId) {
+        self.command(risc, COMMAND_CONTINUE);
+    }
+
+    fn step(&mut self, risc: RiscId) {
+        self.command(risc, COMMAND_STEP);
+    }
+
+    fn halted(&mut self, risc: RiscId) -> bool {
+        let layout = self.layout;
+        read_halted(&mut self.regs(), &layout, risc)
+    }
+
+    fn read_register(&mut self, risc: RiscId, reg: usize) -> u32 {
+        if reg == 0 {
+            return 0;
+        }
+        // The debug bus has the pc of a running RISC, the register file can only be read halted
+        if reg == PC_REGISTER && !self.halted(risc) {
+            let layout = self.layout;
+            return read_pc(&mut self.regs(), &layout, risc) as u32;
+        }
+
+        self.debug_write(risc, REG_COMMAND_ARG_0, reg as u32);
+        self.command(risc, COMMAND_READ_REGISTER);
+        self.debug_read(risc, REG_COMMAND_RETURN_VALUE)
+    }
+
+    fn write_register(&mut self, risc: RiscId, reg: usize, value: u32) {
+        if reg == 0 {
+            return;
+        }
+
+        self.debug_write(risc, REG_COMMAND_ARG_0, reg as u32);
+        self.debug_write(risc, REG_COMMAND_ARG_1, value);
+        self.command(risc, COMMAND_WRITE_REGISTER);
+    }
+
+    fn memory_size(&self) -> u64 {
+        self.kernel.device.tensix_l1()
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Vec<u8> {
+        let Kernel {
+            device,
+            noc_id,
+            core,
+            ..
+        } = self.kernel;
+        read_aligned(device, *noc_id, *core, addr, len)
+    }
+
+    fn write_memory(&mut self, addr: u64, data: &[u8]) {
+        let Kernel {
+            device,
+            noc_id,
+            core,
+            ..
+        } = self.kernel;
+        write_aligned(device, *noc_id, *core, addr, data);
+    }
+}
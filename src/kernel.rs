@@ -1,10 +1,35 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use bytemuck::Pod;
 
 use crate::{
     chip::noc::{NocAddress, NocId, NocInterface, Tile},
-    Chip,
+    loader::ElfTargetError,
+    Arch, Chip,
 };
 
+pub mod diagnose;
+pub mod dwarf;
+pub mod image;
+pub mod log;
+pub mod panic;
+pub mod profile;
+pub mod status;
+pub mod verify;
+
+pub use diagnose::{DiagnoseError, Diagnosis};
+pub use dwarf::{DebugInfo, Global, InspectError, Inspection, Value};
+pub use image::ImageError;
+pub use log::{LogFormats, LogLevel, LogRecord};
+pub use panic::{PanicReport, StackFrame};
+pub use profile::{MarkerKind, ProfileBuffer, ProfileMarker};
+pub use status::{CoreStatus, RiscId, RiscState, RiscStatus};
+pub use verify::{Verify, VerifyError, VerifyPolicy};
+
 #[derive(Clone)]
 #[repr(align(16))]
 pub struct Alignment16(pub Box<[u8]>);
@@ -13,12 +38,36 @@ pub struct Alignment16(pub Box<[u8]>);
 pub struct KernelBytes {
     pub addr: u32,
     pub data: Alignment16,
+    /// The segment can be modified by the kernel (.data/.bss) and is restored on relaunch
+    pub writable: bool,
+    /// Bytes after `data` that start out zeroed (.bss), these are not part of the initial load
+    pub zeroed: u32,
+    crc: OnceLock<u32>,
 }
 
 impl KernelBytes {
+    pub fn new(addr: u32, data: Vec<u8>, writable: bool, zeroed: u32) -> Self {
+        Self {
+            addr,
+            data: Alignment16(data.into_boxed_slice()),
+            writable,
+            zeroed,
+            crc: OnceLock::new(),
+        }
+    }
+
+    /// Crc of `data`, only computed once
+    pub fn crc(&self) -> u32 {
+        *self.crc.get_or_init(|| crc32fast::hash(&self.data.0))
+    }
+
     pub fn len(&self) -> usize {
         self.data.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.0.is_empty()
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -27,13 +76,8 @@ pub struct CoreData {
     pub entry: Option<u64>,
     pub state: Option<u64>,
     pub pc: Option<u64>,
-}
-
-#[derive(Clone, Default)]
-pub struct CoreDataCache {
-    sync: Option<u32>,
-    core_data: Vec<(String, Option<u32>, Option<u32>, Option<PanicData>)>,
-    panic_data: Option<PanicData>,
+    pub log: Option<u64>,
+    pub profile: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -49,26 +93,82 @@ pub struct KernelBinData {
     pub noc_debug: Option<u64>,
     pub unknown_panic: Option<u64>,
 
-    pub core_data_cache: CoreDataCache,
+    pub status_cache: CoreStatus,
 }
 
 impl KernelBinData {
-    pub fn state_vec(&self) -> Vec<(String, CoreData)> {
-        vec![
-            ("BRISC".to_string(), self.brisc_state.clone()),
-            ("NCRISC".to_string(), self.ncrisc_state.clone()),
-            ("TRISC0".to_string(), self.trisc0_state.clone()),
-            ("TRISC1".to_string(), self.trisc1_state.clone()),
-            ("TRISC2".to_string(), self.trisc2_state.clone()),
-        ]
+    pub fn from_symbols(sym_table: &HashMap<String, u64>) -> Self {
+        KernelBinData {
+            start_sync: sym_table.get("START_SYNC").copied(),
+
+            brisc_state: CoreData {
+                entry: sym_table.get("__brisc_start").copied(),
+                state: sym_table.get("STATE_BRISC").copied(),
+                pc: sym_table.get("POSTCODE_BRISC").copied(),
+                panic: sym_table.get("PANIC_DATA_BRISC").copied(),
+                log: sym_table.get("LOG_BUFFER_BRISC").copied(),
+                profile: sym_table.get("PROFILE_BUFFER_BRISC").copied(),
+            },
+
+            ncrisc_state: CoreData {
+                entry: sym_table.get("__ncrisc_start").copied(),
+                state: sym_table.get("STATE_NCRISC").copied(),
+                pc: sym_table.get("POSTCODE_NCRISC").copied(),
+                panic: sym_table.get("PANIC_DATA_NCRISC").copied(),
+                log: sym_table.get("LOG_BUFFER_NCRISC").copied(),
+                profile: sym_table.get("PROFILE_BUFFER_NCRISC").copied(),
+            },
+
+            trisc0_state: CoreData {
+                entry: sym_table.get("__trisc0_start").copied(),
+                state: sym_table.get("STATE_TRISC0").copied(),
+                pc: sym_table.get("POSTCODE_TRISC0").copied(),
+                panic: sym_table.get("PANIC_DATA_TRISC0").copied(),
+                log: sym_table.get("LOG_BUFFER_TRISC0").copied(),
+                profile: sym_table.get("PROFILE_BUFFER_TRISC0").copied(),
+            },
+
+            trisc1_state: CoreData {
+                entry: sym_table.get("__trisc1_start").copied(),
+                state: sym_table.get("STATE_TRISC1").copied(),
+                pc: sym_table.get("POSTCODE_TRISC1").copied(),
+                panic: sym_table.get("PANIC_DATA_TRISC1").copied(),
+                log: sym_table.get("LOG_BUFFER_TRISC1").copied(),
+                profile: sym_table.get("PROFILE_BUFFER_TRISC1").copied(),
+            },
+
+            trisc2_state: CoreData {
+                entry: sym_table.get("__trisc2_start").copied(),
+                state: sym_table.get("STATE_TRISC2").copied(),
+                pc: sym_table.get("POSTCODE_TRISC2").copied(),
+                panic: sym_table.get("PANIC_DATA_TRISC2").copied(),
+                log: sym_table.get("LOG_BUFFER_TRISC2").copied(),
+                profile: sym_table.get("PROFILE_BUFFER_TRISC2").copied(),
+            },
+
+            data_start: sym_table.get("__firmware_end").copied(),
+            unknown_panic: sym_table.get("PANIC_DATA_UNKNOWN").copied(),
+            noc_debug: sym_table.get("NOC_DEBUG").copied(),
+            status_cache: Default::default(),
+        }
+    }
+
+    pub fn risc(&self, risc: RiscId) -> &CoreData {
+        match risc {
+            RiscId::Brisc => &self.brisc_state,
+            RiscId::Ncrisc => &self.ncrisc_state,
+            RiscId::Trisc0 => &self.trisc0_state,
+            RiscId::Trisc1 => &self.trisc1_state,
+            RiscId::Trisc2 => &self.trisc2_state,
+        }
     }
 
     pub fn start_sync(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) -> bool {
         if self
-            .value_vec(chip, noc_id, tile)
-            .core_data
-            .into_iter()
-            .all(|v| v.1.map(|v| v == 0).unwrap_or(true))
+            .read_status(chip, noc_id, tile)
+            .riscs
+            .iter()
+            .all(|v| v.state.map(|v| v == RiscState::NotStarted).unwrap_or(true))
         {
             if let Some(sync) = self.start_sync {
                 let mut sync_value = chip.noc_read32(noc_id, tile, sync);
@@ -93,26 +193,12 @@ impl KernelBinData {
         chip: &mut Chip,
         noc_id: NocId,
         tile: NocAddress,
-        name: &str,
+        risc: Option<RiscId>,
         panic: &PanicData,
     ) -> bool {
         if panic.panicked {
-            tracing::error!("{name} Panicked");
-            let mut buf = vec![0; panic.filename_len as usize];
-            tracing::error!("filename_len: {}", panic.filename_len);
-            chip.noc_read(noc_id, tile, panic.filename_addr as u64, &mut buf);
-
-            let filename = String::from_utf8(buf).unwrap();
-            tracing::error!("{}; {}", filename, panic.line);
-
-            let mut buf = vec![0; panic.message_len as usize];
-            tracing::error!("message_len: {}", panic.message_len);
-            chip.noc_read(noc_id, tile, panic.message_addr as u64, &mut buf);
-
-            // TODO(drosen): For some reason the message is not completely valid utf-8
-            // Also a long message doesn't seem to work...
-            let message = String::from_utf8_lossy(&buf);
-            tracing::error!("{}", message);
+            let report = PanicReport::read(chip, noc_id, tile, risc, panic);
+            tracing::error!("{report}");
 
             return true;
         }
@@ -126,36 +212,43 @@ impl KernelBinData {
         noc_id: NocId,
         tile: NocAddress,
     ) -> Option<PanicData> {
-        // let name = format!("PANIC_DATA_{name}");
-        if let Some(postcode_mapping) = panic_addr {
-            let mut data = [0; size_of::<PanicData>()];
-            chip.noc_read(noc_id, tile, postcode_mapping, &mut data);
+        panic_from(panic_addr, &mut |addr, len| {
+            let mut data = vec![0; len];
+            chip.noc_read(noc_id, tile, addr, &mut data);
+            data
+        })
+    }
 
-            Some(unsafe { std::mem::transmute_copy(&data) })
-        } else {
-            None
-        }
+    pub fn read_status(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) -> CoreStatus {
+        self.status_from(&mut |addr, len| {
+            let mut data = vec![0; len];
+            chip.noc_read(noc_id, tile, addr, &mut data);
+            data
+        })
     }
 
-    fn value_vec(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) -> CoreDataCache {
-        let sync = self
-            .start_sync
-            .map(|sync| chip.noc_read32(noc_id, tile, sync as u64));
-        let mut values = Vec::new();
-        for state in self.state_vec() {
-            let pd = self.read_panic((state.1).panic, chip, noc_id, tile);
-            values.push((
-                state.0,
-                ((state.1).state).map(|v| chip.noc_read32(noc_id, tile, v)),
-                ((state.1).pc).map(|v| chip.noc_read32(noc_id, tile, v)),
-                pd,
-            ));
+    /// Decode the state globals, `read` fetches `len` bytes at an address on the core.
+    pub fn status_from(&self, read: &mut dyn FnMut(u64, usize) -> Vec<u8>) -> CoreStatus {
+        let read32 = |read: &mut dyn FnMut(u64, usize) -> Vec<u8>, addr: u64| {
+            u32::from_le_bytes(read(addr, 4)[..4].try_into().unwrap())
+        };
+
+        let sync = self.start_sync.map(|v| read32(read, v));
+        let mut riscs = Vec::with_capacity(RiscId::ALL.len());
+        for risc in RiscId::ALL {
+            let data = self.risc(risc);
+            riscs.push(RiscStatus {
+                risc,
+                state: data.state.map(|v| RiscState::from_raw(read32(read, v))),
+                postcode: data.pc.map(|v| read32(read, v)),
+                panic: panic_from(data.panic, read),
+            });
         }
 
-        CoreDataCache {
+        CoreStatus {
             sync,
-            core_data: values,
-            panic_data: self.read_panic(self.unknown_panic, chip, noc_id, tile),
+            riscs,
+            unknown_panic: panic_from(self.unknown_panic, read),
         }
     }
 
@@ -174,11 +267,9 @@ impl KernelBinData {
         tile: NocAddress,
         force: bool,
     ) {
-        let state = self.value_vec(chip, noc_id, tile);
+        let state = self.read_status(chip, noc_id, tile);
         if !force {
-            if (state.sync, &state.core_data)
-                == (self.core_data_cache.sync, &self.core_data_cache.core_data)
-            {
+            if (state.sync, &state.riscs) == (self.status_cache.sync, &self.status_cache.riscs) {
                 return;
             }
         }
@@ -194,7 +285,7 @@ impl KernelBinData {
         if let Some(sync) = state.sync {
             tracing::info!("SYNC: {}", sync);
             if sync != 3 {
-                self.core_data_cache = state;
+                self.status_cache = state;
                 return;
             }
         }
@@ -204,42 +295,22 @@ impl KernelBinData {
             tracing::info!("noc_debug: 0x{:x}", brc);
         }
 
-        for (name, state, postcode, panic) in &state.core_data {
-            if let Some(panic) = panic {
-                self.print_core_panic_data(chip, noc_id, tile, name, panic);
-            }
-
-            let mut info = format!("{} {{", name);
-            let mut prev = false;
-            if let Some(state) = state {
-                info = format!("{info} STATE: {state}");
-                prev = true;
-            }
-            if let Some(postcode) = postcode {
-                #[allow(unused_assignments)]
-                if prev {
-                    info = format!("{info},");
-                    prev = false;
-                }
-                info = format!("{info} POSTCODE: {postcode:x}");
+        for risc in &state.riscs {
+            if let Some(panic) = &risc.panic {
+                self.print_core_panic_data(chip, noc_id, tile, Some(risc.risc), panic);
             }
-            info = format!("{info} }}");
 
-            tracing::info!(info);
+            tracing::info!("{risc}");
         }
-        if let Some(panic) = &state.panic_data {
-            self.print_core_panic_data(chip, noc_id, tile, "UNKNOWN", panic);
+        if let Some(panic) = &state.unknown_panic {
+            self.print_core_panic_data(chip, noc_id, tile, None, panic);
         }
 
-        self.core_data_cache = state;
+        self.status_cache = state;
     }
 
     pub fn check_panic(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) -> bool {
-        let value = self.value_vec(chip, noc_id, tile);
-        value
-            .core_data
-            .iter()
-            .any(|v| v.1.map(|v| v == 6).unwrap_or(false))
+        self.read_status(chip, noc_id, tile).any_panicked()
     }
 
     pub fn wait(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) {
@@ -255,106 +326,299 @@ impl KernelBinData {
 
     /// Marked by all cores either having completed... or not started
     pub fn all_complete(&mut self, chip: &mut Chip, noc_id: NocId, tile: NocAddress) -> bool {
-        let states = self.state_vec();
+        self.read_status(chip, noc_id, tile).all_complete()
+    }
+}
 
-        let state_value = states
-            .iter()
-            .filter_map(|v| v.1.state)
-            .map(|v| chip.noc_read32(noc_id, tile, v))
-            .collect::<Vec<_>>();
+fn panic_from(
+    panic_addr: Option<u64>,
+    read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+) -> Option<PanicData> {
+    let addr = panic_addr?;
+    let mut data = [0; size_of::<PanicData>()];
+    data.copy_from_slice(&read(addr, size_of::<PanicData>()));
 
-        let total_count = state_value.iter().count();
-        let complete_count = state_value.iter().filter(|v| **v >= 3).count();
-        let not_started_count = state_value.iter().filter(|v| **v == 0).count();
+    Some(unsafe { std::mem::transmute_copy(&data) })
+}
 
-        total_count == 0
-            || (complete_count > 0 && complete_count + not_started_count == states.len())
+/// The options a kernel was built with, kept so that prebuilt images record where they came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildInfo {
+    pub kernel_name: String,
+    pub profile: String,
+    pub lto: bool,
+    pub build_std: bool,
+    pub default_features: bool,
+    /// Where the elf was built, not kept in images since it is only valid on the host that built it
+    pub elf: Option<std::path::PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub addr: u64,
+    /// Size in bytes as recorded in the elf, zero for linker defined symbols
+    pub size: u64,
+    pub section: Option<String>,
+}
+
+impl Symbol {
+    /// A symbol outside of any section.
+    pub fn new(addr: u64, size: u64) -> Self {
+        Self {
+            addr,
+            size,
+            section: None,
+        }
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum SymbolError {
+    #[error("symbol {0} not found")]
+    NotFound(String),
+
+    #[error("symbol {name} is {size} bytes but the requested type is {expected} bytes")]
+    SizeMismatch {
+        name: String,
+        size: u64,
+        expected: usize,
+    },
+
+    #[error("symbol {name} at 0x{addr:x} is not aligned to {align} bytes")]
+    Misaligned {
+        name: String,
+        addr: u64,
+        align: usize,
+    },
+}
+
 #[derive(Clone)]
 pub struct KernelData {
-    pub sym_table: HashMap<String, u64>,
+    pub symbols: HashMap<String, Symbol>,
     pub writes: Vec<KernelBytes>,
     pub bin: KernelBinData,
+
+    /// The target the elf was built for (the `env` of its target json) if it carried a marker
+    pub target: Option<String>,
+    pub build: Option<BuildInfo>,
+
+    /// Only present if the kernel was loaded with `LoadOptions::keep_debug_info`
+    pub debug: Option<Arc<DebugInfo>>,
+    pub log_formats: LogFormats,
+    /// The elf entry point
+    pub entry: u64,
+
+    /// The program of each RISC when this was merged from a [`crate::program::CoreProgram`]
+    pub riscs: HashMap<RiscId, Arc<KernelData>>,
 }
 
 impl<S: AsRef<str>> std::ops::Index<S> for KernelData {
     type Output = u64;
 
     fn index(&self, index: S) -> &Self::Output {
-        &self.sym_table[index.as_ref()]
+        &self.symbols[index.as_ref()].addr
+    }
+}
+
+impl Default for KernelData {
+    fn default() -> Self {
+        Self::new(Vec::new(), HashMap::new())
     }
 }
 
 impl KernelData {
+    /// A kernel made of `writes` with no target, build info, debug info or log formats.
+    pub fn new(writes: Vec<KernelBytes>, symbols: HashMap<String, Symbol>) -> Self {
+        let addrs = symbols
+            .iter()
+            .map(|(name, symbol)| (name.clone(), symbol.addr))
+            .collect();
+
+        Self {
+            bin: KernelBinData::from_symbols(&addrs),
+            symbols,
+            writes,
+            target: None,
+            build: None,
+            debug: None,
+            log_formats: LogFormats::default(),
+            entry: 0,
+            riscs: HashMap::new(),
+        }
+    }
+
+    pub fn symbol(&self, name: &str) -> Result<&Symbol, SymbolError> {
+        self.symbols
+            .get(name)
+            .ok_or_else(|| SymbolError::NotFound(name.to_string()))
+    }
+
+    /// `name` checked against `T`: the symbol must hold exactly `count` values, or any nonzero
+    /// number of them if `count` is None, and be aligned for `T`.
+    pub fn typed_symbol<T: Pod>(
+        &self,
+        name: &str,
+        count: Option<usize>,
+    ) -> Result<&Symbol, SymbolError> {
+        let symbol = self.symbol(name)?;
+
+        let elem = size_of::<T>() as u64;
+        let size_ok = match count {
+            Some(count) => symbol.size == elem * count as u64,
+            None => elem != 0 && symbol.size != 0 && symbol.size % elem == 0,
+        };
+        if !size_ok {
+            return Err(SymbolError::SizeMismatch {
+                name: name.to_string(),
+                size: symbol.size,
+                expected: size_of::<T>() * count.unwrap_or(1),
+            });
+        }
+
+        if symbol.addr % align_of::<T>() as u64 != 0 {
+            return Err(SymbolError::Misaligned {
+                name: name.to_string(),
+                addr: symbol.addr,
+                align: align_of::<T>(),
+            });
+        }
+
+        Ok(symbol)
+    }
+
+    /// The program running on `risc`, symbols, debug info and log formats of a RISC come from here.
+    pub fn for_risc(&self, risc: Option<RiscId>) -> &KernelData {
+        risc.and_then(|risc| self.riscs.get(&risc))
+            .map(|v| v.as_ref())
+            .unwrap_or(self)
+    }
+
+    /// Parse and keep the DWARF info of `elf`, returns false if it had none.
+    pub fn attach_debug_info(&mut self, elf: &[u8]) -> bool {
+        self.debug = DebugInfo::parse(elf).map(Arc::new);
+        self.debug.is_some()
+    }
+
+    /// Decode the global `name` using its type from the debug info, `read` fetches it and any L1
+    /// memory it points to.
+    pub fn inspect(
+        &self,
+        name: &str,
+        l1_size: u64,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) -> Result<Inspection, InspectError> {
+        let debug = self.debug.as_ref().ok_or(InspectError::NoDebugInfo)?;
+        let global = debug
+            .global(name)
+            .ok_or_else(|| InspectError::NotFound(name.to_string()))?;
+
+        Ok(debug.inspect(global, l1_size, read))
+    }
+
+    /// Write the kernel out as a prebuilt image, see [`image`] for the format.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ImageError> {
+        std::fs::write(path, image::encode(self))?;
+        Ok(())
+    }
+
+    /// Read a prebuilt image written by [`KernelData::save`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        image::decode(&std::fs::read(path)?)
+    }
+
+    /// Check a prebuilt kernel against the arch it is about to be loaded onto.
+    pub fn check_target(&self, arch: Arch) -> Result<(), ElfTargetError> {
+        let expected = crate::loader::standard_target(arch)?.to_string();
+        match &self.target {
+            Some(found) if *found != expected => Err(ElfTargetError::Arch {
+                expected: arch,
+                found: found.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Load the kernel onto `tile` with the default [`Verify`], panicking if a segment doesn't
+    /// verify.
     pub fn load<T: Into<NocAddress>>(&self, chip: &mut Chip, noc_id: NocId, tile: T) {
-        let tile = tile.into();
+        self.load_with(chip, noc_id, tile, Verify::default())
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
 
-        for write in &self.writes {
-            let data = write.data.0.as_ref();
-            // for i in 0..data.len() / 4 {
-            // let data = u32::from_le_bytes([
-            // data[i * 4],
-            // data[i * 4 + 1],
-            // data[i * 4 + 2],
-            // data[i * 4 + 3],
-            // ]);
-            // device
-            // .noc_write32(0, x, y, write.addr as u64 + i as u64 * 4, data)
-            // .unwrap();
-            // }
-
-            if data.as_ptr().align_offset(std::mem::align_of::<u32>()) != 0 {
-                let layout = std::alloc::Layout::array::<u8>(data.len())
-                    .unwrap()
-                    .align_to(std::mem::align_of::<u32>())
-                    .unwrap();
-                let datap = unsafe { std::alloc::alloc(layout) };
-                let new_data = unsafe { std::slice::from_raw_parts_mut(datap, data.len()) };
-                new_data.copy_from_slice(data);
-                chip.noc_write(noc_id, tile, write.addr as u64, new_data);
-                unsafe { std::alloc::dealloc(datap, layout) };
-            } else {
-                chip.noc_write(noc_id, tile, write.addr as u64, data);
-            };
+    /// Load the kernel onto `tile`, checking each segment as it is written.
+    pub fn load_with<T: Into<NocAddress>>(
+        &self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: T,
+        verify: Verify,
+    ) -> Result<(), VerifyError> {
+        let tile = tile.into();
+        if verify.check_target {
+            self.check_target(chip.arch())?;
+        }
 
-            // Readback
-            let mut readback_data = vec![0; write.data.0.len()];
-            chip.noc_read(noc_id, tile, write.addr as u64, &mut readback_data);
-            debug_assert_eq!(readback_data.as_slice(), write.data.0.as_ref());
+        for (segment, write) in self.writes.iter().enumerate() {
+            write_segment(chip, noc_id, tile, write);
+            verify_segment(chip, noc_id, tile, segment, write, verify)?;
         }
+
+        Ok(())
     }
 
+    /// Load the kernel onto every tensix with the default [`Verify`], panicking if a segment
+    /// doesn't verify.
     pub fn load_all(&self, chip: &mut Chip, noc_id: NocId) {
-        for write in &self.writes {
-            let data = write.data.0.as_ref();
-            if data.as_ptr().align_offset(std::mem::align_of::<u32>()) != 0 {
-                let layout = std::alloc::Layout::array::<u8>(data.len())
-                    .unwrap()
-                    .align_to(std::mem::align_of::<u32>())
-                    .unwrap();
-                let datap = unsafe { std::alloc::alloc(layout) };
-                let new_data = unsafe { std::slice::from_raw_parts_mut(datap, data.len()) };
-                new_data.copy_from_slice(data);
-                chip.noc_broadcast(noc_id, write.addr as u64, new_data);
-                unsafe { std::alloc::dealloc(datap, layout) };
-            } else {
-                chip.noc_broadcast(noc_id, write.addr as u64, data);
-            };
+        self.load_all_with(chip, noc_id, Verify::default())
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
 
-            // Readback
-            for tensix in 0..chip.tensix_count() {
-                let mut readback_data = vec![0; write.data.0.len()];
-                chip.noc_read(
-                    noc_id,
-                    chip.tensix(tensix),
-                    write.addr as u64,
-                    &mut readback_data,
-                );
-                debug_assert_eq!(readback_data.as_slice(), write.data.0.as_ref());
+    /// Broadcast the kernel to every tensix, checking each segment on each core.
+    pub fn load_all_with(
+        &self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        verify: Verify,
+    ) -> Result<(), VerifyError> {
+        if verify.check_target {
+            self.check_target(chip.arch())?;
+        }
+
+        for (segment, write) in self.writes.iter().enumerate() {
+            with_aligned(&write.data.0, |data| {
+                chip.noc_broadcast(noc_id, write.addr as u64, data)
+            });
+
+            if verify.policy != VerifyPolicy::None {
+                for tensix in 0..chip.tensix_count() {
+                    let tile = chip.tensix(tensix).addr;
+                    verify_segment(chip, noc_id, tile, segment, write, verify)?;
+                }
             }
         }
+
+        Ok(())
+    }
+
+    /// Check the kernel's segments on `tile`, e.g. after they were multicast. Segments that don't
+    /// match are written again up to `verify.retries` times.
+    pub fn verify<T: Into<NocAddress>>(
+        &self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: T,
+        verify: Verify,
+    ) -> Result<(), VerifyError> {
+        let tile = tile.into();
+        if verify.policy == VerifyPolicy::None {
+            return Ok(());
+        }
+
+        for (segment, write) in self.writes.iter().enumerate() {
+            verify_segment(chip, noc_id, tile, segment, write, verify)?;
+        }
+
+        Ok(())
     }
 
     pub fn set_entry(
@@ -426,11 +690,314 @@ impl KernelData {
     }
 }
 
+/// How many polls without any state or postcode change before a kernel is considered hung.
+/// Polls are 10ms apart.
+pub const DEFAULT_HANG_POLLS: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KernelOutcome {
+    Completed,
+    Panicked {
+        /// None if the panic could not be attributed to a RISC
+        risc: Option<RiscId>,
+        report: PanicReport,
+    },
+    TimedOut {
+        last_states: Vec<(RiscId, Option<RiscState>)>,
+        last_postcodes: Vec<(RiscId, Option<u32>)>,
+    },
+    /// No state or postcode progress for the configured number of polls
+    Hung {
+        last_states: Vec<(RiscId, Option<RiscState>)>,
+        last_postcodes: Vec<(RiscId, Option<u32>)>,
+    },
+}
+
+impl KernelOutcome {
+    pub fn is_completed(&self) -> bool {
+        matches!(self, KernelOutcome::Completed)
+    }
+
+    /// The kernel stopped running, either completing or panicking
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            KernelOutcome::Completed | KernelOutcome::Panicked { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for KernelOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let write_values = |f: &mut std::fmt::Formatter<'_>,
+                            states: &[(RiscId, Option<RiscState>)],
+                            postcodes: &[(RiscId, Option<u32>)]| {
+            for ((risc, state), (_, postcode)) in states.iter().zip(postcodes) {
+                let status = RiscStatus {
+                    risc: *risc,
+                    state: *state,
+                    postcode: *postcode,
+                    panic: None,
+                };
+                write!(f, "; {status}")?;
+            }
+            Ok(())
+        };
+
+        match self {
+            KernelOutcome::Completed => write!(f, "completed"),
+            KernelOutcome::Panicked { report, .. } => write!(f, "{report}"),
+            KernelOutcome::TimedOut {
+                last_states,
+                last_postcodes,
+            } => {
+                write!(f, "timed out")?;
+                write_values(f, last_states, last_postcodes)
+            }
+            KernelOutcome::Hung {
+                last_states,
+                last_postcodes,
+            } => {
+                write!(f, "hung")?;
+                write_values(f, last_states, last_postcodes)
+            }
+        }
+    }
+}
+
+impl KernelData {
+    /// Drain the log buffers of every RISC on `tile`, each record is also emitted as a tracing event.
+    pub fn drain_logs(&mut self, chip: &mut Chip, noc_id: NocId, tile: Tile) -> Vec<LogRecord> {
+        let mut records = Vec::new();
+        for risc in RiscId::ALL {
+            let program = self.for_risc(Some(risc));
+            let Some(symbol) = program.symbols.get(&format!("LOG_BUFFER_{}", risc.name())) else {
+                continue;
+            };
+
+            let (data, read_ptr) = log::drain_buffer(symbol, &mut |addr, len| {
+                read_aligned(chip, noc_id, tile, addr, len)
+            });
+            if let Some(read_ptr) = read_ptr {
+                chip.noc_write32(noc_id, tile, symbol.addr + log::READ_PTR_OFFSET, read_ptr);
+            }
+
+            for record in program.log_formats.decode(risc, &data) {
+                let (chip, tile, risc, message) =
+                    (chip.id(), tile.addr.n0, record.risc.name(), &record.message);
+                match record.level {
+                    LogLevel::Trace => tracing::trace!(chip, ?tile, risc, "{message}"),
+                    LogLevel::Debug => tracing::debug!(chip, ?tile, risc, "{message}"),
+                    LogLevel::Info => tracing::info!(chip, ?tile, risc, "{message}"),
+                    LogLevel::Warn => tracing::warn!(chip, ?tile, risc, "{message}"),
+                    LogLevel::Error => tracing::error!(chip, ?tile, risc, "{message}"),
+                }
+                records.push(record);
+            }
+        }
+
+        records
+    }
+
+    /// Read the profile buffers of every RISC on `tile`, best read once the kernel has finished.
+    pub fn read_profile(&self, chip: &mut Chip, noc_id: NocId, tile: Tile) -> Vec<ProfileBuffer> {
+        RiscId::ALL
+            .into_iter()
+            .filter_map(|risc| {
+                // Each program of a merged kernel has its own buffer under the same name
+                let data = self.for_risc(Some(risc));
+                let symbol = data
+                    .symbols
+                    .get(&format!("PROFILE_BUFFER_{}", risc.name()))?;
+                Some(profile::read_buffer(
+                    risc,
+                    &data.log_formats,
+                    symbol,
+                    &mut |addr, len| read_aligned(chip, noc_id, tile, addr, len),
+                ))
+            })
+            .collect()
+    }
+
+    /// Wait for the kernel on `tile` to finish, panic, run past `deadline` or stop making progress
+    /// for `hang_polls` polls. Cores are stopped if the kernel completed or panicked, otherwise they
+    /// are left running so that they can be inspected.
+    pub fn wait_until(
+        &mut self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: Tile,
+        deadline: Option<std::time::Instant>,
+        hang_polls: Option<usize>,
+    ) -> KernelOutcome {
+        let mut wait = WaitState::new(deadline, hang_polls);
+        loop {
+            if let Some(outcome) = self.poll_wait(chip, noc_id, tile, &mut wait) {
+                return outcome;
+            }
+            std::thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+
+    /// A single poll of [`KernelData::wait_until`], None while the kernel is still running.
+    pub fn poll_wait(
+        &mut self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: Tile,
+        wait: &mut WaitState,
+    ) -> Option<KernelOutcome> {
+        self.drain_logs(chip, noc_id, tile);
+
+        let l1_size = chip.tensix_l1();
+        let outcome = self.check_wait(wait, l1_size, &mut |addr, len| {
+            read_aligned(chip, noc_id, tile, addr, len)
+        });
+
+        match &outcome {
+            Some(KernelOutcome::Panicked { report, .. }) => {
+                tracing::error!("{report}");
+
+                self.drain_logs(chip, noc_id, tile);
+                crate::loader::stop(chip, tile);
+            }
+            Some(KernelOutcome::Completed) => {
+                self.drain_logs(chip, noc_id, tile);
+                crate::loader::stop(chip, tile);
+                self.bin.print_state_diff(chip, noc_id, tile.addr);
+            }
+            Some(KernelOutcome::TimedOut { .. }) => tracing::warn!("{:?}: kernel timed out", tile),
+            Some(KernelOutcome::Hung { .. }) => tracing::warn!("{:?}: kernel hung", tile),
+            None => self.bin.print_state_diff(chip, noc_id, tile.addr),
+        }
+
+        outcome
+    }
+
+    /// Decide whether a wait on the kernel is over, `read` fetches `len` bytes at an address on
+    /// the core. None while the kernel is still running, the core is never stopped here.
+    pub fn check_wait(
+        &self,
+        wait: &mut WaitState,
+        l1_size: u64,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) -> Option<KernelOutcome> {
+        let status = self.bin.status_from(read);
+
+        let mut panicked = status
+            .riscs
+            .iter()
+            .find(|v| v.panicked())
+            .map(|v| (Some(v.risc), v.panic.clone()));
+        if panicked.is_none() {
+            if let Some(panic) = status.unknown_panic.as_ref().filter(|v| v.panicked) {
+                panicked = Some((None, Some(panic.clone())));
+            }
+        }
+
+        if let Some((risc, panic)) = panicked {
+            let mut report = match panic.filter(|v| v.panicked) {
+                Some(panic) => PanicReport::read_with(risc, &panic, read),
+                None => PanicReport {
+                    risc,
+                    file: String::new(),
+                    line: 0,
+                    message: String::new(),
+                    pc: 0,
+                    sp: 0,
+                    frames: Vec::new(),
+                },
+            };
+            report.symbolize(self.for_risc(risc), l1_size, read);
+
+            return Some(KernelOutcome::Panicked { risc, report });
+        }
+
+        if status.all_complete() {
+            return Some(KernelOutcome::Completed);
+        }
+
+        let last_states = status
+            .riscs
+            .iter()
+            .map(|v| (v.risc, v.state))
+            .collect::<Vec<_>>();
+        let last_postcodes = status
+            .riscs
+            .iter()
+            .map(|v| (v.risc, v.postcode))
+            .collect::<Vec<_>>();
+
+        if wait
+            .deadline
+            .map(|deadline| std::time::Instant::now() >= deadline)
+            .unwrap_or(false)
+        {
+            return Some(KernelOutcome::TimedOut {
+                last_states,
+                last_postcodes,
+            });
+        }
+
+        // Kernels without postcodes can't report progress so they are never considered hung
+        let has_postcodes = last_postcodes.iter().any(|v| v.1.is_some());
+        if let Some(hang_polls) = wait.hang_polls.filter(|_| has_postcodes) {
+            let progress = (last_states.clone(), last_postcodes.clone());
+            if wait.last_progress.as_ref() == Some(&progress) {
+                wait.stalled_polls += 1;
+            } else {
+                wait.stalled_polls = 0;
+                wait.last_progress = Some(progress);
+            }
+
+            if wait.stalled_polls >= hang_polls {
+                return Some(KernelOutcome::Hung {
+                    last_states,
+                    last_postcodes,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+/// Globals written by the host after loading, kept across [`Kernel::relaunch`]
+const HOST_SET_SYMBOLS: [&str; 3] = ["CORE_ID", "RUNTIME_ARGS", "COMMON_RUNTIME_ARGS"];
+
+/// How often a waiting kernel is polled
+pub const WAIT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+type Progress = (Vec<(RiscId, Option<RiscState>)>, Vec<(RiscId, Option<u32>)>);
+
+/// Progress tracking for [`KernelData::poll_wait`].
+#[derive(Clone, Debug)]
+pub struct WaitState {
+    pub deadline: Option<std::time::Instant>,
+    pub hang_polls: Option<usize>,
+    last_progress: Option<Progress>,
+    stalled_polls: usize,
+}
+
+impl WaitState {
+    pub fn new(deadline: Option<std::time::Instant>, hang_polls: Option<usize>) -> Self {
+        Self {
+            deadline,
+            hang_polls,
+            last_progress: None,
+            stalled_polls: 0,
+        }
+    }
+}
+
 pub struct Kernel {
     pub device: Chip,
     pub noc_id: NocId,
     pub core: Tile,
     pub data: KernelData,
+
+    /// The outcome of the last wait on the kernel
+    pub outcome: Option<KernelOutcome>,
 }
 
 impl<S: AsRef<str>> std::ops::Index<S> for Kernel {
@@ -448,13 +1015,113 @@ impl Kernel {
             noc_id,
             core,
             data,
+            outcome: None,
         }
     }
 }
 
+/// Call `f` with `data` copied into a u32 aligned buffer if it isn't already aligned.
+pub(crate) fn with_aligned(data: &[u8], f: impl FnOnce(&[u8])) {
+    if data.as_ptr().align_offset(std::mem::align_of::<u32>()) != 0 {
+        let layout = std::alloc::Layout::array::<u8>(data.len())
+            .unwrap()
+            .align_to(std::mem::align_of::<u32>())
+            .unwrap();
+        let datap = unsafe { std::alloc::alloc(layout) };
+        let new_data = unsafe { std::slice::from_raw_parts_mut(datap, data.len()) };
+        new_data.copy_from_slice(data);
+        f(new_data);
+        unsafe { std::alloc::dealloc(datap, layout) };
+    } else {
+        f(data);
+    }
+}
+
+fn write_segment(chip: &mut Chip, noc_id: NocId, tile: NocAddress, write: &KernelBytes) {
+    with_aligned(&write.data.0, |data| {
+        chip.noc_write(noc_id, tile, write.addr as u64, data)
+    });
+}
+
+/// Check `write` on `tile`, writing it again up to `verify.retries` times if it doesn't match.
+fn verify_segment(
+    chip: &mut Chip,
+    noc_id: NocId,
+    tile: NocAddress,
+    segment: usize,
+    write: &KernelBytes,
+    verify: Verify,
+) -> Result<(), VerifyError> {
+    let mut attempts = 1;
+    loop {
+        let verified = verify.policy.check(write, &mut |addr, len| {
+            let mut data = vec![0; len];
+            chip.noc_read(noc_id, tile, addr, &mut data);
+            data
+        });
+        if verified {
+            return Ok(());
+        }
+
+        if attempts > verify.retries {
+            return Err(VerifyError::Mismatch {
+                tile,
+                segment,
+                addr: write.addr,
+                len: write.len(),
+                policy: verify.policy,
+                attempts,
+            });
+        }
+
+        tracing::warn!(
+            "segment {segment} (0x{:x}) on tile {:?} failed verification, rewriting",
+            write.addr,
+            tile.n0
+        );
+        write_segment(chip, noc_id, tile, write);
+        attempts += 1;
+    }
+}
+
+/// Read `len` bytes at `addr`, widening the access to the tile's read alignment.
+pub(crate) fn read_aligned(
+    chip: &mut Chip,
+    noc_id: NocId,
+    core: Tile,
+    addr: u64,
+    len: usize,
+) -> Vec<u8> {
+    let align = (core.align_read as u64).max(1);
+    let start = addr & !(align - 1);
+    let end = (addr + len as u64 + (align - 1)) & !(align - 1);
+
+    let mut data = vec![0; (end - start) as usize];
+    chip.noc_read(noc_id, core, start, &mut data);
+
+    data[(addr - start) as usize..][..len].to_vec()
+}
+
+/// Write `data` at `addr`, widening the access to the tile's write alignment.
+/// The bytes around `data` are read back first so that neighbouring values are preserved.
+pub(crate) fn write_aligned(chip: &mut Chip, noc_id: NocId, core: Tile, addr: u64, data: &[u8]) {
+    let align = (core.align_write as u64).max(1);
+    let start = addr & !(align - 1);
+    let end = (addr + data.len() as u64 + (align - 1)) & !(align - 1);
+
+    if start == addr && end == addr + data.len() as u64 {
+        chip.noc_write(noc_id, core, addr, data);
+    } else {
+        let mut window = vec![0; (end - start) as usize];
+        chip.noc_read(noc_id, core, start, &mut window);
+        window[(addr - start) as usize..][..data.len()].copy_from_slice(data);
+        chip.noc_write(noc_id, core, start, &window);
+    }
+}
+
 // TODO(drosen): This should be a shared definition
 #[repr(C)]
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PanicData {
     pub filename_addr: u32,
     pub filename_len: u32,
@@ -504,6 +1171,30 @@ impl Kernel {
         self.wait_id(self.noc_id);
     }
 
+    /// Wait for the kernel to finish, giving up after `timeout` or once it stops making progress
+    /// for [`DEFAULT_HANG_POLLS`] polls.
+    pub fn wait_timeout(&mut self, timeout: std::time::Duration) -> KernelOutcome {
+        self.wait_for(Some(timeout), Some(DEFAULT_HANG_POLLS))
+    }
+
+    pub fn wait_for(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+        hang_polls: Option<usize>,
+    ) -> KernelOutcome {
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        let outcome = self.data.wait_until(
+            &mut self.device,
+            self.noc_id,
+            self.core,
+            deadline,
+            hang_polls,
+        );
+        self.outcome = Some(outcome.clone());
+
+        outcome
+    }
+
     /// Marked by all cores either having completed... or not started
     pub fn all_complete(&mut self) -> bool {
         self.data
@@ -530,6 +1221,107 @@ impl Kernel {
         )
     }
 
+    /// Run the kernel again without rebuilding or reloading its code. The core is reset, the
+    /// writable segments are restored from the cached image, the state globals are cleared and
+    /// the RISCs are started again. Values set by the host (`CORE_ID` and the runtime args) are
+    /// kept. Use [`Kernel::wait_timeout`] or similar to wait for the new run.
+    pub fn relaunch(&mut self) {
+        self.relaunch_inner(None);
+    }
+
+    /// Like [`Kernel::relaunch`] but with new `RUNTIME_ARGS`.
+    pub fn relaunch_with_args<T: Pod>(&mut self, args: &T) -> Result<(), SymbolError> {
+        let symbol =
+            crate::program::args_symbol(&self.data, crate::program::RUNTIME_ARGS, size_of::<T>())?;
+        self.relaunch_inner(Some((symbol.addr, bytemuck::bytes_of(args))));
+        Ok(())
+    }
+
+    fn relaunch_inner(&mut self, args: Option<(u64, &[u8])>) {
+        crate::loader::stop(&mut self.device, self.core);
+
+        let kept = HOST_SET_SYMBOLS
+            .iter()
+            .filter_map(|name| self.data.symbols.get(*name))
+            .filter(|symbol| symbol.size > 0)
+            .map(|symbol| (symbol.addr, symbol.size as usize))
+            .collect::<Vec<_>>();
+        let kept = kept
+            .into_iter()
+            .map(|(addr, size)| (addr, self.read_aligned(addr, size)))
+            .collect::<Vec<_>>();
+
+        tracing::debug!("{:?}: restoring writable segments", self.core);
+        for write in self.data.writes.iter().filter(|v| v.writable) {
+            let (device, noc_id, core) = (&mut self.device, self.noc_id, self.core);
+            write_aligned(device, noc_id, core, write.addr as u64, &write.data.0);
+            if write.zeroed > 0 {
+                let end = write.addr as u64 + write.len() as u64;
+                write_aligned(device, noc_id, core, end, &vec![0; write.zeroed as usize]);
+            }
+        }
+
+        for (addr, data) in kept {
+            self.write_aligned(addr, &data);
+        }
+        if let Some((addr, data)) = args {
+            self.write_aligned(addr, data);
+        }
+
+        for risc in RiscId::ALL {
+            if let Some(state) = self.data.bin.risc(risc).state {
+                self.write32(state, RiscState::NOT_STARTED);
+            }
+        }
+        if let Some(sync) = self.data.bin.start_sync {
+            self.write32(sync, 0);
+        }
+        self.data.bin.status_cache = Default::default();
+        self.outcome = None;
+
+        // Programs merged from a CoreProgram start each RISC at its own entry and handshake with
+        // each program separately
+        let mut bins = Vec::new();
+        if self.data.riscs.is_empty() {
+            crate::loader::easy_start(&mut self.device, self.core.addr);
+            bins.push(self.data.bin.clone());
+        } else {
+            let riscs = RiscId::ALL
+                .into_iter()
+                .filter(|risc| self.data.riscs.contains_key(risc))
+                .collect::<Vec<_>>();
+            let entry = |risc: RiscId| {
+                let data = self.data.riscs.get(&risc)?;
+                Some(data.bin.risc(risc).entry.unwrap_or(data.entry))
+            };
+            let entries = [
+                entry(RiscId::Ncrisc),
+                entry(RiscId::Trisc0),
+                entry(RiscId::Trisc1),
+                entry(RiscId::Trisc2),
+            ];
+
+            self.data.set_entry(
+                &mut self.device,
+                self.noc_id,
+                self.core.addr,
+                entries[0],
+                entries[1],
+                entries[2],
+                entries[3],
+            );
+            crate::loader::start_riscs(&mut self.device, self.core.addr, &riscs);
+            bins.extend(riscs.iter().map(|risc| self.data.riscs[risc].bin.clone()));
+        }
+
+        tracing::debug!("{:?}: waiting for start", self.core);
+        for mut bin in bins.into_iter().filter(|v| v.start_sync.is_some()) {
+            while !bin.start_sync(&mut self.device, self.noc_id, self.core.addr) {
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+        }
+    }
+
     pub fn read_id(&mut self, noc_id: NocId, addr: u64, data: &mut [u8]) {
         self.device.noc_read(noc_id, self.core, addr, data);
     }
@@ -561,4 +1353,120 @@ impl Kernel {
     pub fn write32(&mut self, addr: u64, value: u32) {
         self.write32_id(self.noc_id, addr, value)
     }
+
+    fn read_aligned(&mut self, addr: u64, len: usize) -> Vec<u8> {
+        read_aligned(&mut self.device, self.noc_id, self.core, addr, len)
+    }
+
+    fn write_aligned(&mut self, addr: u64, data: &[u8]) {
+        write_aligned(&mut self.device, self.noc_id, self.core, addr, data)
+    }
+
+    /// Read a global from the kernel, checking `T` against the size of the symbol in the elf.
+    pub fn read_sym<T: Pod>(&mut self, name: &str) -> Result<T, SymbolError> {
+        let addr = self.data.typed_symbol::<T>(name, Some(1))?.addr;
+        let data = self.read_aligned(addr, size_of::<T>());
+        Ok(bytemuck::pod_read_unaligned(&data))
+    }
+
+    pub fn write_sym<T: Pod>(&mut self, name: &str, value: &T) -> Result<(), SymbolError> {
+        let addr = self.data.typed_symbol::<T>(name, Some(1))?.addr;
+        self.write_aligned(addr, bytemuck::bytes_of(value));
+        Ok(())
+    }
+
+    /// Read an array global, the symbol size must be a multiple of `size_of::<T>()`.
+    pub fn read_sym_slice<T: Pod>(&mut self, name: &str) -> Result<Vec<T>, SymbolError> {
+        let symbol = self.data.typed_symbol::<T>(name, None)?.clone();
+        let data = self.read_aligned(symbol.addr, symbol.size as usize);
+        Ok(bytemuck::pod_collect_to_vec(&data))
+    }
+
+    pub fn write_sym_slice<T: Pod>(&mut self, name: &str, values: &[T]) -> Result<(), SymbolError> {
+        let addr = self.data.typed_symbol::<T>(name, Some(values.len()))?.addr;
+        self.write_aligned(addr, bytemuck::cast_slice(values));
+        Ok(())
+    }
+
+    /// Read a global and decode it using its type from the debug info.
+    pub fn inspect(&mut self, name: &str) -> Result<Inspection, InspectError> {
+        let l1_size = self.device.tensix_l1();
+        let (device, noc_id, core) = (&mut self.device, self.noc_id, self.core);
+        self.data.inspect(name, l1_size, &mut |addr, len| {
+            read_aligned(device, noc_id, core, addr, len)
+        })
+    }
+
+    /// All globals with a static address, sorted by address.
+    pub fn globals(&self) -> Result<&[Global], InspectError> {
+        self.data
+            .debug
+            .as_deref()
+            .map(|debug| debug.globals.as_slice())
+            .ok_or(InspectError::NoDebugInfo)
+    }
+
+    pub fn drain_logs(&mut self) -> Vec<LogRecord> {
+        self.data
+            .drain_logs(&mut self.device, self.noc_id, self.core)
+    }
+
+    pub fn read_profile(&mut self) -> Vec<ProfileBuffer> {
+        self.data
+            .read_profile(&mut self.device, self.noc_id, self.core)
+    }
+
+    pub fn status(&mut self) -> CoreStatus {
+        self.data
+            .bin
+            .read_status(&mut self.device, self.noc_id, self.core.addr)
+    }
+
+    /// Sample the pc of every RISC and the NoC counters of the tile, for a kernel that appears hung.
+    pub fn diagnose(&mut self) -> Result<Diagnosis, DiagnoseError> {
+        let layout = diagnose::DebugRegisters::for_arch(self.device.arch())?;
+        let mut regs = diagnose::ChipTile {
+            chip: &mut self.device,
+            noc_id: self.noc_id,
+            tile: self.core,
+        };
+        Ok(Diagnosis::collect(
+            &mut regs,
+            &layout,
+            self.core,
+            &self.data,
+            diagnose::PC_SAMPLES,
+        ))
+    }
+
+    /// A symbolized report for every RISC that has panicked.
+    pub fn panic_reports(&mut self) -> Vec<PanicReport> {
+        let mut panics = RiscId::ALL
+            .into_iter()
+            .map(|risc| (Some(risc), self.data.bin.risc(risc).panic))
+            .collect::<Vec<_>>();
+        panics.push((None, self.data.bin.unknown_panic));
+
+        let l1_size = self.device.tensix_l1();
+        let mut reports = Vec::new();
+        for (risc, addr) in panics {
+            let panic =
+                self.data
+                    .bin
+                    .read_panic(addr, &mut self.device, self.noc_id, self.core.addr);
+            let Some(panic) = panic.filter(|panic| panic.panicked) else {
+                continue;
+            };
+
+            let mut report =
+                PanicReport::read(&mut self.device, self.noc_id, self.core.addr, risc, &panic);
+            let (device, noc_id, core) = (&mut self.device, self.noc_id, self.core);
+            report.symbolize(self.data.for_risc(risc), l1_size, &mut |addr, len| {
+                read_aligned(device, noc_id, core, addr, len)
+            });
+            reports.push(report);
+        }
+
+        reports
+    }
 }
@@ -0,0 +1,375 @@
+//! Diagnosing a kernel that never finishes.
+//!
+//! The pc of every RISC is sampled through the tensix debug bus, which does not disturb the
+//! RISCs, and symbolized against the kernel. The RISC debug interface tells whether a RISC has
+//! halted, e.g. on an `ebreak`. Alongside it the NIU counters of both NoCs show whether the tile is
+//! still waiting on requests it sent out.
+//!
+//! The registers involved move between arches, [`DebugRegisters`] has their location on the
+//! arches they have been checked against and refuses the others.
+
+use luwen::luwen_core::Arch;
+
+use super::{panic::resolve_pc, KernelData, RiscId, RiscState, StackFrame};
+use crate::{
+    chip::noc::{NocId, NocInterface, Tile},
+    Chip,
+};
+
+/// Pcs sampled per RISC
+pub const PC_SAMPLES: usize = 16;
+/// A RISC whose sampled pcs all fall within this many bytes is stuck in a loop
+pub const SPIN_WINDOW: u64 = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiagnoseError {
+    #[error("the debug registers of {0} have not been checked, can't diagnose its tiles")]
+    UncheckedArch(Arch),
+}
+
+/// Where the registers read by a diagnosis live on one arch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugRegisters {
+    pub soft_reset: u64,
+    pub dbg_bus_cntl: u64,
+    pub dbg_rd_data: u64,
+    /// Daisy chain and read selector of the debug bus signals holding the pcs
+    pub pc_daisy_sel: u32,
+    pub pc_rd_sel: u32,
+    /// Debug bus signal of the pc of each RISC, in [`RiscId::ALL`] order
+    pub pc_signals: [u32; 5],
+    /// Indirect access to the RISC debug interface: the register to access and the value to write
+    /// go in the control registers, a read lands in the status register
+    pub risc_dbg_cntl0: u64,
+    pub risc_dbg_cntl1: u64,
+    pub risc_dbg_status1: u64,
+    /// Base of the NIU registers of NoC 0, NoC 1 follows at `noc_id_offset`
+    pub noc_regs: u64,
+    pub noc_id_offset: u64,
+    /// Offset of the first status counter in the NIU registers
+    pub noc_status_offset: u64,
+}
+
+impl DebugRegisters {
+    pub const WORMHOLE: Self = Self {
+        soft_reset: 0xFFB1_21B0,
+        dbg_bus_cntl: 0xFFB1_2054,
+        dbg_rd_data: 0xFFB1_205C,
+        pc_daisy_sel: 7,
+        pc_rd_sel: 0,
+        pc_signals: [19, 27, 21, 23, 25],
+        risc_dbg_cntl0: 0xFFB1_2080,
+        risc_dbg_cntl1: 0xFFB1_2084,
+        risc_dbg_status1: 0xFFB1_208C,
+        noc_regs: 0xFFB2_0000,
+        noc_id_offset: 0x1_0000,
+        noc_status_offset: 0x200,
+    };
+
+    /// The registers of `arch`, only wormhole's have been checked against the hardware.
+    pub fn for_arch(arch: Arch) -> Result<Self, DiagnoseError> {
+        match arch {
+            Arch::Wormhole => Ok(Self::WORMHOLE),
+            arch => Err(DiagnoseError::UncheckedArch(arch)),
+        }
+    }
+
+    /// The debug bus signal holding the pc of `risc`.
+    fn pc_signal(&self, risc: RiscId) -> u32 {
+        const ENABLE: u32 = 1 << 29;
+
+        let index = RiscId::ALL.iter().position(|v| *v == risc).unwrap();
+        ENABLE | (self.pc_rd_sel << 25) | (self.pc_daisy_sel << 16) | self.pc_signals[index]
+    }
+}
+
+/// Register access to a single tile.
+pub trait TileRegisters {
+    fn read32(&mut self, addr: u64) -> u32;
+    fn write32(&mut self, addr: u64, value: u32);
+}
+
+pub struct ChipTile<'a> {
+    pub chip: &'a mut Chip,
+    pub noc_id: NocId,
+    pub tile: Tile,
+}
+
+impl TileRegisters for ChipTile<'_> {
+    fn read32(&mut self, addr: u64) -> u32 {
+        self.chip.noc_read32(self.noc_id, self.tile, addr)
+    }
+
+    fn write32(&mut self, addr: u64, value: u32) {
+        self.chip.noc_write32(self.noc_id, self.tile, addr, value)
+    }
+}
+
+/// Read the current pc of `risc` off the debug bus.
+pub fn read_pc(regs: &mut dyn TileRegisters, layout: &DebugRegisters, risc: RiscId) -> u64 {
+    regs.write32(layout.dbg_bus_cntl, layout.pc_signal(risc));
+    let pc = regs.read32(layout.dbg_rd_data) & 0x7fff_ffff;
+    regs.write32(layout.dbg_bus_cntl, 0);
+
+    pc as u64
+}
+
+/// The status register in each RISC's block of the debug interface
+pub const RISC_DBG_STATUS: u32 = 0;
+pub const STATUS_HALTED: u32 = 1 << 0;
+
+const RISC_DBG_ENABLE: u32 = 1 << 31;
+const RISC_DBG_WRITE: u32 = 1 << 16;
+
+/// The address of `reg` of `risc` in the debug interface, each RISC has a block of 8 registers.
+fn risc_dbg_addr(risc: RiscId, reg: u32) -> u32 {
+    let index = match risc {
+        RiscId::Brisc => 0,
+        RiscId::Trisc0 => 1,
+        RiscId::Trisc1 => 2,
+        RiscId::Trisc2 => 3,
+        RiscId::Ncrisc => 4,
+    };
+    index * 8 + reg
+}
+
+/// Read debug register `reg` of `risc`.
+pub fn risc_dbg_read(
+    regs: &mut dyn TileRegisters,
+    layout: &DebugRegisters,
+    risc: RiscId,
+    reg: u32,
+) -> u32 {
+    let addr = risc_dbg_addr(risc, reg);
+    regs.write32(layout.risc_dbg_cntl0, RISC_DBG_ENABLE | addr);
+    regs.write32(layout.risc_dbg_cntl0, 0);
+    regs.read32(layout.risc_dbg_status1)
+}
+
+/// Write debug register `reg` of `risc`.
+pub fn risc_dbg_write(
+    regs: &mut dyn TileRegisters,
+    layout: &DebugRegisters,
+    risc: RiscId,
+    reg: u32,
+    value: u32,
+) {
+    let addr = risc_dbg_addr(risc, reg);
+    regs.write32(layout.risc_dbg_cntl1, value);
+    regs.write32(
+        layout.risc_dbg_cntl0,
+        RISC_DBG_ENABLE | RISC_DBG_WRITE | addr,
+    );
+    regs.write32(layout.risc_dbg_cntl0, 0);
+}
+
+/// Whether `risc` is halted in debug mode.
+pub fn read_halted(regs: &mut dyn TileRegisters, layout: &DebugRegisters, risc: RiscId) -> bool {
+    risc_dbg_read(regs, layout, risc, RISC_DBG_STATUS) & STATUS_HALTED != 0
+}
+
+/// Transaction counters of one NoC interface, the master side counts what the tile sent out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NiuCounters {
+    pub noc_id: u8,
+    pub atomic_responses: u32,
+    pub write_acks: u32,
+    pub read_responses: u32,
+    pub reads_sent: u32,
+    pub nonposted_atomics_sent: u32,
+    pub nonposted_writes_sent: u32,
+    pub posted_writes_sent: u32,
+}
+
+impl NiuCounters {
+    const ATOMIC_RESP_RECEIVED: u64 = 0x0;
+    const WR_ACK_RECEIVED: u64 = 0x1;
+    const RD_RESP_RECEIVED: u64 = 0x2;
+    const RD_REQ_SENT: u64 = 0x5;
+    const NONPOSTED_ATOMIC_SENT: u64 = 0x6;
+    const NONPOSTED_WR_REQ_SENT: u64 = 0x8;
+    const POSTED_WR_REQ_SENT: u64 = 0x9;
+
+    pub fn read(regs: &mut dyn TileRegisters, layout: &DebugRegisters, noc_id: u8) -> Self {
+        let base =
+            layout.noc_regs + noc_id as u64 * layout.noc_id_offset + layout.noc_status_offset;
+        let mut counter = |index: u64| regs.read32(base + index * 4);
+
+        Self {
+            noc_id,
+            atomic_responses: counter(Self::ATOMIC_RESP_RECEIVED),
+            write_acks: counter(Self::WR_ACK_RECEIVED),
+            read_responses: counter(Self::RD_RESP_RECEIVED),
+            reads_sent: counter(Self::RD_REQ_SENT),
+            nonposted_atomics_sent: counter(Self::NONPOSTED_ATOMIC_SENT),
+            nonposted_writes_sent: counter(Self::NONPOSTED_WR_REQ_SENT),
+            posted_writes_sent: counter(Self::POSTED_WR_REQ_SENT),
+        }
+    }
+
+    pub fn reads_outstanding(&self) -> u32 {
+        self.reads_sent.wrapping_sub(self.read_responses)
+    }
+
+    pub fn writes_outstanding(&self) -> u32 {
+        self.nonposted_writes_sent.wrapping_sub(self.write_acks)
+    }
+
+    pub fn atomics_outstanding(&self) -> u32 {
+        self.nonposted_atomics_sent
+            .wrapping_sub(self.atomic_responses)
+    }
+
+    pub fn idle(&self) -> bool {
+        self.reads_outstanding() == 0
+            && self.writes_outstanding() == 0
+            && self.atomics_outstanding() == 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RiscDiagnosis {
+    pub risc: RiscId,
+    /// Held in soft reset, the pcs are meaningless
+    pub in_reset: bool,
+    /// Halted in debug mode, e.g. by an `ebreak`
+    pub halted: bool,
+    pub state: Option<RiscState>,
+    pub postcode: Option<u32>,
+    /// Sampled pcs, in order
+    pub pcs: Vec<u64>,
+    /// The last sampled pc symbolized, innermost first
+    pub frames: Vec<StackFrame>,
+}
+
+impl RiscDiagnosis {
+    /// All samples landed within [`SPIN_WINDOW`] bytes of each other.
+    pub fn spinning(&self) -> bool {
+        let (Some(min), Some(max)) = (self.pcs.iter().min(), self.pcs.iter().max()) else {
+            return false;
+        };
+        !self.in_reset && !self.halted && max - min < SPIN_WINDOW
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnosis {
+    pub tile: Tile,
+    pub soft_reset: u32,
+    pub riscs: Vec<RiscDiagnosis>,
+    pub nocs: Vec<NiuCounters>,
+}
+
+impl Diagnosis {
+    /// Sample the pcs `samples` times and read the debug status of every RISC and the NIU
+    /// counters of both NoCs.
+    pub fn collect(
+        regs: &mut dyn TileRegisters,
+        layout: &DebugRegisters,
+        tile: Tile,
+        data: &KernelData,
+        samples: usize,
+    ) -> Self {
+        let soft_reset = regs.read32(layout.soft_reset);
+
+        let mut pcs = vec![Vec::with_capacity(samples); RiscId::ALL.len()];
+        for _ in 0..samples {
+            for (index, risc) in RiscId::ALL.into_iter().enumerate() {
+                pcs[index].push(read_pc(regs, layout, risc));
+            }
+        }
+
+        let riscs = RiscId::ALL
+            .into_iter()
+            .zip(pcs)
+            .map(|(risc, pcs)| {
+                let globals = data.bin.risc(risc);
+                let frames = pcs
+                    .last()
+                    .map(|pc| resolve_pc(data.for_risc(Some(risc)), *pc))
+                    .unwrap_or_default();
+
+                RiscDiagnosis {
+                    risc,
+                    in_reset: soft_reset & crate::loader::soft_reset_bit(risc) != 0,
+                    halted: read_halted(regs, layout, risc),
+                    state: globals
+                        .state
+                        .map(|addr| RiscState::from_raw(regs.read32(addr))),
+                    postcode: globals.pc.map(|addr| regs.read32(addr)),
+                    pcs,
+                    frames,
+                }
+            })
+            .collect();
+
+        let nocs = (0..2)
+            .map(|noc| NiuCounters::read(regs, layout, noc))
+            .collect();
+
+        Self {
+            tile,
+            soft_reset,
+            riscs,
+            nocs,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (x, y) = self.tile.addr.n0;
+        write!(f, "tile {x},{y} (soft reset 0x{:08x})", self.soft_reset)?;
+
+        for risc in &self.riscs {
+            write!(f, "\n{}: ", risc.risc)?;
+            if risc.in_reset {
+                write!(f, "in reset")?;
+                continue;
+            }
+
+            let pc = risc.pcs.last().copied().unwrap_or_default();
+            if risc.halted {
+                write!(f, "halted at 0x{pc:08x}")?;
+            } else if risc.spinning() {
+                write!(f, "spinning at 0x{pc:08x}")?;
+            } else {
+                write!(f, "running, last at 0x{pc:08x}")?;
+            }
+            if let Some(state) = risc.state {
+                write!(f, ", STATE: {state}")?;
+            }
+            if let Some(postcode) = risc.postcode {
+                write!(f, ", POSTCODE: {postcode:x}")?;
+            }
+
+            for frame in &risc.frames {
+                let function = frame.function.as_deref().unwrap_or("<unknown>");
+                write!(f, "\n    {function}")?;
+                if let Some(file) = &frame.file {
+                    write!(f, " at {file}")?;
+                    if let Some(line) = frame.line {
+                        write!(f, ":{line}")?;
+                    }
+                }
+            }
+        }
+
+        for noc in &self.nocs {
+            write!(f, "\nNOC{}: ", noc.noc_id)?;
+            if noc.idle() {
+                write!(f, "idle")?;
+            } else {
+                write!(
+                    f,
+                    "{} reads, {} writes, {} atomics outstanding",
+                    noc.reads_outstanding(),
+                    noc.writes_outstanding(),
+                    noc.atomics_outstanding()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
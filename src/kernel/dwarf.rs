@@ -0,0 +1,892 @@
+//! DWARF backed view of a kernel's globals.
+//!
+//! The types of every global with a static address are parsed once when the debug info is loaded,
+//! values are then decoded from raw L1 bytes without needing the elf again.
+
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use gimli::{constants, AttributeValue, EndianArcSlice, LittleEndian, Reader, Unit, UnitOffset};
+
+pub type DwarfReader = EndianArcSlice<LittleEndian>;
+
+/// How deep pointers into L1 are followed when decoding a value
+const MAX_POINTER_DEPTH: usize = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InspectError {
+    #[error("kernel was loaded without debug info")]
+    NoDebugInfo,
+
+    #[error("global {0} not found in the debug info")]
+    NotFound(String),
+}
+
+pub type TypeId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BaseEncoding {
+    Signed,
+    Unsigned,
+    Float,
+    Bool,
+    Char,
+    Other,
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: String,
+    pub offset: u64,
+    pub ty: Option<TypeId>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Type {
+    Base {
+        name: String,
+        size: u64,
+        encoding: BaseEncoding,
+    },
+    Pointer {
+        name: String,
+        size: u64,
+        target: Option<TypeId>,
+    },
+    Struct {
+        name: String,
+        size: u64,
+        members: Vec<Member>,
+        union: bool,
+    },
+    Array {
+        element: Option<TypeId>,
+        count: u64,
+    },
+    Enum {
+        name: String,
+        size: u64,
+        /// Whether values are sign extended, from the underlying type
+        signed: bool,
+        variants: Vec<(String, i64)>,
+    },
+    /// Typedefs and const/volatile qualifiers
+    Alias {
+        name: Option<String>,
+        target: Option<TypeId>,
+    },
+    Unknown {
+        name: String,
+        size: u64,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Global {
+    pub name: String,
+    /// Namespace qualified name, the same as `name` for globals outside of a namespace
+    pub path: String,
+    pub linkage_name: Option<String>,
+    pub addr: u64,
+    pub size: u64,
+    pub type_name: String,
+    pub ty: Option<TypeId>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Pointer {
+        addr: u64,
+        symbol: Option<String>,
+        target: Option<Box<Value>>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Value)>,
+    },
+    Array(Vec<Value>),
+    Enum {
+        name: String,
+        variant: Option<String>,
+        value: i64,
+    },
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    fn is_scalar(&self) -> bool {
+        !matches!(
+            self,
+            Value::Struct { .. }
+                | Value::Array(_)
+                | Value::Pointer {
+                    target: Some(_),
+                    ..
+                }
+        )
+    }
+
+    fn write_indented(&self, f: &mut String, indent: usize) {
+        let pad = "    ";
+        match self {
+            Value::Int(v) => write!(f, "{v}").unwrap(),
+            Value::UInt(v) => write!(f, "{v}").unwrap(),
+            Value::Float(v) => write!(f, "{v}").unwrap(),
+            Value::Bool(v) => write!(f, "{v}").unwrap(),
+            Value::Char(v) => write!(f, "{v:?}").unwrap(),
+            Value::Pointer {
+                addr,
+                symbol,
+                target,
+            } => {
+                write!(f, "0x{addr:x}").unwrap();
+                if let Some(symbol) = symbol {
+                    write!(f, " <{symbol}>").unwrap();
+                }
+                if let Some(target) = target {
+                    f.push_str(" -> ");
+                    target.write_indented(f, indent);
+                }
+            }
+            Value::Struct { name, fields } => {
+                if fields.is_empty() {
+                    f.push_str(name);
+                    return;
+                }
+                writeln!(f, "{name} {{").unwrap();
+                for (field, value) in fields {
+                    write!(f, "{}{field}: ", pad.repeat(indent + 1)).unwrap();
+                    value.write_indented(f, indent + 1);
+                    f.push_str(",\n");
+                }
+                write!(f, "{}}}", pad.repeat(indent)).unwrap();
+            }
+            Value::Array(values) => {
+                if values.iter().all(|v| v.is_scalar()) {
+                    f.push('[');
+                    for (index, value) in values.iter().enumerate() {
+                        if index > 0 {
+                            f.push_str(", ");
+                        }
+                        value.write_indented(f, indent);
+                    }
+                    f.push(']');
+                } else {
+                    f.push_str("[\n");
+                    for value in values {
+                        f.push_str(&pad.repeat(indent + 1));
+                        value.write_indented(f, indent + 1);
+                        f.push_str(",\n");
+                    }
+                    write!(f, "{}]", pad.repeat(indent)).unwrap();
+                }
+            }
+            Value::Enum {
+                name,
+                variant,
+                value,
+            } => match variant {
+                Some(variant) => write!(f, "{name}::{variant}").unwrap(),
+                None => write!(f, "{name}({value})").unwrap(),
+            },
+            Value::Bytes(bytes) => {
+                f.push('<');
+                for (index, byte) in bytes.iter().enumerate() {
+                    if index > 0 {
+                        f.push(' ');
+                    }
+                    write!(f, "{byte:02x}").unwrap();
+                }
+                f.push('>');
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        self.write_indented(&mut out, 0);
+        f.write_str(&out)
+    }
+}
+
+/// A decoded global, displays as `NAME @ 0xaddr: type = value`
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    pub global: Global,
+    pub value: Value,
+}
+
+impl std::fmt::Display for Inspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} @ 0x{:x}: {} = {}",
+            self.global.path, self.global.addr, self.global.type_name, self.value
+        )
+    }
+}
+
+#[derive(Clone)]
+struct Section {
+    range: std::ops::Range<usize>,
+    addr: u64,
+}
+
+pub struct DebugInfo {
+    elf: DwarfReader,
+    sections: HashMap<String, Section>,
+    pub types: Vec<Type>,
+    pub globals: Vec<Global>,
+}
+
+impl std::fmt::Debug for DebugInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugInfo")
+            .field("types", &self.types.len())
+            .field("globals", &self.globals.len())
+            .finish()
+    }
+}
+
+struct TypeParser<'a> {
+    dwarf: &'a gimli::Dwarf<DwarfReader>,
+    types: Vec<Type>,
+    // Keyed by the global offset of the type's DIE
+    cache: HashMap<usize, TypeId>,
+}
+
+impl<'a> TypeParser<'a> {
+    fn name(
+        &self,
+        unit: &Unit<DwarfReader>,
+        entry: &gimli::DebuggingInformationEntry<DwarfReader>,
+        attr: constants::DwAt,
+    ) -> Option<String> {
+        let value = entry.attr_value(attr).ok()??;
+        let name = self.dwarf.attr_string(unit, value).ok()?;
+        name.to_string_lossy().ok().map(|v| v.to_string())
+    }
+
+    fn udata(
+        entry: &gimli::DebuggingInformationEntry<DwarfReader>,
+        attr: constants::DwAt,
+    ) -> Option<u64> {
+        entry.attr(attr).ok()??.udata_value()
+    }
+
+    fn type_ref(
+        &mut self,
+        unit: &Unit<DwarfReader>,
+        entry: &gimli::DebuggingInformationEntry<DwarfReader>,
+    ) -> Option<TypeId> {
+        match entry.attr_value(constants::DW_AT_type).ok()?? {
+            AttributeValue::UnitRef(offset) => self.parse(unit, offset),
+            _ => None,
+        }
+    }
+
+    fn parse(&mut self, unit: &Unit<DwarfReader>, offset: UnitOffset) -> Option<TypeId> {
+        let key = offset.to_debug_info_offset(&unit.header)?.0;
+        if let Some(id) = self.cache.get(&key) {
+            return Some(*id);
+        }
+
+        // Reserve the slot first so that self referential types terminate
+        let id = self.types.len();
+        self.types.push(Type::Unknown {
+            name: String::new(),
+            size: 0,
+        });
+        self.cache.insert(key, id);
+
+        let ty = self.parse_entry(unit, offset).unwrap_or(Type::Unknown {
+            name: "?".to_string(),
+            size: 0,
+        });
+        self.types[id] = ty;
+
+        Some(id)
+    }
+
+    fn parse_entry(&mut self, unit: &Unit<DwarfReader>, offset: UnitOffset) -> Option<Type> {
+        let entry = unit.entry(offset).ok()?;
+        let name = self.name(unit, &entry, constants::DW_AT_name);
+        let size = Self::udata(&entry, constants::DW_AT_byte_size);
+
+        Some(match entry.tag() {
+            constants::DW_TAG_base_type => {
+                let encoding = match entry.attr_value(constants::DW_AT_encoding).ok()? {
+                    Some(AttributeValue::Encoding(encoding)) => match encoding {
+                        constants::DW_ATE_signed | constants::DW_ATE_signed_char => {
+                            BaseEncoding::Signed
+                        }
+                        constants::DW_ATE_unsigned | constants::DW_ATE_unsigned_char => {
+                            BaseEncoding::Unsigned
+                        }
+                        constants::DW_ATE_float => BaseEncoding::Float,
+                        constants::DW_ATE_boolean => BaseEncoding::Bool,
+                        constants::DW_ATE_UTF => BaseEncoding::Char,
+                        _ => BaseEncoding::Other,
+                    },
+                    _ => BaseEncoding::Other,
+                };
+
+                Type::Base {
+                    name: name.unwrap_or_default(),
+                    size: size.unwrap_or(0),
+                    encoding,
+                }
+            }
+            constants::DW_TAG_pointer_type | constants::DW_TAG_reference_type => {
+                let target = self.type_ref(unit, &entry);
+                Type::Pointer {
+                    name: name.unwrap_or_else(|| "*".to_string()),
+                    size: size.unwrap_or(unit.encoding().address_size as u64),
+                    target,
+                }
+            }
+            tag @ (constants::DW_TAG_structure_type
+            | constants::DW_TAG_class_type
+            | constants::DW_TAG_union_type) => {
+                let mut members = Vec::new();
+
+                let mut tree = unit.entries_tree(Some(offset)).ok()?;
+                let root = tree.root().ok()?;
+                let mut children = root.children();
+                while let Ok(Some(child)) = children.next() {
+                    let child = child.entry();
+                    if child.tag() != constants::DW_TAG_member {
+                        continue;
+                    }
+                    // Static members don't live inside of the struct
+                    if child
+                        .attr(constants::DW_AT_external)
+                        .ok()
+                        .flatten()
+                        .is_some()
+                    {
+                        continue;
+                    }
+
+                    let member_name = self
+                        .name(unit, child, constants::DW_AT_name)
+                        .unwrap_or_default();
+                    let member_offset =
+                        Self::udata(child, constants::DW_AT_data_member_location).unwrap_or(0);
+                    let child = child.clone();
+                    let ty = self.type_ref(unit, &child);
+                    members.push(Member {
+                        name: member_name,
+                        offset: member_offset,
+                        ty,
+                    });
+                }
+
+                Type::Struct {
+                    name: name.unwrap_or_default(),
+                    size: size.unwrap_or(0),
+                    members,
+                    union: tag == constants::DW_TAG_union_type,
+                }
+            }
+            constants::DW_TAG_array_type => {
+                let element = self.type_ref(unit, &entry);
+
+                // Each subrange is a dimension, [[T; 4]; 2] is emitted as one array with two
+                let mut dims = Vec::new();
+                let mut tree = unit.entries_tree(Some(offset)).ok()?;
+                let root = tree.root().ok()?;
+                let mut children = root.children();
+                while let Ok(Some(child)) = children.next() {
+                    let child = child.entry();
+                    if child.tag() != constants::DW_TAG_subrange_type {
+                        continue;
+                    }
+                    let count = Self::udata(child, constants::DW_AT_count)
+                        .or_else(|| {
+                            let lower =
+                                Self::udata(child, constants::DW_AT_lower_bound).unwrap_or(0);
+                            Self::udata(child, constants::DW_AT_upper_bound)
+                                .map(|upper| upper + 1 - lower)
+                        })
+                        .unwrap_or(0);
+                    dims.push(count);
+                }
+                if dims.is_empty() {
+                    dims.push(0);
+                }
+
+                let mut element = element;
+                for count in dims.iter().skip(1).rev() {
+                    self.types.push(Type::Array {
+                        element,
+                        count: *count,
+                    });
+                    element = Some(self.types.len() - 1);
+                }
+
+                Type::Array {
+                    element,
+                    count: dims[0],
+                }
+            }
+            constants::DW_TAG_enumeration_type => {
+                let underlying = self.type_ref(unit, &entry);
+
+                let mut enumerators = Vec::new();
+
+                let mut tree = unit.entries_tree(Some(offset)).ok()?;
+                let root = tree.root().ok()?;
+                let mut children = root.children();
+                while let Ok(Some(child)) = children.next() {
+                    let child = child.entry();
+                    if child.tag() != constants::DW_TAG_enumerator {
+                        continue;
+                    }
+                    let variant = self
+                        .name(unit, child, constants::DW_AT_name)
+                        .unwrap_or_default();
+                    let value = child
+                        .attr_value(constants::DW_AT_const_value)
+                        .ok()
+                        .flatten();
+                    enumerators.push((variant, value));
+                }
+
+                // Without an underlying type only negative enumerators make the enum signed
+                let signed = match underlying {
+                    Some(ty) => base_encoding(&self.types, ty, 0) == Some(BaseEncoding::Signed),
+                    None => enumerators
+                        .iter()
+                        .any(|(_, v)| matches!(v, Some(AttributeValue::Sdata(v)) if *v < 0)),
+                };
+                let variants = enumerators
+                    .into_iter()
+                    .map(|(variant, value)| {
+                        let value = value.and_then(|v| {
+                            if signed {
+                                v.sdata_value()
+                            } else {
+                                v.udata_value().map(|v| v as i64)
+                            }
+                        });
+                        (variant, value.unwrap_or(0))
+                    })
+                    .collect();
+
+                let size = match size {
+                    Some(size) => size,
+                    None => underlying.map(|ty| self.size_of(ty)).unwrap_or(4),
+                };
+
+                Type::Enum {
+                    name: name.unwrap_or_default(),
+                    size,
+                    signed,
+                    variants,
+                }
+            }
+            constants::DW_TAG_typedef
+            | constants::DW_TAG_const_type
+            | constants::DW_TAG_volatile_type
+            | constants::DW_TAG_atomic_type => Type::Alias {
+                name,
+                target: self.type_ref(unit, &entry),
+            },
+            _ => Type::Unknown {
+                name: name.unwrap_or_else(|| "?".to_string()),
+                size: size.unwrap_or(0),
+            },
+        })
+    }
+
+    fn size_of(&self, ty: TypeId) -> u64 {
+        size_of_type(&self.types, ty, 0)
+    }
+}
+
+fn size_of_type(types: &[Type], ty: TypeId, depth: usize) -> u64 {
+    if depth > 32 {
+        return 0;
+    }
+
+    match &types[ty] {
+        Type::Base { size, .. }
+        | Type::Pointer { size, .. }
+        | Type::Struct { size, .. }
+        | Type::Enum { size, .. }
+        | Type::Unknown { size, .. } => *size,
+        Type::Array { element, count } => element
+            .map(|element| size_of_type(types, element, depth + 1) * count)
+            .unwrap_or(0),
+        Type::Alias { target, .. } => target
+            .map(|target| size_of_type(types, target, depth + 1))
+            .unwrap_or(0),
+    }
+}
+
+/// The encoding of `ty` if it is a base type, looking through aliases.
+fn base_encoding(types: &[Type], ty: TypeId, depth: usize) -> Option<BaseEncoding> {
+    if depth > 32 {
+        return None;
+    }
+
+    match &types[ty] {
+        Type::Base { encoding, .. } => Some(*encoding),
+        Type::Alias { target, .. } => base_encoding(types, (*target)?, depth + 1),
+        _ => None,
+    }
+}
+
+fn type_name(types: &[Type], ty: Option<TypeId>, depth: usize) -> String {
+    let Some(ty) = ty else {
+        return "()".to_string();
+    };
+    if depth > 32 {
+        return "...".to_string();
+    }
+
+    match &types[ty] {
+        Type::Base { name, .. }
+        | Type::Pointer { name, .. }
+        | Type::Struct { name, .. }
+        | Type::Enum { name, .. }
+        | Type::Unknown { name, .. } => name.clone(),
+        Type::Array { element, count } => {
+            format!("[{}; {count}]", type_name(types, *element, depth + 1))
+        }
+        Type::Alias { name, target } => name
+            .clone()
+            .unwrap_or_else(|| type_name(types, *target, depth + 1)),
+    }
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+    let mut value = [0; 8];
+    let len = bytes.len().min(8);
+    value[..len].copy_from_slice(&bytes[..len]);
+    u64::from_le_bytes(value)
+}
+
+fn read_int(bytes: &[u8]) -> i64 {
+    let len = bytes.len().min(8);
+    if len == 0 {
+        return 0;
+    }
+    let shift = 64 - 8 * len as u32;
+    ((read_uint(bytes) << shift) as i64) >> shift
+}
+
+impl DebugInfo {
+    /// Returns None if the elf has neither DWARF info nor unwind tables
+    pub fn parse(elf: &[u8]) -> Option<Self> {
+        let bin = goblin::elf::Elf::parse(elf).ok()?;
+
+        let sections = bin
+            .section_headers
+            .iter()
+            .filter_map(|section| {
+                let name = bin.shdr_strtab.get_at(section.sh_name)?;
+                let range = section.file_range()?;
+                Some((
+                    name.to_string(),
+                    Section {
+                        range,
+                        addr: section.sh_addr,
+                    },
+                ))
+            })
+            .collect::<HashMap<_, _>>();
+
+        if ![".debug_info", ".debug_frame", ".eh_frame"]
+            .iter()
+            .any(|name| sections.contains_key(*name))
+        {
+            return None;
+        }
+
+        let mut info = DebugInfo {
+            elf: EndianArcSlice::new(Arc::from(elf), LittleEndian),
+            sections,
+            types: Vec::new(),
+            globals: Vec::new(),
+        };
+
+        let dwarf = info.dwarf();
+        let mut parser = TypeParser {
+            dwarf: &dwarf,
+            types: Vec::new(),
+            cache: HashMap::new(),
+        };
+        let mut globals = Vec::new();
+
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let Ok(unit) = dwarf.unit(header) else {
+                continue;
+            };
+
+            let mut namespaces: Vec<(isize, String)> = Vec::new();
+            let mut depth = 0;
+            let mut entries = unit.entries();
+            while let Ok(Some((delta, entry))) = entries.next_dfs() {
+                depth += delta;
+                while namespaces.last().map(|v| v.0 >= depth).unwrap_or(false) {
+                    namespaces.pop();
+                }
+
+                if entry.tag() == constants::DW_TAG_namespace {
+                    let name = parser
+                        .name(&unit, entry, constants::DW_AT_name)
+                        .unwrap_or_default();
+                    namespaces.push((depth, name));
+                    continue;
+                }
+
+                if entry.tag() != constants::DW_TAG_variable {
+                    continue;
+                }
+
+                let Some(AttributeValue::Exprloc(expr)) =
+                    entry.attr_value(constants::DW_AT_location).ok().flatten()
+                else {
+                    continue;
+                };
+                let mut ops = expr.operations(unit.encoding());
+                let Ok(Some(gimli::Operation::Address { address })) = ops.next() else {
+                    continue;
+                };
+
+                let Some(name) = parser.name(&unit, entry, constants::DW_AT_name) else {
+                    continue;
+                };
+                let linkage_name = parser.name(&unit, entry, constants::DW_AT_linkage_name);
+
+                let path = namespaces
+                    .iter()
+                    .map(|v| v.1.as_str())
+                    .chain(std::iter::once(name.as_str()))
+                    .collect::<Vec<_>>()
+                    .join("::");
+
+                let entry = entry.clone();
+                let ty = parser.type_ref(&unit, &entry);
+
+                globals.push(Global {
+                    name,
+                    path,
+                    linkage_name,
+                    addr: address,
+                    size: ty.map(|ty| parser.size_of(ty)).unwrap_or(0),
+                    type_name: type_name(&parser.types, ty, 0),
+                    ty,
+                });
+            }
+        }
+
+        info.types = parser.types;
+        globals.sort_by_key(|global| global.addr);
+        info.globals = globals;
+
+        Some(info)
+    }
+
+    pub(crate) fn section(&self, name: &str) -> DwarfReader {
+        match self.sections.get(name) {
+            Some(section) => self.elf.range(section.range.clone()),
+            None => self.elf.range(0..0),
+        }
+    }
+
+    pub(crate) fn section_addr(&self, name: &str) -> Option<u64> {
+        self.sections.get(name).map(|section| section.addr)
+    }
+
+    /// The sections are shared with the elf so loading is cheap.
+    pub fn dwarf(&self) -> gimli::Dwarf<DwarfReader> {
+        gimli::Dwarf::load(|id| -> Result<DwarfReader, gimli::Error> {
+            Ok(self.section(id.name()))
+        })
+        .unwrap()
+    }
+
+    /// Look a global up by its name, namespace path or linkage name.
+    pub fn global(&self, name: &str) -> Option<&Global> {
+        self.globals
+            .iter()
+            .find(|global| global.name == name)
+            .or_else(|| self.globals.iter().find(|global| global.path == name))
+            .or_else(|| {
+                self.globals
+                    .iter()
+                    .find(|global| global.linkage_name.as_deref() == Some(name))
+            })
+    }
+
+    /// The global containing `addr` as `NAME+offset`
+    pub fn symbolize_data(&self, addr: u64) -> Option<String> {
+        let global = self
+            .globals
+            .iter()
+            .rev()
+            .find(|global| global.addr <= addr && addr < global.addr + global.size.max(1))?;
+
+        let offset = addr - global.addr;
+        Some(if offset == 0 {
+            global.path.clone()
+        } else {
+            format!("{}+0x{offset:x}", global.path)
+        })
+    }
+
+    pub fn size_of(&self, ty: TypeId) -> u64 {
+        size_of_type(&self.types, ty, 0)
+    }
+
+    pub fn type_name(&self, ty: Option<TypeId>) -> String {
+        type_name(&self.types, ty, 0)
+    }
+
+    /// Decode a global, `read` is used to fetch the global and any L1 memory pointed to by it.
+    pub fn inspect(
+        &self,
+        global: &Global,
+        l1_size: u64,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) -> Inspection {
+        let bytes = read(global.addr, global.size as usize);
+        Inspection {
+            global: global.clone(),
+            value: self.decode(global.ty, &bytes, 0, l1_size, read),
+        }
+    }
+
+    pub fn decode(
+        &self,
+        ty: Option<TypeId>,
+        bytes: &[u8],
+        depth: usize,
+        l1_size: u64,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) -> Value {
+        let Some(ty) = ty else {
+            return Value::Bytes(bytes.to_vec());
+        };
+
+        match &self.types[ty] {
+            Type::Base { encoding, size, .. } => {
+                let bytes = &bytes[..(*size as usize).min(bytes.len())];
+                match encoding {
+                    BaseEncoding::Signed => Value::Int(read_int(bytes)),
+                    BaseEncoding::Unsigned => Value::UInt(read_uint(bytes)),
+                    BaseEncoding::Bool => Value::Bool(read_uint(bytes) != 0),
+                    BaseEncoding::Char => char::from_u32(read_uint(bytes) as u32)
+                        .map(Value::Char)
+                        .unwrap_or(Value::UInt(read_uint(bytes))),
+                    BaseEncoding::Float if bytes.len() == 4 => {
+                        Value::Float(f32::from_bits(read_uint(bytes) as u32) as f64)
+                    }
+                    BaseEncoding::Float if bytes.len() == 8 => {
+                        Value::Float(f64::from_bits(read_uint(bytes)))
+                    }
+                    _ => Value::Bytes(bytes.to_vec()),
+                }
+            }
+            Type::Pointer { size, target, .. } => {
+                let addr = read_uint(&bytes[..(*size as usize).min(bytes.len())]);
+
+                let target_size = target.map(|target| self.size_of(target)).unwrap_or(0);
+                let in_l1 = addr != 0 && addr + target_size <= l1_size;
+                let target = if in_l1 && target_size > 0 && depth < MAX_POINTER_DEPTH {
+                    let pointee = read(addr, target_size as usize);
+                    Some(Box::new(self.decode(
+                        *target,
+                        &pointee,
+                        depth + 1,
+                        l1_size,
+                        read,
+                    )))
+                } else {
+                    None
+                };
+
+                Value::Pointer {
+                    addr,
+                    symbol: self.symbolize_data(addr),
+                    target,
+                }
+            }
+            Type::Struct {
+                name,
+                members,
+                union,
+                size,
+            } => {
+                if members.is_empty() && *size > 0 {
+                    return Value::Bytes(bytes[..(*size as usize).min(bytes.len())].to_vec());
+                }
+
+                let fields = members
+                    .iter()
+                    .map(|member| {
+                        let offset = if *union { 0 } else { member.offset as usize };
+                        let bytes = bytes.get(offset..).unwrap_or(&[]);
+                        (
+                            member.name.clone(),
+                            self.decode(member.ty, bytes, depth, l1_size, read),
+                        )
+                    })
+                    .collect();
+
+                Value::Struct {
+                    name: name.clone(),
+                    fields,
+                }
+            }
+            Type::Array { element, count } => {
+                let stride = element.map(|element| self.size_of(element)).unwrap_or(0) as usize;
+                let values = (0..*count as usize)
+                    .map(|index| {
+                        let bytes = bytes.get(index * stride..).unwrap_or(&[]);
+                        self.decode(*element, bytes, depth, l1_size, read)
+                    })
+                    .collect();
+
+                Value::Array(values)
+            }
+            Type::Enum {
+                name,
+                size,
+                signed,
+                variants,
+            } => {
+                let bytes = &bytes[..(*size as usize).min(bytes.len())];
+                let value = if *signed {
+                    read_int(bytes)
+                } else {
+                    read_uint(bytes) as i64
+                };
+                let variant = variants
+                    .iter()
+                    .find(|(_, v)| *v == value)
+                    .map(|(name, _)| name.clone());
+
+                Value::Enum {
+                    name: name.clone(),
+                    variant,
+                    value,
+                }
+            }
+            Type::Alias { target, .. } => self.decode(*target, bytes, depth, l1_size, read),
+            Type::Unknown { size, .. } => {
+                Value::Bytes(bytes[..(*size as usize).min(bytes.len())].to_vec())
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! On-disk format for prebuilt kernels.
+//!
+//! All integers are little endian.
+//!
+//! ```text
+//! magic    b"TTXKIMG\0"
+//! version  u32
+//! length   u64           length of the payload
+//! payload  [u8; length]
+//! crc32    u32           crc of the payload
+//! ```
+//!
+//! The payload holds the target, the build options, the loadable segments with their writable
+//! flag and zeroed length, the symbol table with sizes and sections, the log format strings and
+//! the entry point.
+//! The `KernelBinData` symbol locations are rebuilt from the symbol table when the image is opened.
+//! Programs merged from a `CoreProgram` are saved without their per RISC parts.
+//!
+//! Debug info is not stored, images are meant for loading rather than inspection.
+
+use std::collections::HashMap;
+
+use super::{BuildInfo, KernelBytes, KernelData, LogFormats, Symbol};
+
+const MAGIC: &[u8; 8] = b"TTXKIMG\0";
+pub const IMAGE_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("not a kernel image")]
+    BadMagic,
+
+    #[error("kernel image version {0} is not supported (expected {IMAGE_VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("kernel image checksum mismatch: expected {expected:x} found {found:x}")]
+    Checksum { expected: u32, found: u32 },
+
+    #[error("kernel image is truncated")]
+    Truncated,
+
+    #[error("kernel image contains an invalid string")]
+    InvalidString,
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.0.extend(value);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ImageError> {
+        let end = self.pos.checked_add(len).ok_or(ImageError::Truncated)?;
+        let value = self.data.get(self.pos..end).ok_or(ImageError::Truncated)?;
+        self.pos = end;
+        Ok(value)
+    }
+
+    fn u8(&mut self) -> Result<u8, ImageError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ImageError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ImageError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], ImageError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> Result<String, ImageError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| ImageError::InvalidString)
+    }
+}
+
+pub fn encode(data: &KernelData) -> Vec<u8> {
+    let mut payload = Writer(Vec::new());
+
+    if let Some(target) = &data.target {
+        payload.u8(1);
+        payload.str(target);
+    } else {
+        payload.u8(0);
+    }
+
+    if let Some(build) = &data.build {
+        payload.u8(1);
+        payload.str(&build.kernel_name);
+        payload.str(&build.profile);
+        payload.u8(build.lto as u8);
+        payload.u8(build.build_std as u8);
+        payload.u8(build.default_features as u8);
+    } else {
+        payload.u8(0);
+    }
+
+    payload.u32(data.writes.len() as u32);
+    for write in &data.writes {
+        payload.u32(write.addr);
+        payload.bytes(&write.data.0);
+        payload.u8(write.writable as u8);
+        payload.u32(write.zeroed);
+    }
+
+    // Sort the symbols so that the same kernel always produces the same image
+    let mut symbols = data.symbols.iter().collect::<Vec<_>>();
+    symbols.sort_by_key(|(name, _)| *name);
+
+    payload.u32(symbols.len() as u32);
+    for (name, symbol) in symbols {
+        payload.str(name);
+        payload.u64(symbol.addr);
+        payload.u64(symbol.size);
+        if let Some(section) = &symbol.section {
+            payload.u8(1);
+            payload.str(section);
+        } else {
+            payload.u8(0);
+        }
+    }
+
+    let mut formats = data.log_formats.0.iter().collect::<Vec<_>>();
+    formats.sort();
+
+    payload.u32(formats.len() as u32);
+    for (id, format) in formats {
+        payload.u32(*id as u32);
+        payload.str(format);
+    }
+
+    payload.u64(data.entry);
+
+    let payload = payload.0;
+
+    let mut image = Writer(Vec::with_capacity(payload.len() + 24));
+    image.0.extend(MAGIC);
+    image.u32(IMAGE_VERSION);
+    image.u64(payload.len() as u64);
+    image.0.extend(&payload);
+    image.u32(crc32fast::hash(&payload));
+
+    image.0
+}
+
+pub fn decode(image: &[u8]) -> Result<KernelData, ImageError> {
+    let mut reader = Reader {
+        data: image,
+        pos: 0,
+    };
+
+    if reader.take(MAGIC.len()).map_err(|_| ImageError::BadMagic)? != MAGIC {
+        return Err(ImageError::BadMagic);
+    }
+
+    let version = reader.u32()?;
+    if version != IMAGE_VERSION {
+        return Err(ImageError::UnsupportedVersion(version));
+    }
+
+    let len = reader.u64()? as usize;
+    let payload = reader.take(len)?;
+    let expected = reader.u32()?;
+    let found = crc32fast::hash(payload);
+    if expected != found {
+        return Err(ImageError::Checksum { expected, found });
+    }
+
+    let mut reader = Reader {
+        data: payload,
+        pos: 0,
+    };
+
+    let target = if reader.u8()? != 0 {
+        Some(reader.str()?)
+    } else {
+        None
+    };
+
+    let build = if reader.u8()? != 0 {
+        Some(BuildInfo {
+            kernel_name: reader.str()?,
+            profile: reader.str()?,
+            lto: reader.u8()? != 0,
+            build_std: reader.u8()? != 0,
+            default_features: reader.u8()? != 0,
+            elf: None,
+        })
+    } else {
+        None
+    };
+
+    let write_count = reader.u32()?;
+    let mut writes = Vec::with_capacity(write_count as usize);
+    for _ in 0..write_count {
+        let addr = reader.u32()?;
+        let data = reader.bytes()?;
+        let (writable, zeroed) = (reader.u8()? != 0, reader.u32()?);
+        writes.push(KernelBytes::new(addr, data.to_vec(), writable, zeroed));
+    }
+
+    let sym_count = reader.u32()?;
+    let mut symbols = HashMap::with_capacity(sym_count as usize);
+    for _ in 0..sym_count {
+        let name = reader.str()?;
+        let addr = reader.u64()?;
+        let size = reader.u64()?;
+        let section = if reader.u8()? != 0 {
+            Some(reader.str()?)
+        } else {
+            None
+        };
+
+        symbols.insert(
+            name,
+            Symbol {
+                addr,
+                size,
+                section,
+            },
+        );
+    }
+
+    let mut log_formats = LogFormats::default();
+    for _ in 0..reader.u32()? {
+        let id = reader.u32()? as u16;
+        log_formats.0.insert(id, reader.str()?);
+    }
+
+    let entry = reader.u64()?;
+
+    Ok(KernelData {
+        target,
+        build,
+        log_formats,
+        entry,
+        ..KernelData::new(writes, symbols)
+    })
+}
@@ -0,0 +1,219 @@
+//! Host side of the device log ring buffers.
+//!
+//! Each RISC that logs has a `LOG_BUFFER_<RISC>` global laid out as
+//!
+//! ```text
+//! write  u32          bytes written by the device, wrapping
+//! read   u32          bytes consumed by the host, wrapping
+//! size   u32          size of data in bytes, a multiple of 4
+//! _      u32
+//! data   [u8; size]
+//! ```
+//!
+//! Records are whole words: a header `id: u16 | level: u8 | nargs: u8` followed by `nargs` u32
+//! arguments. The id is the offset of the record's format string in the `.ttx_log` section, which
+//! is kept in the elf but not loaded, so only the arguments ever cross the NOC.
+//!
+//! Format strings use `{}` placeholders with an optional type and display hint, e.g. `{=i32}`,
+//! `{=f32}`, `{=u64:x}` or `{:#x}`. Untyped placeholders are u32.
+
+use std::collections::HashMap;
+
+use super::{RiscId, Symbol};
+
+pub const LOG_SECTION: &str = ".ttx_log";
+
+const HEADER_SIZE: u64 = 16;
+/// Offset of the host's read pointer in the ring buffer header
+pub const READ_PTR_OFFSET: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn from_raw(value: u8) -> Self {
+        match value {
+            0 => LogLevel::Trace,
+            1 => LogLevel::Debug,
+            2 => LogLevel::Info,
+            3 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    pub risc: RiscId,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+/// The interned format strings of a kernel, keyed by their offset in the log section.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogFormats(pub HashMap<u16, String>);
+
+impl LogFormats {
+    /// Parse the contents of the log section, a sequence of nul terminated strings.
+    pub fn parse(section: &[u8]) -> Self {
+        let mut formats = HashMap::new();
+        let mut offset = 0;
+        for string in section.split(|v| *v == 0) {
+            if !string.is_empty() && offset <= u16::MAX as usize {
+                formats.insert(offset as u16, String::from_utf8_lossy(string).to_string());
+            }
+            offset += string.len() + 1;
+        }
+
+        Self(formats)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Decode a stream of whole records, a truncated record at the end is dropped.
+    pub fn decode(&self, risc: RiscId, data: &[u8]) -> Vec<LogRecord> {
+        let words = data
+            .chunks_exact(4)
+            .map(|v| u32::from_le_bytes(v.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut records = Vec::new();
+        let mut words = words.as_slice();
+        while let Some((header, rest)) = words.split_first() {
+            let id = *header as u16;
+            let level = LogLevel::from_raw((header >> 16) as u8);
+            let nargs = (header >> 24) as usize;
+            if rest.len() < nargs {
+                break;
+            }
+            let (args, rest) = rest.split_at(nargs);
+            words = rest;
+
+            let message = match self.0.get(&id) {
+                Some(format) => format_record(format, args),
+                None => format!("<unknown log format {id:#x}> {args:x?}"),
+            };
+            records.push(LogRecord {
+                risc,
+                level,
+                message,
+            });
+        }
+
+        records
+    }
+}
+
+fn format_arg(ty: &str, hint: &str, args: &mut std::slice::Iter<u32>) -> Option<String> {
+    let mut next = || args.next().copied();
+
+    let value = match ty {
+        "" | "u32" | "u16" | "u8" | "usize" => next()? as u64,
+        "i32" | "i16" | "i8" | "isize" => next()? as i32 as i64 as u64,
+        "u64" | "i64" => next()? as u64 | ((next()? as u64) << 32),
+        "bool" => return Some((next()? != 0).to_string()),
+        "f32" => return Some(f32::from_bits(next()?).to_string()),
+        "char" => return Some(char::from_u32(next()?).unwrap_or('?').to_string()),
+        _ => next()? as u64,
+    };
+    let signed = ty.starts_with('i');
+
+    Some(match hint {
+        "x" => format!("{value:x}"),
+        "#x" => format!("{value:#x}"),
+        "X" => format!("{value:X}"),
+        "#X" => format!("{value:#X}"),
+        "b" => format!("{value:b}"),
+        "#b" => format!("{value:#b}"),
+        _ if signed => (value as i64).to_string(),
+        _ => value.to_string(),
+    })
+}
+
+pub fn format_record(format: &str, args: &[u32]) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut args = args.iter();
+
+    let mut chars = format.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '{' if chars.peek().map(|v| v.1) == Some('{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek().map(|v| v.1) == Some('}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let Some(end) = format[index..].find('}') else {
+                    out.push_str(&format[index..]);
+                    break;
+                };
+                let spec = &format[index + 1..index + end];
+                while chars.peek().map(|v| v.0 <= index + end).unwrap_or(false) {
+                    chars.next();
+                }
+
+                let (ty, hint) = spec.split_once(':').unwrap_or((spec, ""));
+                let ty = ty.strip_prefix('=').unwrap_or(ty);
+                match format_arg(ty, hint, &mut args) {
+                    Some(value) => out.push_str(&value),
+                    None => out.push_str("<missing>"),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Read the pending bytes out of the ring buffer `symbol`. Returns them along with the value the
+/// read pointer must be set to in order to consume them. A header claiming more data than fits in
+/// the symbol is garbage and nothing is read.
+pub fn drain_buffer(
+    symbol: &Symbol,
+    read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+) -> (Vec<u8>, Option<u32>) {
+    let addr = symbol.addr;
+    let header = read(addr, 12);
+    let word = |index: usize| u32::from_le_bytes(header[index * 4..][..4].try_into().unwrap());
+    let (write, read_ptr, size) = (word(0), word(1), word(2));
+
+    let capacity = symbol.size.saturating_sub(HEADER_SIZE);
+    if size as u64 > capacity {
+        tracing::warn!(
+            "log buffer at {addr:#x} claims {size} bytes of data but only {capacity} fit, ignoring it"
+        );
+        return (Vec::new(), None);
+    }
+
+    let pending = write.wrapping_sub(read_ptr);
+    if size == 0 || pending == 0 {
+        return (Vec::new(), None);
+    }
+    if pending > size {
+        tracing::warn!("log buffer at {addr:#x} overflowed, dropping {pending} bytes");
+        return (Vec::new(), Some(write));
+    }
+
+    let data_addr = addr + HEADER_SIZE;
+    let start = read_ptr % size;
+    let first = pending.min(size - start);
+
+    let mut data = read(data_addr + start as u64, first as usize);
+    if first < pending {
+        data.extend(read(data_addr, (pending - first) as usize));
+    }
+
+    (data, Some(write))
+}
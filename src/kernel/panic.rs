@@ -0,0 +1,340 @@
+//! Panic reports built from a core's `PanicData`.
+//!
+//! The pc is symbolized against the kernel's DWARF line tables (falling back to the elf symbol
+//! table), and the stack is unwound from L1 using the kernel's call frame information.
+
+use std::borrow::Cow;
+
+use gimli::{BaseAddresses, CfaRule, RegisterRule, RiscV, UnwindContext, UnwindSection};
+
+use super::{dwarf::DebugInfo, KernelData, PanicData, RiscId};
+use crate::{
+    chip::noc::{NocAddress, NocId, NocInterface},
+    Chip,
+};
+
+const MAX_FRAMES: usize = 32;
+
+/// Panic filenames and messages longer than this are assumed to be garbage
+const MAX_STRING_LEN: u32 = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame {
+    pub pc: u64,
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// The function was inlined into the frame that follows it
+    pub inlined: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanicReport {
+    /// None if the panic could not be attributed to a RISC
+    pub risc: Option<RiscId>,
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+    pub pc: u32,
+    pub sp: u32,
+    /// Innermost first, empty until the report is symbolized
+    pub frames: Vec<StackFrame>,
+}
+
+impl PanicReport {
+    pub fn read(
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: NocAddress,
+        risc: Option<RiscId>,
+        panic: &PanicData,
+    ) -> Self {
+        Self::read_with(risc, panic, &mut |addr, len| {
+            let mut data = vec![0; len];
+            chip.noc_read(noc_id, tile, addr, &mut data);
+            data
+        })
+    }
+
+    /// Like [`PanicReport::read`], `read` fetches `len` bytes at an address on the core.
+    pub fn read_with(
+        risc: Option<RiscId>,
+        panic: &PanicData,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) -> Self {
+        let mut read_string = |addr: u32, len: u32| {
+            let len = len.min(MAX_STRING_LEN) as usize;
+            let buf = if len > 0 {
+                read(addr as u64, len)
+            } else {
+                Vec::new()
+            };
+
+            // TODO(drosen): For some reason the message is not completely valid utf-8
+            String::from_utf8_lossy(&buf).to_string()
+        };
+
+        let file = read_string(panic.filename_addr, panic.filename_len);
+        let message = read_string(panic.message_addr, panic.message_len);
+
+        Self {
+            risc,
+            file,
+            line: panic.line,
+            message,
+            pc: panic.program_counter,
+            sp: panic.stack_pointer,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Unwind the stack and resolve each frame, `read` is used to fetch the core's stack.
+    pub fn symbolize(
+        &mut self,
+        data: &KernelData,
+        l1_size: u64,
+        read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+    ) {
+        let debug = data.debug.as_deref();
+        let context = debug.and_then(|debug| addr2line::Context::from_dwarf(debug.dwarf()).ok());
+
+        let pcs = match debug {
+            Some(debug) => backtrace(debug, self.pc as u64, self.sp as u64, l1_size, read),
+            None => vec![self.pc as u64],
+        };
+
+        self.frames.clear();
+        for (index, pc) in pcs.into_iter().enumerate() {
+            // Return addresses point after the call, look up the call itself
+            let probe = if index == 0 { pc } else { pc.saturating_sub(1) };
+            self.frames
+                .extend(symbolize_pc(context.as_ref(), data, pc, probe));
+        }
+    }
+}
+
+/// The frames at `pc`, innermost (inlined) first, without unwinding the stack.
+pub fn resolve_pc(data: &KernelData, pc: u64) -> Vec<StackFrame> {
+    let context = data
+        .debug
+        .as_deref()
+        .and_then(|debug| addr2line::Context::from_dwarf(debug.dwarf()).ok());
+    symbolize_pc(context.as_ref(), data, pc, pc)
+}
+
+fn symbol_name(data: &KernelData, pc: u64) -> Option<String> {
+    let (name, _) = data.symbols.iter().find(|(_, symbol)| {
+        symbol.size > 0
+            && symbol.addr <= pc
+            && pc < symbol.addr + symbol.size
+            && symbol
+                .section
+                .as_deref()
+                .map(|section| section.starts_with(".text"))
+                .unwrap_or(false)
+    })?;
+
+    Some(addr2line::demangle_auto(Cow::from(name.as_str()), None).to_string())
+}
+
+fn symbolize_pc(
+    context: Option<&addr2line::Context<super::dwarf::DwarfReader>>,
+    data: &KernelData,
+    pc: u64,
+    probe: u64,
+) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+
+    if let Some(Ok(mut iter)) = context.map(|context| context.find_frames(probe).skip_all_loads()) {
+        while let Ok(Some(frame)) = iter.next() {
+            let function = frame
+                .function
+                .as_ref()
+                .and_then(|function| function.demangle().ok())
+                .map(|function| function.to_string());
+            let location = frame.location.as_ref();
+
+            frames.push(StackFrame {
+                pc,
+                function,
+                file: location.and_then(|v| v.file).map(|v| v.to_string()),
+                line: location.and_then(|v| v.line),
+                column: location.and_then(|v| v.column),
+                inlined: true,
+            });
+        }
+    }
+
+    match frames.last_mut() {
+        Some(last) => {
+            last.inlined = false;
+            if last.function.is_none() {
+                last.function = symbol_name(data, probe);
+            }
+        }
+        None => frames.push(StackFrame {
+            pc,
+            function: symbol_name(data, probe),
+            file: None,
+            line: None,
+            column: None,
+            inlined: false,
+        }),
+    }
+
+    frames
+}
+
+#[derive(Clone, Copy)]
+struct Registers {
+    pc: u64,
+    sp: u64,
+    ra: Option<u64>,
+    fp: Option<u64>,
+}
+
+fn backtrace(
+    debug: &DebugInfo,
+    pc: u64,
+    sp: u64,
+    l1_size: u64,
+    read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+) -> Vec<u64> {
+    let mut debug_frame = gimli::DebugFrame::from(debug.section(".debug_frame"));
+    debug_frame.set_address_size(4);
+    let mut eh_frame = gimli::EhFrame::from(debug.section(".eh_frame"));
+    eh_frame.set_address_size(4);
+
+    let mut bases = BaseAddresses::default();
+    if let Some(addr) = debug.section_addr(".eh_frame") {
+        bases = bases.set_eh_frame(addr);
+    }
+    if let Some(addr) = debug.section_addr(".text") {
+        bases = bases.set_text(addr);
+    }
+
+    let mut ctx = UnwindContext::new();
+    let mut read_u32 = |addr: u64| {
+        (addr + 4 <= l1_size).then(|| {
+            let data = read(addr, 4);
+            u32::from_le_bytes(data[..4].try_into().unwrap()) as u64
+        })
+    };
+
+    let mut pcs = vec![pc];
+    let mut regs = Registers {
+        pc,
+        sp,
+        ra: None,
+        fp: None,
+    };
+
+    while pcs.len() < MAX_FRAMES {
+        let probe = if pcs.len() == 1 { regs.pc } else { regs.pc - 1 };
+        let row = match debug_frame.unwind_info_for_address(
+            &bases,
+            &mut ctx,
+            probe,
+            gimli::DebugFrame::cie_from_offset,
+        ) {
+            Ok(row) => row.clone(),
+            Err(_) => match eh_frame.unwind_info_for_address(
+                &bases,
+                &mut ctx,
+                probe,
+                gimli::EhFrame::cie_from_offset,
+            ) {
+                Ok(row) => row.clone(),
+                Err(_) => break,
+            },
+        };
+
+        let cfa = match row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => {
+                let base = match *register {
+                    RiscV::SP => Some(regs.sp),
+                    RiscV::S0 => regs.fp,
+                    _ => None,
+                };
+                base.map(|base| base.wrapping_add_signed(*offset) & 0xffff_ffff)
+            }
+            CfaRule::Expression(_) => None,
+        };
+        let Some(cfa) = cfa else {
+            break;
+        };
+
+        let mut restore = |rule: RegisterRule<usize>, current: Option<u64>| match rule {
+            RegisterRule::Undefined | RegisterRule::SameValue => current,
+            RegisterRule::Offset(offset) => read_u32(cfa.wrapping_add_signed(offset)),
+            RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add_signed(offset)),
+            RegisterRule::Register(RiscV::RA) => regs.ra,
+            RegisterRule::Register(RiscV::S0) => regs.fp,
+            RegisterRule::Constant(value) => Some(value),
+            _ => None,
+        };
+
+        // The return address of the innermost frame may still be live in a register,
+        // which the panic data doesn't record
+        let ra = match row.register(RiscV::RA) {
+            RegisterRule::Undefined => None,
+            rule => restore(rule, regs.ra),
+        };
+        let fp = restore(row.register(RiscV::S0), regs.fp);
+
+        // The stack grows down, so each caller's frame must sit above its callee
+        let Some(ra) = ra else {
+            break;
+        };
+        if ra == 0 || cfa <= regs.sp {
+            break;
+        }
+
+        pcs.push(ra);
+        regs = Registers {
+            pc: ra,
+            sp: cfa,
+            ra: None,
+            fp,
+        };
+    }
+
+    pcs
+}
+
+impl std::fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.risc {
+            Some(risc) => write!(f, "{risc}")?,
+            None => write!(f, "UNKNOWN")?,
+        }
+        writeln!(f, " panicked at {}:{}:", self.file, self.line)?;
+        writeln!(f, "{}", self.message)?;
+        write!(f, "pc: 0x{:08x}, sp: 0x{:08x}", self.pc, self.sp)?;
+
+        if !self.frames.is_empty() {
+            write!(f, "\nstack backtrace:")?;
+        }
+        for (index, frame) in self.frames.iter().enumerate() {
+            let function = frame.function.as_deref().unwrap_or("<unknown>");
+            if frame.inlined {
+                write!(f, "\n{index:>4}: {function} (inlined)")?;
+            } else {
+                write!(f, "\n{index:>4}: 0x{:08x} - {function}", frame.pc)?;
+            }
+
+            if let Some(file) = &frame.file {
+                write!(f, "\n             at {file}")?;
+                if let Some(line) = frame.line {
+                    write!(f, ":{line}")?;
+                    if let Some(column) = frame.column {
+                        write!(f, ":{column}")?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
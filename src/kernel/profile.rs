@@ -0,0 +1,134 @@
+//! Host side of the device profiler buffers.
+//!
+//! Each RISC that profiles has a `PROFILE_BUFFER_<RISC>` global laid out as
+//!
+//! ```text
+//! count  u32          markers recorded by the device, including ones that did not fit
+//! size   u32          capacity of data in markers
+//! _      u64
+//! data   [Marker; size]
+//! ```
+//!
+//! A marker is three words: a header `id: u16 | kind: u8 | _: u8` followed by the low and high
+//! words of the wall clock when it was recorded. The id is the offset of the marker's name in the
+//! `.ttx_log` section, the names are interned alongside the log format strings.
+
+use super::{LogFormats, RiscId, Symbol};
+
+const HEADER_SIZE: u64 = 16;
+/// Size of a marker in bytes
+pub const MARKER_SIZE: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkerKind {
+    /// Start of a zone
+    Begin,
+    /// End of the innermost open zone
+    End,
+    /// A single point in time
+    Instant,
+}
+
+impl MarkerKind {
+    pub fn from_raw(value: u8) -> Self {
+        match value {
+            0 => MarkerKind::Begin,
+            1 => MarkerKind::End,
+            _ => MarkerKind::Instant,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileMarker {
+    pub risc: RiscId,
+    pub name: String,
+    pub kind: MarkerKind,
+    /// Wall clock cycles, counted at AICLK
+    pub cycles: u64,
+}
+
+/// The markers recorded by one RISC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileBuffer {
+    pub risc: RiscId,
+    pub markers: Vec<ProfileMarker>,
+    /// Markers recorded after the buffer filled up
+    pub dropped: u32,
+}
+
+impl LogFormats {
+    /// The name of a profiler marker.
+    pub fn marker_name(&self, id: u16) -> String {
+        match self.0.get(&id) {
+            Some(name) => name.clone(),
+            None => format!("<unknown marker {id:#x}>"),
+        }
+    }
+}
+
+/// Decode whole markers, a truncated marker at the end is dropped.
+pub fn decode(risc: RiscId, names: &LogFormats, data: &[u8]) -> Vec<ProfileMarker> {
+    data.chunks_exact(MARKER_SIZE)
+        .map(|marker| {
+            let word =
+                |index: usize| u32::from_le_bytes(marker[index * 4..][..4].try_into().unwrap());
+            let header = word(0);
+            ProfileMarker {
+                risc,
+                name: names.marker_name(header as u16),
+                kind: MarkerKind::from_raw((header >> 16) as u8),
+                cycles: word(1) as u64 | ((word(2) as u64) << 32),
+            }
+        })
+        .collect()
+}
+
+/// Read the profile buffer `symbol`. The size in its header is not trusted past the end of the
+/// symbol.
+pub fn read_buffer(
+    risc: RiscId,
+    names: &LogFormats,
+    symbol: &Symbol,
+    read: &mut dyn FnMut(u64, usize) -> Vec<u8>,
+) -> ProfileBuffer {
+    let addr = symbol.addr;
+    let header = read(addr, 8);
+    let word = |index: usize| u32::from_le_bytes(header[index * 4..][..4].try_into().unwrap());
+    let (count, size) = (word(0), word(1));
+
+    let capacity = (symbol.size.saturating_sub(HEADER_SIZE) / MARKER_SIZE as u64)
+        .try_into()
+        .unwrap_or(u32::MAX);
+    if size > capacity {
+        tracing::warn!(
+            "{} profile buffer at {addr:#x} claims room for {size} markers but only {capacity} fit",
+            risc.name()
+        );
+    }
+
+    let stored = count.min(size).min(capacity);
+    let dropped = count - stored;
+    if dropped > 0 {
+        tracing::warn!(
+            "{} profile buffer at {addr:#x} overflowed, dropped {dropped} markers",
+            risc.name()
+        );
+    }
+
+    let markers = if stored == 0 {
+        Vec::new()
+    } else {
+        decode(
+            risc,
+            names,
+            &read(addr + HEADER_SIZE, stored as usize * MARKER_SIZE),
+        )
+    };
+
+    ProfileBuffer {
+        risc,
+        markers,
+        dropped,
+    }
+}
@@ -0,0 +1,180 @@
+//! Typed view of the per RISC state globals (`STATE_*`, `POSTCODE_*` and `PANIC_DATA_*`).
+
+use serde::{Deserialize, Serialize};
+
+use super::PanicData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RiscId {
+    Brisc,
+    Ncrisc,
+    Trisc0,
+    Trisc1,
+    Trisc2,
+}
+
+impl RiscId {
+    pub const ALL: [RiscId; 5] = [
+        RiscId::Brisc,
+        RiscId::Ncrisc,
+        RiscId::Trisc0,
+        RiscId::Trisc1,
+        RiscId::Trisc2,
+    ];
+
+    /// The suffix used by the kernel's globals, e.g. `STATE_BRISC`
+    pub fn name(&self) -> &'static str {
+        match self {
+            RiscId::Brisc => "BRISC",
+            RiscId::Ncrisc => "NCRISC",
+            RiscId::Trisc0 => "TRISC0",
+            RiscId::Trisc1 => "TRISC1",
+            RiscId::Trisc2 => "TRISC2",
+        }
+    }
+}
+
+impl std::fmt::Display for RiscId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Decoded value of a `STATE_*` global.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RiscState {
+    NotStarted,
+    /// Started but not yet complete, the raw state is kept
+    Running(u32),
+    Complete(u32),
+    Panicked,
+}
+
+impl RiscState {
+    pub const NOT_STARTED: u32 = 0;
+    pub const COMPLETE: u32 = 3;
+    pub const PANICKED: u32 = 6;
+
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            Self::NOT_STARTED => RiscState::NotStarted,
+            Self::PANICKED => RiscState::Panicked,
+            v if v >= Self::COMPLETE => RiscState::Complete(v),
+            v => RiscState::Running(v),
+        }
+    }
+
+    pub fn raw(&self) -> u32 {
+        match self {
+            RiscState::NotStarted => Self::NOT_STARTED,
+            RiscState::Running(v) | RiscState::Complete(v) => *v,
+            RiscState::Panicked => Self::PANICKED,
+        }
+    }
+
+    /// The RISC has stopped running the kernel, a panic counts as finished
+    pub fn is_finished(&self) -> bool {
+        matches!(self, RiscState::Complete(_) | RiscState::Panicked)
+    }
+}
+
+impl std::fmt::Display for RiscState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiscState::NotStarted => write!(f, "not started"),
+            RiscState::Running(v) => write!(f, "running ({v})"),
+            RiscState::Complete(v) => write!(f, "complete ({v})"),
+            RiscState::Panicked => write!(f, "panicked"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RiscStatus {
+    pub risc: RiscId,
+    /// None if the kernel has no state global for this RISC
+    pub state: Option<RiscState>,
+    pub postcode: Option<u32>,
+    pub panic: Option<PanicData>,
+}
+
+impl RiscStatus {
+    pub fn panicked(&self) -> bool {
+        self.state == Some(RiscState::Panicked)
+            || self.panic.as_ref().map(|v| v.panicked).unwrap_or(false)
+    }
+}
+
+impl std::fmt::Display for RiscStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {{", self.risc)?;
+        if let Some(state) = self.state {
+            write!(f, " STATE: {state}")?;
+            if self.postcode.is_some() {
+                write!(f, ",")?;
+            }
+        }
+        if let Some(postcode) = self.postcode {
+            write!(f, " POSTCODE: {postcode:x}")?;
+        }
+        write!(f, " }}")
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoreStatus {
+    pub sync: Option<u32>,
+    pub riscs: Vec<RiscStatus>,
+    /// Panics that could not be attributed to a RISC
+    pub unknown_panic: Option<PanicData>,
+}
+
+impl CoreStatus {
+    pub fn risc(&self, risc: RiscId) -> Option<&RiscStatus> {
+        self.riscs.iter().find(|v| v.risc == risc)
+    }
+
+    /// Marked by all cores either having completed... or not started
+    pub fn all_complete(&self) -> bool {
+        let states = self
+            .riscs
+            .iter()
+            .filter_map(|v| v.state)
+            .collect::<Vec<_>>();
+
+        let complete_count = states.iter().filter(|v| v.is_finished()).count();
+        let not_started_count = states
+            .iter()
+            .filter(|v| **v == RiscState::NotStarted)
+            .count();
+
+        // RISCs without a state global can't be monitored, so they don't hold up completion
+        states.is_empty()
+            || (complete_count > 0 && complete_count + not_started_count == states.len())
+    }
+
+    pub fn any_panicked(&self) -> bool {
+        self.riscs
+            .iter()
+            .any(|v| v.state == Some(RiscState::Panicked))
+    }
+}
+
+impl std::fmt::Display for CoreStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        if let Some(sync) = self.sync {
+            write!(f, "SYNC: {sync}")?;
+            first = false;
+        }
+        for risc in &self.riscs {
+            if !first {
+                writeln!(f)?;
+            }
+            write!(f, "{risc}")?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Checking kernel segments after they have been written to a core.
+
+use super::KernelBytes;
+use crate::{chip::noc::NocAddress, loader::ElfTargetError};
+
+/// Number of windows read back by [`VerifyPolicy::Sample`]
+pub const SAMPLE_COUNT: usize = 8;
+/// Size in bytes of each window read back by [`VerifyPolicy::Sample`]
+pub const SAMPLE_LEN: usize = 64;
+
+/// How much of a segment is read back after it is written. Defaults to `Checksum` in debug builds
+/// and `None` in release builds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerifyPolicy {
+    /// Trust the write
+    #[cfg_attr(not(debug_assertions), default)]
+    None,
+    /// Read the segment back and compare its crc with the one cached for the segment
+    #[cfg_attr(debug_assertions, default)]
+    Checksum,
+    /// Read the segment back and compare it byte for byte
+    FullReadback,
+    /// Read back [`SAMPLE_COUNT`] windows spread over the segment, including its start and end
+    Sample,
+}
+
+impl VerifyPolicy {
+    /// Check that the bytes at `write.addr` match `write`, `read` fetches `len` bytes at an address.
+    pub fn check(&self, write: &KernelBytes, read: &mut dyn FnMut(u64, usize) -> Vec<u8>) -> bool {
+        let addr = write.addr as u64;
+        let data = write.data.0.as_ref();
+        if data.is_empty() {
+            return true;
+        }
+
+        match self {
+            VerifyPolicy::None => true,
+            VerifyPolicy::Checksum => crc32fast::hash(&read(addr, data.len())) == write.crc(),
+            VerifyPolicy::FullReadback => read(addr, data.len()) == data,
+            VerifyPolicy::Sample => sample_windows(data.len())
+                .into_iter()
+                .all(|(offset, len)| read(addr + offset as u64, len) == data[offset..][..len]),
+        }
+    }
+}
+
+/// The (offset, len) of each sampled window, evenly spread and 16 byte aligned.
+pub fn sample_windows(len: usize) -> Vec<(usize, usize)> {
+    if len <= SAMPLE_COUNT * SAMPLE_LEN {
+        return vec![(0, len)];
+    }
+
+    let last = (len - SAMPLE_LEN) & !15;
+    let mut windows = (0..SAMPLE_COUNT)
+        .map(|index| ((last * index / (SAMPLE_COUNT - 1)) & !15, SAMPLE_LEN))
+        .collect::<Vec<_>>();
+    // Rounding down moved the last window away from the end, stretch it to cover the tail
+    windows[SAMPLE_COUNT - 1] = (last, len - last);
+
+    windows
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error(
+        "segment {segment} (0x{addr:x}, {len} bytes) on tile {x},{y} failed {policy:?} verification after {attempts} attempts",
+        x = tile.n0.0,
+        y = tile.n0.1
+    )]
+    Mismatch {
+        tile: NocAddress,
+        segment: usize,
+        addr: u32,
+        len: usize,
+        policy: VerifyPolicy,
+        attempts: usize,
+    },
+
+    #[error(transparent)]
+    Target(#[from] ElfTargetError),
+}
+
+/// Verification done by [`super::KernelData::load_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Verify {
+    pub policy: VerifyPolicy,
+    /// How many times a segment that fails verification is written again before giving up
+    pub retries: usize,
+    /// Refuse kernels built for another arch, see [`super::KernelData::check_target`]
+    pub check_target: bool,
+}
+
+impl Default for Verify {
+    fn default() -> Self {
+        Self {
+            policy: VerifyPolicy::default(),
+            retries: 0,
+            check_target: true,
+        }
+    }
+}
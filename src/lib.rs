@@ -1,12 +1,24 @@
 pub use chip::{open, Chip};
 pub use luwen::luwen_core::Arch;
 
+pub use bytemuck;
 pub use macros::kernel;
 pub use tensix_builder;
 
+pub mod allocator;
 pub mod chip;
+#[cfg(feature = "async")]
+pub mod driver;
+pub mod gdbstub;
 pub mod kernel;
 pub mod loader;
+pub mod parallel;
+pub mod profile;
+pub mod program;
+pub mod resident;
+pub mod sampler;
+pub mod tensor;
+pub mod trace;
 
 pub fn enumerate() -> Vec<usize> {
     luwen::ttkmd_if::PciDevice::scan()
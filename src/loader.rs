@@ -1,21 +1,37 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use goblin::elf::program_header;
+use goblin::elf::{header, program_header, Elf};
 use luwen::luwen_core::Arch;
-use tensix_builder::{CacheEnable, Rewrite};
+use tensix_builder::{CacheEnable, Rewrite, StandardTarget, TARGET_MARKER_PREFIX};
 
 use crate::{
     chip::{
         noc::{NocAddress, NocId, NocInterface, Tile},
         Chip,
     },
-    kernel::{Alignment16, CoreData, Kernel, KernelBinData, KernelBytes, KernelData},
+    kernel::{
+        log::LOG_SECTION, BuildInfo, Kernel, KernelBytes, KernelData, LogFormats, RiscId, Symbol,
+        Verify, VerifyPolicy,
+    },
 };
 
 const BRISC_SOFT_RESET: u32 = 1 << 11;
-const TRISC_SOFT_RESETS: u32 = (1 << 12) | (1 << 13) | (1 << 14);
+const TRISC0_SOFT_RESET: u32 = 1 << 12;
+const TRISC1_SOFT_RESET: u32 = 1 << 13;
+const TRISC2_SOFT_RESET: u32 = 1 << 14;
+const TRISC_SOFT_RESETS: u32 = TRISC0_SOFT_RESET | TRISC1_SOFT_RESET | TRISC2_SOFT_RESET;
 const NCRISC_SOFT_RESET: u32 = 1 << 18;
 
+pub(crate) fn soft_reset_bit(risc: RiscId) -> u32 {
+    match risc {
+        RiscId::Brisc => BRISC_SOFT_RESET,
+        RiscId::Ncrisc => NCRISC_SOFT_RESET,
+        RiscId::Trisc0 => TRISC0_SOFT_RESET,
+        RiscId::Trisc1 => TRISC1_SOFT_RESET,
+        RiscId::Trisc2 => TRISC2_SOFT_RESET,
+    }
+}
+
 pub fn reset_to_default(device: &mut Chip) {
     device.go_idle();
     device.deassert_riscv_reset();
@@ -82,6 +98,21 @@ pub fn start(
     );
 }
 
+/// Take exactly `riscs` out of reset, every other RISC on the core is held in reset.
+pub fn start_riscs(device: &mut Chip, core: NocAddress, riscs: &[RiscId]) {
+    let soft_reset_value = RiscId::ALL
+        .into_iter()
+        .filter(|risc| !riscs.contains(risc))
+        .fold(0, |value, risc| value | soft_reset_bit(risc));
+
+    device.noc_write32(NocId::Noc0, core, 0xFFB121B0, soft_reset_value);
+    let readback = device.noc_read32(NocId::Noc0, core, 0xFFB121B0);
+    debug_assert_eq!(
+        readback, soft_reset_value,
+        "Failed to start core tried to write {soft_reset_value:x} != {readback:x} "
+    );
+}
+
 pub fn easy_start(device: &mut Chip, core: NocAddress) {
     start(device, core, true, true);
 }
@@ -105,88 +136,163 @@ pub fn stop<T: Into<NocAddress>>(device: &mut Chip, core: T) {
     );
 }
 
-fn load_elf(elf: &[u8]) -> KernelData {
+const EF_RISCV_FLOAT_ABI: u32 = 0x6;
+const EF_RISCV_FLOAT_ABI_SOFT: u32 = 0x0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ElfTargetError {
+    #[error("elf machine is {0}, expected RISC-V ({riscv})", riscv = header::EM_RISCV)]
+    Machine(u16),
+
+    #[error("elf is 64-bit, tensix cores are rv32")]
+    Class,
+
+    #[error("elf float abi flags are 0x{0:x}, the tensix targets are soft-float (ilp32)")]
+    FloatAbi(u32),
+
+    #[error("elf was built for {found}, but the chip is {expected}")]
+    Arch { expected: Arch, found: String },
+
+    #[error("there is no tensix target for {0}")]
+    UnknownArch(Arch),
+}
+
+pub(crate) fn standard_target(arch: Arch) -> Result<StandardTarget, ElfTargetError> {
+    match arch {
+        Arch::Grayskull => Ok(StandardTarget::Grayskull),
+        Arch::Wormhole => Ok(StandardTarget::Wormhole),
+        Arch::Blackhole => Ok(StandardTarget::Blackhole),
+        Arch::Unknown(_) => Err(ElfTargetError::UnknownArch(arch)),
+    }
+}
+
+/// The target to build kernels for `arch` with.
+fn build_target(arch: Arch) -> StandardTarget {
+    standard_target(arch).unwrap_or_else(|err| panic!("Can't build kernel: {err}"))
+}
+
+fn target_marker<'a>(bin: &'a Elf) -> Option<&'a str> {
+    bin.syms.iter().find_map(|sym| {
+        bin.strtab
+            .get_at(sym.st_name)
+            .and_then(|name| name.strip_prefix(TARGET_MARKER_PREFIX))
+    })
+}
+
+/// Check that an elf was built for the tensix cores of `arch`.
+///
+/// The target marker is a symbol defined by tensix-builder, elfs built without it
+/// only get the machine/class/abi checks.
+pub fn check_elf_target(bin: &Elf, arch: Arch) -> Result<(), ElfTargetError> {
+    if bin.header.e_machine != header::EM_RISCV {
+        return Err(ElfTargetError::Machine(bin.header.e_machine));
+    }
+
+    if bin.is_64 {
+        return Err(ElfTargetError::Class);
+    }
+
+    if bin.header.e_flags & EF_RISCV_FLOAT_ABI != EF_RISCV_FLOAT_ABI_SOFT {
+        return Err(ElfTargetError::FloatAbi(bin.header.e_flags));
+    }
+
+    let expected = standard_target(arch)?.to_string();
+    match target_marker(bin) {
+        Some(found) if found != expected => Err(ElfTargetError::Arch {
+            expected: arch,
+            found: found.to_string(),
+        }),
+        Some(_) => Ok(()),
+        None => {
+            tracing::warn!("elf has no target marker; assuming it was built for {arch}");
+            Ok(())
+        }
+    }
+}
+
+/// Parse a kernel elf, if `arch` is set the elf is first checked against it.
+fn load_elf(elf: &[u8], arch: Option<Arch>) -> Result<KernelData, ElfTargetError> {
     let bin = goblin::elf::Elf::parse(elf).unwrap();
 
-    assert_eq!(bin.entry, 0, "Don't yet support non-zero entrypoint");
+    if let Some(arch) = arch {
+        check_elf_target(&bin, arch)?;
+    }
 
     let mut writes = vec![];
 
-    for header in bin.program_headers {
+    for header in &bin.program_headers {
         if header.p_type == program_header::PT_LOAD {
             let write = header.vm_range();
             let data = &elf[header.file_range()];
 
-            writes.push(KernelBytes {
-                addr: write.start as u32,
-                data: Alignment16(data.to_vec().into_boxed_slice()),
-            });
+            writes.push(KernelBytes::new(
+                write.start as u32,
+                data.to_vec(),
+                header.is_write(),
+                header.p_memsz.saturating_sub(header.p_filesz) as u32,
+            ));
         }
     }
 
-    let mut sym_table = HashMap::with_capacity(bin.syms.len());
+    let mut symbols = HashMap::with_capacity(bin.syms.len());
     for sym in bin.syms.iter() {
         if let Some(name) = bin.strtab.get_at(sym.st_name) {
-            sym_table.insert(name, sym.st_value);
+            let section = bin
+                .section_headers
+                .get(sym.st_shndx)
+                .and_then(|section| bin.shdr_strtab.get_at(section.sh_name))
+                .map(|v| v.to_string());
+            symbols.insert(
+                name.to_string(),
+                Symbol {
+                    addr: sym.st_value,
+                    size: sym.st_size,
+                    section,
+                },
+            );
         }
     }
 
-    let bin_data = KernelBinData {
-        start_sync: sym_table.get("START_SYNC").copied(),
-
-        brisc_state: CoreData {
-            entry: sym_table.get("__brisc_start").copied(),
-            state: sym_table.get("STATE_BRISC").copied(),
-            pc: sym_table.get("POSTCODE_BRISC").copied(),
-            panic: sym_table.get("PANIC_DATA_BRISC").copied(),
-        },
-
-        ncrisc_state: CoreData {
-            entry: sym_table.get("__ncrisc_start").copied(),
-            state: sym_table.get("STATE_NCRISC").copied(),
-            pc: sym_table.get("POSTCODE_NCRISC").copied(),
-            panic: sym_table.get("PANIC_DATA_NCRISC").copied(),
-        },
-
-        trisc0_state: CoreData {
-            entry: sym_table.get("__trisc0_start").copied(),
-            state: sym_table.get("STATE_TRISC0").copied(),
-            pc: sym_table.get("POSTCODE_TRISC0").copied(),
-            panic: sym_table.get("PANIC_DATA_TRISC0").copied(),
-        },
+    Ok(KernelData {
+        target: target_marker(&bin).map(|v| v.to_string()),
+        log_formats: log_formats(&bin, elf),
+        entry: bin.entry,
+        ..KernelData::new(writes, symbols)
+    })
+}
 
-        trisc1_state: CoreData {
-            entry: sym_table.get("__trisc1_start").copied(),
-            state: sym_table.get("STATE_TRISC1").copied(),
-            pc: sym_table.get("POSTCODE_TRISC1").copied(),
-            panic: sym_table.get("PANIC_DATA_TRISC1").copied(),
-        },
+/// Parse a kernel elf without loading it, e.g. to build a [`crate::program::CoreProgram`].
+/// If `arch` is given the elf is checked against it.
+pub fn parse_elf(elf: &[u8], arch: Option<Arch>) -> Result<KernelData, ElfTargetError> {
+    load_elf(elf, arch)
+}
 
-        trisc2_state: CoreData {
-            entry: sym_table.get("__trisc2_start").copied(),
-            state: sym_table.get("STATE_TRISC2").copied(),
-            pc: sym_table.get("POSTCODE_TRISC2").copied(),
-            panic: sym_table.get("PANIC_DATA_TRISC2").copied(),
-        },
+/// Single kernels are started by releasing BRISC, which always starts at 0
+pub(crate) fn assert_brisc_entry(data: &KernelData) {
+    assert_eq!(data.entry, 0, "Don't yet support non-zero entrypoint");
+}
 
-        data_start: sym_table.get("__firmware_end").copied(),
-        unknown_panic: sym_table.get("PANIC_DATA_UNKNOWN").copied(),
-        noc_debug: sym_table.get("NOC_DEBUG").copied(),
-        core_data_cache: Default::default(),
-    };
+/// The log section is kept in the elf but never loaded, so the format strings are pulled out here.
+fn log_formats(bin: &Elf, elf: &[u8]) -> LogFormats {
+    bin.section_headers
+        .iter()
+        .find(|section| bin.shdr_strtab.get_at(section.sh_name) == Some(LOG_SECTION))
+        .and_then(|section| section.file_range())
+        .map(|range| LogFormats::parse(&elf[range]))
+        .unwrap_or_default()
+}
 
-    KernelData {
-        bin: bin_data,
-        sym_table: sym_table
-            .into_iter()
-            .map(|v| (v.0.to_string(), v.1))
-            .collect(),
-        writes,
-    }
+fn load_elf_checked(elf: &[u8], arch: Option<Arch>) -> KernelData {
+    load_elf(elf, arch).unwrap_or_else(|err| panic!("Refusing to load kernel: {err}"))
 }
 
-fn load_to_all(device: &mut Chip, elf: &[u8]) -> KernelData {
-    let data = load_elf(elf);
+fn load_to_all(
+    device: &mut Chip,
+    elf: &[u8],
+    arch: Option<Arch>,
+) -> Result<KernelData, ElfTargetError> {
+    let data = load_elf(elf, arch)?;
+    assert_brisc_entry(&data);
 
     for write in &data.writes {
         let data = write.data.0.as_ref();
@@ -205,37 +311,77 @@ fn load_to_all(device: &mut Chip, elf: &[u8]) -> KernelData {
         };
     }
 
-    data
+    Ok(data)
 }
 
-fn load_to_cores(device: &mut Chip, cores: &[Tile], elf: &[u8]) -> KernelData {
-    let data = load_elf(elf);
-
+fn load_to_cores(
+    device: &mut Chip,
+    cores: &[Tile],
+    elf: &[u8],
+    arch: Option<Arch>,
+    verify: Verify,
+) -> Result<KernelData, ElfTargetError> {
+    let data = load_elf(elf, arch)?;
+    assert_brisc_entry(&data);
+
+    // The target was already checked (or deliberately skipped) with the elf
+    let verify = Verify {
+        check_target: false,
+        ..verify
+    };
     for core in cores.iter().copied() {
-        data.load(device, NocId::Noc0, core);
+        data.load_with(device, NocId::Noc0, core, verify)
+            .unwrap_or_else(|err| panic!("Failed to load kernel: {err}"));
     }
 
-    data
+    Ok(data)
 }
 
-fn load_to_core(mut device: Chip, noc_id: NocId, core: Tile, elf: &[u8]) -> Kernel {
-    let kernel_data = load_to_cores(&mut device, &[core], elf);
-    Kernel::new(device, noc_id, core, kernel_data)
+fn load_to_core(
+    mut device: Chip,
+    noc_id: NocId,
+    core: Tile,
+    elf: &[u8],
+    arch: Option<Arch>,
+    verify: Verify,
+) -> Result<Kernel, ElfTargetError> {
+    let kernel_data = load_to_cores(&mut device, &[core], elf, arch, verify)?;
+    Ok(Kernel::new(device, noc_id, core, kernel_data))
 }
 
-pub fn load_file_to_all(device: &mut Chip, kernel: PathBuf) -> KernelData {
+/// Load an elf onto every tensix. If `check_target` is set the elf is checked against the chip's
+/// arch first, and refused if it was built for another one.
+pub fn load_file_to_all(
+    device: &mut Chip,
+    kernel: PathBuf,
+    check_target: bool,
+) -> Result<KernelData, ElfTargetError> {
     let kernel = std::fs::read(kernel).unwrap();
-    load_to_all(device, &kernel)
+    let arch = check_target.then_some(device.arch());
+    load_to_all(device, &kernel, arch)
 }
 
-pub fn load_file_to_cores(device: &mut Chip, cores: &[Tile], kernel: PathBuf) -> KernelData {
+pub fn load_file_to_cores(
+    device: &mut Chip,
+    cores: &[Tile],
+    kernel: PathBuf,
+    check_target: bool,
+) -> Result<KernelData, ElfTargetError> {
     let kernel = std::fs::read(kernel).unwrap();
-    load_to_cores(device, cores, &kernel)
+    let arch = check_target.then_some(device.arch());
+    load_to_cores(device, cores, &kernel, arch, Verify::default())
 }
 
-pub fn load_file_to_core(device: Chip, noc_id: NocId, core: Tile, kernel: PathBuf) -> Kernel {
+pub fn load_file_to_core(
+    device: Chip,
+    noc_id: NocId,
+    core: Tile,
+    kernel: PathBuf,
+    check_target: bool,
+) -> Result<Kernel, ElfTargetError> {
     let kernel = std::fs::read(kernel).unwrap();
-    load_to_core(device, noc_id, core, &kernel)
+    let arch = check_target.then_some(device.arch());
+    load_to_core(device, noc_id, core, &kernel, arch, Verify::default())
 }
 
 pub struct LoadOptions {
@@ -251,6 +397,11 @@ pub struct LoadOptions {
     pub stack_probes: bool,
     pub hide_output: bool,
     pub noc_id: NocId,
+    pub check_target: bool,
+    pub keep_debug_info: bool,
+    pub timeout: Option<std::time::Duration>,
+    pub hang_polls: Option<usize>,
+    pub verify: Verify,
 }
 
 impl LoadOptions {
@@ -268,6 +419,11 @@ impl LoadOptions {
             stack_probes: false,
             hide_output: false,
             noc_id: NocId::Noc0,
+            check_target: true,
+            keep_debug_info: false,
+            timeout: None,
+            hang_polls: None,
+            verify: Verify::default(),
         }
     }
 }
@@ -335,6 +491,53 @@ impl LoadOptions {
         self.noc_id = noc_id;
         self
     }
+
+    /// Set to false to load elfs even if they were not built for the chip's arch.
+    pub fn check_target(mut self, check: bool) -> Self {
+        self.check_target = check;
+        self
+    }
+
+    /// Keep the DWARF info of the kernel so that globals can be inspected with `Kernel::inspect`.
+    pub fn keep_debug_info(mut self, keep: bool) -> Self {
+        self.keep_debug_info = keep;
+        self
+    }
+
+    /// Give up waiting for the kernel after `timeout`, see [`Kernel::wait_for`].
+    pub fn timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up waiting once the kernel's states and postcodes haven't changed for `polls` polls.
+    pub fn hang_polls(mut self, polls: usize) -> Self {
+        self.hang_polls = Some(polls);
+        self
+    }
+
+    /// How much of each segment is read back after loading, see [`VerifyPolicy`].
+    pub fn verify(mut self, policy: VerifyPolicy) -> Self {
+        self.verify.policy = policy;
+        self
+    }
+
+    /// Rewrite a segment that fails verification up to `retries` times before giving up.
+    pub fn load_retries(mut self, retries: usize) -> Self {
+        self.verify.retries = retries;
+        self
+    }
+}
+
+fn build_info(name: &str, options: &LoadOptions) -> BuildInfo {
+    BuildInfo {
+        kernel_name: name.to_string(),
+        profile: options.profile.clone(),
+        lto: options.lto,
+        build_std: options.build_std,
+        default_features: options.default_features,
+        elf: None,
+    }
 }
 
 pub fn build_kernel(
@@ -343,12 +546,9 @@ pub fn build_kernel(
     options: LoadOptions,
     custom_link: Option<(String, Vec<Rewrite>)>,
 ) -> KernelData {
-    let arch = match arch {
-        luwen::luwen_core::Arch::Grayskull => tensix_builder::StandardTarget::Grayskull,
-        luwen::luwen_core::Arch::Wormhole => tensix_builder::StandardTarget::Wormhole,
-        luwen::luwen_core::Arch::Blackhole => tensix_builder::StandardTarget::Blackhole,
-        luwen::luwen_core::Arch::Unknown(_) => todo!(),
-    };
+    let mut build = build_info(name, &options);
+    let chip_arch = options.check_target.then_some(arch);
+    let arch = build_target(arch);
 
     let arch = if let Some((link, rewrites)) = custom_link {
         tensix_builder::TensixTarget::Custom {
@@ -386,17 +586,20 @@ pub fn build_kernel(
         },
     );
 
-    let elf = std::fs::read(kernel.path).unwrap();
-    load_elf(&elf)
+    let elf = std::fs::read(&kernel.path).unwrap();
+    build.elf = Some(kernel.path);
+    let mut data = load_elf_checked(&elf, chip_arch);
+    data.build = Some(build);
+    if options.keep_debug_info && !data.attach_debug_info(&elf) {
+        tracing::warn!("{name}: kernel has no debug info");
+    }
+    data
 }
 
 pub fn quick_load(name: &str, mut device: Chip, core: Tile, options: LoadOptions) -> Kernel {
-    let arch = match device.arch() {
-        luwen::luwen_core::Arch::Grayskull => tensix_builder::StandardTarget::Grayskull,
-        luwen::luwen_core::Arch::Wormhole => tensix_builder::StandardTarget::Wormhole,
-        luwen::luwen_core::Arch::Blackhole => tensix_builder::StandardTarget::Blackhole,
-        luwen::luwen_core::Arch::Unknown(_) => todo!(),
-    };
+    let chip_arch = options.check_target.then_some(device.arch());
+    let arch = build_target(device.arch());
+    let mut build = build_info(name, &options);
 
     let profile = match options.profile.as_str() {
         "debug" => tensix_builder::CargoProfile::Debug,
@@ -436,12 +639,21 @@ pub fn quick_load(name: &str, mut device: Chip, core: Tile, options: LoadOptions
     tracing::debug!("{}: loading binary", device);
 
     assert!(build_result.bin, "Can only quick load binary");
-    let mut kernel = load_file_to_core(
+    let elf = std::fs::read(&build_result.path).unwrap();
+    build.elf = Some(build_result.path);
+    let mut kernel = load_to_core(
         device.dupe().unwrap(),
         options.noc_id,
         core,
-        build_result.path,
-    );
+        &elf,
+        chip_arch,
+        options.verify,
+    )
+    .unwrap_or_else(|err| panic!("Refusing to load kernel: {err}"));
+    kernel.data.build = Some(build);
+    if options.keep_debug_info && !kernel.data.attach_debug_info(&elf) {
+        tracing::warn!("{name}: kernel has no debug info");
+    }
 
     tracing::debug!("{}: starting {core:?}", device);
     easy_start(&mut device, core.addr);
@@ -470,7 +682,7 @@ pub fn quick_load(name: &str, mut device: Chip, core: Tile, options: LoadOptions
     }
 
     tracing::debug!("{}: waiting for kernel to complete", device);
-    kernel.wait();
+    kernel.wait_for(options.timeout, options.hang_polls);
 
     kernel
 }
@@ -0,0 +1,326 @@
+//! Loading kernels onto many tiles and chips at once.
+//!
+//! Each chip gets its own thread, which splits its tiles between worker threads. Every worker uses
+//! its own [`Chip::dupe`] handle, and so its own TLB window. Loading, starting and the start
+//! handshake are separate phases: no tile is started until every tile on every chip has loaded.
+//!
+//! Failures are collected from all workers and reported in job then tile order, so the same
+//! failure always produces the same error regardless of thread timing. Each phase is a call to
+//! [`run`], which works with any per-tile closure and handle type.
+
+use std::any::Any;
+
+use crate::{
+    chip::noc::{NocId, NocInterface, Tile},
+    kernel::{KernelData, Verify, VerifyError, WAIT_POLL_INTERVAL},
+    loader, Chip,
+};
+
+/// A kernel to load onto a set of tiles of one chip.
+pub struct LoadJob<'a> {
+    pub chip: &'a mut Chip,
+    pub data: &'a KernelData,
+    pub tiles: Vec<Tile>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParallelOptions {
+    pub noc_id: NocId,
+    /// Worker threads (and TLB windows) per chip
+    pub workers: usize,
+    pub verify: Verify,
+    /// Release the tiles and run the start handshake once everything has loaded
+    pub start: bool,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            noc_id: NocId::Noc0,
+            workers: 4,
+            verify: Verify::default(),
+            start: true,
+        }
+    }
+}
+
+impl ParallelOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn noc_id(mut self, noc_id: NocId) -> Self {
+        self.noc_id = noc_id;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn start(mut self, start: bool) -> Self {
+        self.start = start;
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FailureKind {
+    #[error("failed to open a worker handle: {0}")]
+    Dupe(String),
+
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+
+    #[error("worker panicked: {0}")]
+    Panic(String),
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    /// Index of the job in the list passed to [`load`]
+    pub job: usize,
+    pub chip: usize,
+    /// Index of the tile in the job, for a panic this is the first tile of the worker
+    pub index: usize,
+    /// None if the failure can't be pinned on a single tile
+    pub tile: Option<Tile>,
+    pub error: FailureKind,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "chip {}", self.chip)?;
+        if let Some(tile) = self.tile {
+            write!(f, " tile {},{}", tile.addr.n0.0, tile.addr.n0.1)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Every failure of a parallel load, ordered by job and tile.
+#[derive(Debug, thiserror::Error)]
+#[error("{} tile(s) failed to load, first: {}", .0.len(), .0[0])]
+pub struct LoadErrors(pub Vec<Failure>);
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// The tiles of one chip to run a step on, see [`run`].
+pub struct TileJob<'a, H> {
+    /// Chip id the failures are reported against
+    pub chip: usize,
+    pub data: &'a KernelData,
+    pub tiles: &'a [Tile],
+    /// A worker thread per handle, each taking a contiguous chunk of the tiles
+    pub handles: Vec<H>,
+}
+
+/// Run `f` on every tile of every job, one thread per job and a worker per handle within it. `f`
+/// is given the worker's handle, the job's data and the index of the tile in the job.
+///
+/// Failures, including workers that panicked, are returned in job then tile order.
+pub fn run<H, F>(jobs: &mut [TileJob<H>], f: F) -> Result<(), LoadErrors>
+where
+    H: Send,
+    F: Fn(&mut H, &KernelData, usize, Tile) -> Result<(), FailureKind> + Sync,
+{
+    let f = &f;
+    let mut failures = std::thread::scope(|scope| {
+        let chips = jobs
+            .iter_mut()
+            .enumerate()
+            .map(
+                |(
+                    job,
+                    TileJob {
+                        chip,
+                        data,
+                        tiles,
+                        handles,
+                    },
+                )| {
+                    assert!(
+                        tiles.is_empty() || !handles.is_empty(),
+                        "job {job} has tiles but no handles"
+                    );
+                    let (chip, data) = (*chip, *data);
+                    let chunk = tiles.len().div_ceil(handles.len().max(1)).max(1);
+                    let tiles = tiles.chunks(chunk).enumerate();
+
+                    scope.spawn(move || {
+                        std::thread::scope(|scope| {
+                            let threads = tiles
+                                .zip(handles.iter_mut())
+                                .map(|((n, tiles), handle)| {
+                                    let first = n * chunk;
+                                    let thread = scope.spawn(move || {
+                                        let mut failures = Vec::new();
+                                        for (offset, tile) in tiles.iter().enumerate() {
+                                            let index = first + offset;
+                                            if let Err(error) = f(handle, data, index, *tile) {
+                                                failures.push(Failure {
+                                                    job,
+                                                    chip,
+                                                    index,
+                                                    tile: Some(*tile),
+                                                    error,
+                                                });
+                                            }
+                                        }
+                                        failures
+                                    });
+                                    (first, thread)
+                                })
+                                .collect::<Vec<_>>();
+
+                            threads
+                                .into_iter()
+                                .flat_map(|(first, thread)| {
+                                    thread.join().unwrap_or_else(|panic| {
+                                        vec![Failure {
+                                            job,
+                                            chip,
+                                            index: first,
+                                            tile: None,
+                                            error: FailureKind::Panic(panic_message(panic)),
+                                        }]
+                                    })
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                },
+            )
+            .collect::<Vec<_>>();
+
+        chips
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    failures.sort_by_key(|v| (v.job, v.index));
+    Err(LoadErrors(failures))
+}
+
+/// Load every job in parallel, then (if `options.start` is set) start all of their tiles
+/// together. Nothing is started if any tile fails to load.
+pub fn load(mut jobs: Vec<LoadJob>, options: &ParallelOptions) -> Result<(), LoadErrors> {
+    let noc_id = options.noc_id;
+
+    let mut failures = Vec::new();
+    let mut handles = Vec::with_capacity(jobs.len());
+    for (job, LoadJob { chip, data, tiles }) in jobs.iter_mut().enumerate() {
+        loader::assert_brisc_entry(data);
+
+        tracing::debug!("{chip}: deasserting riscv reset");
+        chip.deassert_riscv_reset();
+        tracing::debug!("{chip}: go busy");
+        chip.go_busy();
+
+        let count = options.workers.clamp(1, tiles.len().max(1));
+        let mut chip_handles = Vec::with_capacity(count);
+        for _ in 0..count {
+            match chip.dupe() {
+                Ok(handle) => chip_handles.push(handle),
+                Err(err) => {
+                    failures.push(Failure {
+                        job,
+                        chip: chip.id(),
+                        index: 0,
+                        tile: None,
+                        error: FailureKind::Dupe(err),
+                    });
+                    break;
+                }
+            }
+        }
+        handles.push(chip_handles);
+    }
+    if !failures.is_empty() {
+        return Err(LoadErrors(failures));
+    }
+
+    let mut jobs = jobs
+        .iter()
+        .zip(handles)
+        .map(|(job, handles)| TileJob {
+            chip: job.chip.id(),
+            data: job.data,
+            tiles: &job.tiles,
+            handles,
+        })
+        .collect::<Vec<_>>();
+
+    tracing::debug!(
+        "loading {} tiles with {} workers",
+        jobs.iter().map(|v| v.tiles.len()).sum::<usize>(),
+        jobs.iter().map(|v| v.handles.len()).sum::<usize>()
+    );
+    run(&mut jobs, |chip, data, index, tile| {
+        loader::stop(chip, tile);
+        data.load_with(chip, noc_id, tile, options.verify)?;
+        if let Some(id) = data.symbols.get("CORE_ID") {
+            chip.noc_write32(noc_id, tile, id.addr, index as u32);
+        }
+        Ok(())
+    })?;
+
+    if !options.start {
+        return Ok(());
+    }
+
+    tracing::debug!("starting tensix");
+    run(&mut jobs, |chip, _, _, tile| {
+        loader::start(chip, tile.addr, true, true);
+        Ok(())
+    })?;
+
+    tracing::debug!("waiting for tensix start");
+    run(&mut jobs, |chip, data, _, tile| {
+        let mut bin = data.bin.clone();
+        if bin.start_sync.is_some() {
+            while !bin.start_sync(chip, noc_id, tile.addr) {
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+        }
+        Ok(())
+    })
+}
+
+impl Chip {
+    /// Load `data` onto `tiles` with `options.workers` threads, see [`load`].
+    pub fn load_kernels_parallel(
+        &mut self,
+        data: &KernelData,
+        tiles: Vec<Tile>,
+        options: &ParallelOptions,
+    ) -> Result<(), LoadErrors> {
+        load(
+            vec![LoadJob {
+                chip: self,
+                data,
+                tiles,
+            }],
+            options,
+        )
+    }
+}
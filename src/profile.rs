@@ -0,0 +1,176 @@
+//! Collecting device profiles into a timeline.
+//!
+//! Kernels record markers into per RISC buffers, see [`crate::kernel::profile`] for the layout. Once
+//! the kernels are done a [`Profiler`] reads the buffers of every tile and converts their wall
+//! clock cycles to time using the AICLK of each chip, then exports them as a Chrome trace with
+//! one track per chip, tile and RISC.
+
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    chip::noc::{NocId, Tile},
+    kernel::{Kernel, KernelData, MarkerKind, ProfileBuffer},
+    program::ProgramHandle,
+    trace::{Phase, Trace, TraceEvent},
+    Chip,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("failed to read the AICLK of chip {chip}: {error}")]
+    Aiclk { chip: usize, error: String },
+
+    #[error("no AICLK known for chip {0}")]
+    UnknownChip(usize),
+
+    #[error("chip {0} has an AICLK of 0 MHz")]
+    ZeroAiclk(usize),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// The markers of one RISC of a tile.
+#[derive(Clone, Debug)]
+pub struct TileProfile {
+    pub chip: usize,
+    pub tile: Tile,
+    pub buffer: ProfileBuffer,
+}
+
+#[derive(Clone, Debug)]
+struct ChipClock {
+    name: String,
+    mhz: u32,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    clocks: BTreeMap<usize, ChipClock>,
+    pub profiles: Vec<TileProfile>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the AICLK of `chip`, done automatically the first time a tile of the chip is collected.
+    pub fn add_chip(&mut self, chip: &mut Chip) -> Result<u32, ProfileError> {
+        let mhz = chip.aiclk().map_err(|error| ProfileError::Aiclk {
+            chip: chip.id(),
+            error,
+        })?;
+        self.set_aiclk(chip.id(), chip.to_string(), mhz);
+
+        Ok(mhz)
+    }
+
+    /// Use `mhz` to convert the cycles of chip `id` instead of asking the chip.
+    pub fn set_aiclk(&mut self, id: usize, name: impl Into<String>, mhz: u32) {
+        self.clocks.insert(
+            id,
+            ChipClock {
+                name: name.into(),
+                mhz,
+            },
+        );
+    }
+
+    /// Read the profile buffers of `data` on `tile`.
+    pub fn collect(
+        &mut self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: Tile,
+        data: &KernelData,
+    ) -> Result<(), ProfileError> {
+        if !self.clocks.contains_key(&chip.id()) {
+            self.add_chip(chip)?;
+        }
+
+        for buffer in data.read_profile(chip, noc_id, tile) {
+            if !buffer.markers.is_empty() || buffer.dropped > 0 {
+                self.profiles.push(TileProfile {
+                    chip: chip.id(),
+                    tile,
+                    buffer,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn collect_kernel(&mut self, kernel: &mut Kernel) -> Result<(), ProfileError> {
+        self.collect(&mut kernel.device, kernel.noc_id, kernel.core, &kernel.data)
+    }
+
+    /// Collect every core of a program.
+    pub fn collect_program(&mut self, handle: &mut ProgramHandle) -> Result<(), ProfileError> {
+        for (cores, data) in &handle.groups {
+            for tile in cores.tiles() {
+                self.collect(&mut handle.device, handle.noc_id, *tile, data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The collected markers as a trace. Times are relative to the first marker of each chip, the
+    /// wall clocks of different chips are not synchronized.
+    pub fn trace(&self) -> Result<Trace, ProfileError> {
+        let mut starts = BTreeMap::new();
+        for profile in &self.profiles {
+            for marker in &profile.buffer.markers {
+                let start = starts.entry(profile.chip).or_insert(marker.cycles);
+                *start = (*start).min(marker.cycles);
+            }
+        }
+
+        let mut trace = Trace::new();
+        for profile in &self.profiles {
+            let clock = self
+                .clocks
+                .get(&profile.chip)
+                .ok_or(ProfileError::UnknownChip(profile.chip))?;
+            if clock.mhz == 0 {
+                return Err(ProfileError::ZeroAiclk(profile.chip));
+            }
+            let start = starts.get(&profile.chip).copied().unwrap_or(0);
+            let time = |cycles: u64| (cycles - start) as f64 / clock.mhz as f64;
+
+            let pid = profile.chip as u64;
+            trace.process(pid, clock.name.clone());
+            let tid = trace.track(pid, profile.tile, profile.buffer.risc);
+
+            let mut markers = profile.buffer.markers.iter().collect::<Vec<_>>();
+            markers.sort_by_key(|v| v.cycles);
+            for marker in &markers {
+                let ph = match marker.kind {
+                    MarkerKind::Begin => Phase::Begin,
+                    MarkerKind::End => Phase::End,
+                    MarkerKind::Instant => Phase::Instant,
+                };
+                trace.push(
+                    TraceEvent::new(marker.name.clone(), ph, time(marker.cycles), pid, tid)
+                        .arg("cycles", marker.cycles),
+                );
+            }
+
+            if profile.buffer.dropped > 0 {
+                let ts = markers.last().map(|v| time(v.cycles)).unwrap_or(0.0);
+                trace.push(
+                    TraceEvent::new("dropped markers", Phase::Instant, ts, pid, tid)
+                        .arg("count", profile.buffer.dropped),
+                );
+            }
+        }
+
+        Ok(trace)
+    }
+
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> Result<(), ProfileError> {
+        Ok(self.trace()?.write(path)?)
+    }
+}
@@ -0,0 +1,697 @@
+//! Launching kernels across groups of cores.
+//!
+//! A [`CoreProgram`] runs a separate elf on each RISC of a tensix core. Each elf is linked by the
+//! user into its own region of L1. BRISC always starts at 0, the other RISCs start at their
+//! `__<risc>_start` symbol (or the elf entry point) through the reset PC overrides. Only the RISCs
+//! that were given a program are taken out of reset.
+//!
+//! A [`Program`] maps [`CoreSet`]s to kernels, e.g. readers, compute and writers on different
+//! groups of cores. All groups are loaded before any core is released, and the start handshake
+//! is done across every core so that the groups start together.
+//!
+//! Per core parameters are passed through the `RUNTIME_ARGS` global (and `COMMON_RUNTIME_ARGS`
+//! for values shared by a group), written by [`ProgramHandle::set_args`] between
+//! [`Program::load`] and [`ProgramHandle::start`].
+
+use std::{collections::HashMap, mem::size_of, sync::Arc, time::Duration};
+
+use bytemuck::Pod;
+
+use crate::{
+    chip::noc::{NocId, NocInterface, Tile},
+    kernel::{
+        with_aligned, write_aligned, CoreStatus, Kernel, KernelBinData, KernelData, KernelOutcome,
+        RiscId, RiscState, Symbol, SymbolError, Verify, VerifyError,
+    },
+    loader, Chip,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProgramError {
+    #[error("program has no RISCs")]
+    Empty,
+
+    #[error("{a} and {b} both write to 0x{start:x}..0x{end:x}")]
+    Overlap {
+        a: RiscId,
+        b: RiscId,
+        start: u64,
+        end: u64,
+    },
+
+    #[error("BRISC always starts at 0, but its program starts at 0x{0:x}")]
+    BriscEntry(u64),
+
+    #[error("group {0} has no cores")]
+    EmptyGroup(usize),
+
+    #[error("{tile:?} is in both group {a} and group {b}")]
+    TileConflict { tile: Tile, a: usize, b: usize },
+
+    #[error("{0:?} is not part of the program")]
+    NotInProgram(Tile),
+
+    #[error("program has no group {0}")]
+    NoGroup(usize),
+
+    #[error(transparent)]
+    Symbol(#[from] SymbolError),
+
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+}
+
+#[derive(Clone, Default)]
+pub struct CoreProgram {
+    pub brisc: Option<KernelData>,
+    pub ncrisc: Option<KernelData>,
+    pub trisc0: Option<KernelData>,
+    pub trisc1: Option<KernelData>,
+    pub trisc2: Option<KernelData>,
+}
+
+impl CoreProgram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn brisc(mut self, data: KernelData) -> Self {
+        self.brisc = Some(data);
+        self
+    }
+
+    pub fn ncrisc(mut self, data: KernelData) -> Self {
+        self.ncrisc = Some(data);
+        self
+    }
+
+    pub fn trisc0(mut self, data: KernelData) -> Self {
+        self.trisc0 = Some(data);
+        self
+    }
+
+    pub fn trisc1(mut self, data: KernelData) -> Self {
+        self.trisc1 = Some(data);
+        self
+    }
+
+    pub fn trisc2(mut self, data: KernelData) -> Self {
+        self.trisc2 = Some(data);
+        self
+    }
+
+    pub fn set(&mut self, risc: RiscId, data: Option<KernelData>) {
+        *self.slot(risc) = data;
+    }
+
+    fn slot(&mut self, risc: RiscId) -> &mut Option<KernelData> {
+        match risc {
+            RiscId::Brisc => &mut self.brisc,
+            RiscId::Ncrisc => &mut self.ncrisc,
+            RiscId::Trisc0 => &mut self.trisc0,
+            RiscId::Trisc1 => &mut self.trisc1,
+            RiscId::Trisc2 => &mut self.trisc2,
+        }
+    }
+
+    pub fn get(&self, risc: RiscId) -> Option<&KernelData> {
+        match risc {
+            RiscId::Brisc => self.brisc.as_ref(),
+            RiscId::Ncrisc => self.ncrisc.as_ref(),
+            RiscId::Trisc0 => self.trisc0.as_ref(),
+            RiscId::Trisc1 => self.trisc1.as_ref(),
+            RiscId::Trisc2 => self.trisc2.as_ref(),
+        }
+    }
+
+    /// The RISCs that have a program, these are the ones that get released from reset
+    pub fn riscs(&self) -> Vec<RiscId> {
+        RiscId::ALL
+            .into_iter()
+            .filter(|risc| self.get(*risc).is_some())
+            .collect()
+    }
+
+    /// Where `risc` starts executing, None if it has no program.
+    pub fn entry(&self, risc: RiscId) -> Option<u64> {
+        let data = self.get(risc)?;
+        Some(data.bin.risc(risc).entry.unwrap_or(data.entry))
+    }
+
+    /// Make sure the programs can share the core: at least one RISC is used, BRISC starts at 0
+    /// and no two programs write to (or zero, for .bss) the same bytes of L1.
+    pub fn check(&self) -> Result<(), ProgramError> {
+        let riscs = self.riscs();
+        if riscs.is_empty() {
+            return Err(ProgramError::Empty);
+        }
+
+        if let Some(entry) = self.entry(RiscId::Brisc).filter(|v| *v != 0) {
+            return Err(ProgramError::BriscEntry(entry));
+        }
+
+        let mut regions = riscs
+            .iter()
+            .flat_map(|risc| {
+                self.get(*risc).unwrap().writes.iter().map(move |write| {
+                    let start = write.addr as u64;
+                    (
+                        start,
+                        start + write.len() as u64 + write.zeroed as u64,
+                        *risc,
+                    )
+                })
+            })
+            .filter(|(start, end, _)| start < end)
+            .collect::<Vec<_>>();
+        regions.sort();
+
+        for pair in regions.windows(2) {
+            let (a_start, a_end, a) = pair[0];
+            let (b_start, b_end, b) = pair[1];
+            if b_start < a_end {
+                return Err(ProgramError::Overlap {
+                    a,
+                    b,
+                    start: a_start.max(b_start),
+                    end: a_end.min(b_end),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Combine the programs into a single kernel. The state globals of each RISC come from that
+    /// RISC's program, the core wide ones from BRISC (or the first program if BRISC is unused).
+    pub fn merged(&self) -> Result<KernelData, ProgramError> {
+        self.check()?;
+
+        let riscs = self.riscs();
+        let primary = self.get(riscs[0]).unwrap();
+
+        let mut symbols = HashMap::new();
+        let mut writes = Vec::new();
+        // Later programs don't override names from earlier ones, so BRISC wins
+        for risc in riscs.iter().rev() {
+            let data = self.get(*risc).unwrap();
+            symbols.extend(data.symbols.clone());
+        }
+        for risc in &riscs {
+            writes.extend(self.get(*risc).unwrap().writes.iter().cloned());
+        }
+
+        let core_data = |risc: RiscId| match self.get(risc) {
+            Some(data) => data.bin.risc(risc).clone(),
+            None => KernelBinData::from_symbols(&HashMap::new())
+                .risc(risc)
+                .clone(),
+        };
+        let bin = KernelBinData {
+            brisc_state: core_data(RiscId::Brisc),
+            ncrisc_state: core_data(RiscId::Ncrisc),
+            trisc0_state: core_data(RiscId::Trisc0),
+            trisc1_state: core_data(RiscId::Trisc1),
+            trisc2_state: core_data(RiscId::Trisc2),
+            ..primary.bin.clone()
+        };
+
+        Ok(KernelData {
+            bin,
+            target: primary.target.clone(),
+            build: primary.build.clone(),
+            debug: primary.debug.clone(),
+            log_formats: primary.log_formats.clone(),
+            riscs: riscs
+                .iter()
+                .map(|risc| (*risc, Arc::new(self.get(*risc).unwrap().clone())))
+                .collect(),
+            ..KernelData::new(writes, symbols)
+        })
+    }
+
+    /// Load every program onto `tile`, checking the segments with `verify`, and release the RISCs
+    /// that have one. The returned kernel monitors all of them.
+    pub fn load(
+        &self,
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: Tile,
+        verify: Verify,
+    ) -> Result<Kernel, ProgramError> {
+        let mut data = self.merged()?;
+        let riscs = self.riscs();
+
+        tracing::debug!("{}: stopping {tile:?}", chip);
+        loader::stop(chip, tile);
+
+        tracing::debug!("{}: deasserting riscv reset", chip);
+        chip.deassert_riscv_reset();
+
+        tracing::debug!("{}: go busy", chip);
+        chip.go_busy();
+
+        tracing::debug!("{}: loading {} programs to {tile:?}", chip, riscs.len());
+        data.load_with(chip, noc_id, tile, verify)?;
+
+        data.set_entry(
+            chip,
+            noc_id,
+            tile.addr,
+            self.entry(RiscId::Ncrisc),
+            self.entry(RiscId::Trisc0),
+            self.entry(RiscId::Trisc1),
+            self.entry(RiscId::Trisc2),
+        );
+
+        tracing::debug!("{}: starting {riscs:?} on {tile:?}", chip);
+        loader::start_riscs(chip, tile.addr, &riscs);
+
+        // Each program runs its own start handshake
+        for risc in &riscs {
+            let mut bin = self.get(*risc).unwrap().bin.clone();
+            if bin.start_sync.is_none() {
+                continue;
+            }
+
+            tracing::debug!("{}: waiting for {risc} start on {tile:?}", chip);
+            while !bin.start_sync(chip, noc_id, tile.addr) {
+                if !bin.all_complete(chip, noc_id, tile.addr) {
+                    bin.print_state_diff(chip, noc_id, tile.addr);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+
+        data.bin.print_state(chip, noc_id, tile.addr);
+
+        Ok(Kernel::new(chip.dupe().unwrap(), noc_id, tile, data))
+    }
+}
+
+/// A group of tensix cores.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoreSet {
+    tiles: Vec<Tile>,
+}
+
+impl CoreSet {
+    pub fn new(tiles: impl IntoIterator<Item = Tile>) -> Self {
+        let mut set = Self::default();
+        for tile in tiles {
+            set.insert(tile);
+        }
+        set
+    }
+
+    pub fn all(chip: &Chip) -> Self {
+        Self::new(chip.tensix_tiles())
+    }
+
+    /// The tensix cores of `chip` in the rectangle from `start` to `end` inclusive, in NOC0
+    /// coordinates.
+    pub fn rect(chip: &Chip, start: (u8, u8), end: (u8, u8)) -> Self {
+        let (x0, x1) = (start.0.min(end.0), start.0.max(end.0));
+        let (y0, y1) = (start.1.min(end.1), start.1.max(end.1));
+        Self::new(chip.tensix_tiles().into_iter().filter(|tile| {
+            let (x, y) = tile.get(NocId::Noc0);
+            (x0..=x1).contains(&x) && (y0..=y1).contains(&y)
+        }))
+    }
+
+    /// Add `tile` to the set, duplicates are ignored.
+    pub fn insert(&mut self, tile: Tile) {
+        if !self.contains(&tile) {
+            self.tiles.push(tile);
+        }
+    }
+
+    pub fn contains(&self, tile: &Tile) -> bool {
+        self.tiles.iter().any(|v| v.addr == tile.addr)
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// The NOC0 rectangle covering the set, if the set fills it and every tile in it is one of
+    /// `tensix`. Writes to such a set can be multicast, a rectangle that also covers harvested or
+    /// non-tensix tiles would write to those as well.
+    pub fn multicast_rect(&self, tensix: &[Tile]) -> Option<((u8, u8), (u8, u8))> {
+        let coords = self.tiles.iter().map(|tile| tile.get(NocId::Noc0));
+        let start = coords.clone().reduce(|a, b| (a.0.min(b.0), a.1.min(b.1)))?;
+        let end = coords.reduce(|a, b| (a.0.max(b.0), a.1.max(b.1)))?;
+
+        let area = (end.0 - start.0 + 1) as usize * (end.1 - start.1 + 1) as usize;
+        let all_tensix = self
+            .tiles
+            .iter()
+            .all(|tile| tensix.iter().any(|v| v.addr == tile.addr));
+
+        (self.len() > 1 && self.len() == area && all_tensix).then_some((start, end))
+    }
+}
+
+impl From<Tile> for CoreSet {
+    fn from(value: Tile) -> Self {
+        Self::new([value])
+    }
+}
+
+impl From<Vec<Tile>> for CoreSet {
+    fn from(value: Vec<Tile>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&[Tile]> for CoreSet {
+    fn from(value: &[Tile]) -> Self {
+        Self::new(value.iter().copied())
+    }
+}
+
+/// Kernels for groups of cores, launched together with [`Program::launch`].
+#[derive(Clone, Default)]
+pub struct Program {
+    pub groups: Vec<(CoreSet, KernelData)>,
+    pub verify: Verify,
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `data` on every core of `cores`.
+    pub fn kernel(mut self, cores: impl Into<CoreSet>, data: KernelData) -> Self {
+        self.groups.push((cores.into(), data));
+        self
+    }
+
+    /// How each core's segments are checked after loading.
+    pub fn verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Every group has cores, no core is in two groups and every kernel starts at 0.
+    pub fn check(&self) -> Result<(), ProgramError> {
+        if self.groups.is_empty() {
+            return Err(ProgramError::Empty);
+        }
+
+        let mut owners = HashMap::new();
+        for (group, (cores, data)) in self.groups.iter().enumerate() {
+            if cores.is_empty() {
+                return Err(ProgramError::EmptyGroup(group));
+            }
+            if data.entry != 0 {
+                return Err(ProgramError::BriscEntry(data.entry));
+            }
+
+            for tile in cores.tiles() {
+                if let Some(a) = owners.insert(tile.addr, group) {
+                    return Err(ProgramError::TileConflict {
+                        tile: *tile,
+                        a,
+                        b: group,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        self.groups
+            .iter()
+            .flat_map(|(cores, _)| cores.tiles().iter().copied())
+            .collect()
+    }
+
+    /// Load every group and start all of their cores.
+    pub fn launch(&self, chip: &mut Chip, noc_id: NocId) -> Result<ProgramHandle, ProgramError> {
+        let mut handle = self.load(chip, noc_id)?;
+        handle.start();
+        Ok(handle)
+    }
+
+    /// Load every group but keep the cores in reset, so that runtime args can be set before
+    /// [`ProgramHandle::start`].
+    pub fn load(&self, chip: &mut Chip, noc_id: NocId) -> Result<ProgramHandle, ProgramError> {
+        self.check()?;
+
+        let tiles = self.tiles();
+
+        tracing::debug!("{}: stopping {} cores", chip, tiles.len());
+        for tile in &tiles {
+            loader::stop(chip, *tile);
+        }
+
+        tracing::debug!("{}: deasserting riscv reset", chip);
+        chip.deassert_riscv_reset();
+
+        tracing::debug!("{}: go busy", chip);
+        chip.go_busy();
+
+        let tensix = chip.tensix_tiles();
+        for (group, (cores, data)) in self.groups.iter().enumerate() {
+            let rect = chip
+                .rect_multicast()
+                .then(|| cores.multicast_rect(&tensix))
+                .flatten();
+
+            match rect {
+                Some((start, end)) => {
+                    if self.verify.check_target {
+                        data.check_target(chip.arch()).map_err(VerifyError::from)?;
+                    }
+                    tracing::debug!("{}: multicasting group {group} to {start:?}..{end:?}", chip);
+                    for write in &data.writes {
+                        with_aligned(&write.data.0, |bytes| {
+                            chip.noc_multicast(NocId::Noc0, start, end, write.addr as u64, bytes)
+                        });
+                    }
+                    for tile in cores.tiles() {
+                        data.verify(chip, noc_id, *tile, self.verify)?;
+                    }
+                }
+                None => {
+                    tracing::debug!("{}: loading group {group} to {} cores", chip, cores.len());
+                    for tile in cores.tiles() {
+                        data.load_with(chip, noc_id, *tile, self.verify)?;
+                    }
+                }
+            }
+
+            if let Some(id) = data.symbols.get("CORE_ID") {
+                for (core_id, tile) in cores.tiles().iter().enumerate() {
+                    chip.noc_write32(noc_id, *tile, id.addr, core_id as u32);
+                }
+            }
+        }
+
+        Ok(ProgramHandle {
+            device: chip.dupe().unwrap(),
+            noc_id,
+            groups: self.groups.clone(),
+            outcomes: Vec::new(),
+        })
+    }
+}
+
+/// Args region the kernel reads its per core parameters from
+pub const RUNTIME_ARGS: &str = "RUNTIME_ARGS";
+/// Args region shared by every core of a group
+pub const COMMON_RUNTIME_ARGS: &str = "COMMON_RUNTIME_ARGS";
+
+/// The args region `name` of `data`, which must be able to hold `len` bytes.
+pub fn args_symbol(data: &KernelData, name: &str, len: usize) -> Result<Symbol, SymbolError> {
+    let symbol = data.symbol(name)?;
+    if len as u64 > symbol.size {
+        return Err(SymbolError::SizeMismatch {
+            name: name.to_string(),
+            size: symbol.size,
+            expected: len,
+        });
+    }
+
+    Ok(symbol.clone())
+}
+
+/// Release the cores of every group together: wait for all of them to reach the start sync point
+/// before letting any of them go.
+fn start_sync(chip: &mut Chip, noc_id: NocId, groups: &mut [(CoreSet, KernelData)]) {
+    let pending = |chip: &mut Chip, bin: &mut KernelBinData, tile: Tile| {
+        let sync = bin.start_sync?;
+        let not_started = bin
+            .read_status(chip, noc_id, tile.addr)
+            .riscs
+            .iter()
+            .all(|v| v.state.map(|v| v == RiscState::NotStarted).unwrap_or(true));
+        not_started.then_some(sync)
+    };
+
+    tracing::debug!("{}: waiting for all cores to reach start", chip);
+    for (cores, data) in groups.iter_mut() {
+        for tile in cores.tiles() {
+            while let Some(sync) = pending(chip, &mut data.bin, *tile) {
+                if matches!(chip.noc_read32(noc_id, *tile, sync), 1 | 3) {
+                    break;
+                }
+                data.bin.print_state_diff(chip, noc_id, tile.addr);
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+    }
+
+    tracing::debug!("{}: releasing all cores", chip);
+    for (cores, data) in groups.iter_mut() {
+        for tile in cores.tiles() {
+            while !data.bin.start_sync(chip, noc_id, tile.addr) {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoreOutcome {
+    /// Index of the core's group in the [`Program`]
+    pub group: usize,
+    pub tile: Tile,
+    pub outcome: KernelOutcome,
+}
+
+/// A loaded [`Program`].
+pub struct ProgramHandle {
+    pub device: Chip,
+    pub noc_id: NocId,
+    pub groups: Vec<(CoreSet, KernelData)>,
+
+    /// The outcome of every core after the last wait
+    pub outcomes: Vec<CoreOutcome>,
+}
+
+impl ProgramHandle {
+    /// Release every core of the program from reset and run the start handshake.
+    pub fn start(&mut self) {
+        let tiles = self.tiles();
+
+        tracing::debug!("{}: starting {} cores", self.device, tiles.len());
+        for tile in &tiles {
+            loader::start(&mut self.device, tile.addr, true, true);
+        }
+
+        start_sync(&mut self.device, self.noc_id, &mut self.groups);
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        self.groups
+            .iter()
+            .flat_map(|(cores, _)| cores.tiles().iter().copied())
+            .collect()
+    }
+
+    /// Write `args` into the `RUNTIME_ARGS` of `tile`. Args should be set before
+    /// [`ProgramHandle::start`] so that the kernel never sees stale values.
+    pub fn set_args<T: Pod>(&mut self, tile: Tile, args: &T) -> Result<(), ProgramError> {
+        let (_, data) = self
+            .groups
+            .iter()
+            .find(|(cores, _)| cores.contains(&tile))
+            .ok_or(ProgramError::NotInProgram(tile))?;
+
+        let symbol = args_symbol(data, RUNTIME_ARGS, size_of::<T>())?;
+        write_aligned(
+            &mut self.device,
+            self.noc_id,
+            tile,
+            symbol.addr,
+            bytemuck::bytes_of(args),
+        );
+
+        Ok(())
+    }
+
+    /// Write `args` into the `COMMON_RUNTIME_ARGS` of every core of `group`.
+    pub fn set_common_args<T: Pod>(&mut self, group: usize, args: &T) -> Result<(), ProgramError> {
+        let (cores, data) = self.groups.get(group).ok_or(ProgramError::NoGroup(group))?;
+
+        let symbol = args_symbol(data, COMMON_RUNTIME_ARGS, size_of::<T>())?;
+        for tile in cores.tiles() {
+            write_aligned(
+                &mut self.device,
+                self.noc_id,
+                *tile,
+                symbol.addr,
+                bytemuck::bytes_of(args),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Wait for every core to finish, a core that panics or hangs does not stop the others from
+    /// being waited on.
+    pub fn wait(&mut self) -> &[CoreOutcome] {
+        self.wait_inner(None)
+    }
+
+    /// Like [`ProgramHandle::wait`] but waiting at most `timeout` for all cores.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> &[CoreOutcome] {
+        self.wait_inner(Some(timeout))
+    }
+
+    fn wait_inner(&mut self, timeout: Option<Duration>) -> &[CoreOutcome] {
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+
+        self.outcomes.clear();
+        for (group, (cores, data)) in self.groups.iter_mut().enumerate() {
+            for tile in cores.tiles() {
+                let outcome = data.wait_until(&mut self.device, self.noc_id, *tile, deadline, None);
+                self.outcomes.push(CoreOutcome {
+                    group,
+                    tile: *tile,
+                    outcome,
+                });
+            }
+        }
+
+        &self.outcomes
+    }
+
+    pub fn all_completed(&self) -> bool {
+        !self.outcomes.is_empty() && self.outcomes.iter().all(|v| v.outcome.is_completed())
+    }
+
+    /// The current status of every core.
+    pub fn status(&mut self) -> Vec<(Tile, CoreStatus)> {
+        let mut status = Vec::new();
+        for (cores, data) in self.groups.iter_mut() {
+            for tile in cores.tiles() {
+                let core = data
+                    .bin
+                    .read_status(&mut self.device, self.noc_id, tile.addr);
+                status.push((*tile, core));
+            }
+        }
+
+        status
+    }
+
+    /// Put every core of the program back into reset.
+    pub fn stop(&mut self) {
+        let tiles = self.tiles();
+        self.device.stop_tile(Some(tiles));
+    }
+}
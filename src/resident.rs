@@ -0,0 +1,480 @@
+//! Sending work to a dispatcher firmware that stays resident on a core.
+//!
+//! Instead of loading and resetting a core for every job, a dispatcher is loaded once and polls a
+//! command queue in L1 (the `COMMAND_QUEUE` global). The queue is a 16 byte header of `write` and
+//! `read` counters followed by a ring of 64 byte commands:
+//!
+//! ```text
+//! word 0      id          set by the host, echoed back untouched
+//! word 1      kind        CALL: run function `function`, OVERLAY: jump to the code at `overlay`
+//!                         EXIT: return from the dispatcher
+//! word 2      function
+//! word 3      status      QUEUED by the host, RUNNING then DONE or FAILED by the dispatcher
+//! word 4      result      set by the dispatcher along with the final status
+//! word 5      overlay     address of the overlay code in L1
+//! word 6      overlay_len
+//! word 7      arg_count
+//! word 8..16  args
+//! ```
+//!
+//! The host fills slot `write % capacity` and then bumps `write`. The dispatcher runs the command
+//! at `read % capacity`, sets its final status and then bumps `read`. Both counters start at zero
+//! and the capacity is however many commands fit in the global. Overlays are copied into the
+//! `OVERLAY` global, which holds one overlay at a time.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chip::noc::{NocId, Tile},
+    kernel::{
+        diagnose::{ChipTile, TileRegisters},
+        write_aligned, Kernel, KernelData, RiscId, SymbolError, Verify, VerifyError,
+        WAIT_POLL_INTERVAL,
+    },
+    loader, Chip,
+};
+
+pub const COMMAND_QUEUE: &str = "COMMAND_QUEUE";
+pub const OVERLAY: &str = "OVERLAY";
+
+pub const HEADER_SIZE: u64 = 16;
+pub const COMMAND_SIZE: u64 = 64;
+pub const MAX_ARGS: usize = 8;
+
+pub const KIND_CALL: u32 = 1;
+pub const KIND_OVERLAY: u32 = 2;
+pub const KIND_EXIT: u32 = 3;
+
+pub const STATUS_QUEUED: u32 = 1;
+pub const STATUS_RUNNING: u32 = 2;
+pub const STATUS_DONE: u32 = 3;
+pub const STATUS_FAILED: u32 = 4;
+
+const WRITE_OFFSET: u64 = 0;
+const READ_OFFSET: u64 = 4;
+const STATUS_OFFSET: u64 = 12;
+const RESULT_OFFSET: u64 = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error(transparent)]
+    Symbol(#[from] SymbolError),
+
+    #[error("{COMMAND_QUEUE} is {0} bytes, too small to hold a single command")]
+    QueueTooSmall(u64),
+
+    #[error("at most {MAX_ARGS} args can be passed, got {0}")]
+    TooManyArgs(usize),
+
+    #[error("overlay is {len} bytes but {OVERLAY} only holds {size}")]
+    OverlayTooLarge { len: usize, size: u64 },
+
+    #[error("{0:?} was never submitted or was already collected")]
+    Unknown(WorkId),
+
+    #[error("timed out waiting for {0:?}")]
+    Timeout(WorkId),
+
+    #[error("the dispatcher panicked")]
+    Panicked,
+
+    #[error("the dispatcher is no longer running")]
+    Exited,
+
+    #[error(transparent)]
+    Verify(#[from] VerifyError),
+}
+
+/// A submitted work item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WorkId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub id: WorkId,
+    /// Whether the dispatcher reported success
+    pub ok: bool,
+    pub result: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Work<'a> {
+    Call {
+        function: u32,
+        args: &'a [u32],
+    },
+    /// Code already copied into L1 at `addr`
+    Overlay {
+        addr: u64,
+        len: u32,
+        args: &'a [u32],
+    },
+    Exit,
+}
+
+impl Work<'_> {
+    fn args(&self) -> &[u32] {
+        match self {
+            Work::Call { args, .. } | Work::Overlay { args, .. } => args,
+            Work::Exit => &[],
+        }
+    }
+
+    /// The command as written to L1.
+    fn encode(&self, id: WorkId) -> [u32; (COMMAND_SIZE / 4) as usize] {
+        let mut words = [0; (COMMAND_SIZE / 4) as usize];
+        words[0] = id.0;
+        words[3] = STATUS_QUEUED;
+        match self {
+            Work::Call { function, .. } => {
+                words[1] = KIND_CALL;
+                words[2] = *function;
+            }
+            Work::Overlay { addr, len, .. } => {
+                words[1] = KIND_OVERLAY;
+                words[5] = *addr as u32;
+                words[6] = *len;
+            }
+            Work::Exit => words[1] = KIND_EXIT,
+        }
+
+        let args = self.args();
+        words[7] = args.len() as u32;
+        words[8..8 + args.len()].copy_from_slice(args);
+        words
+    }
+}
+
+/// The host side of the command queue.
+#[derive(Clone, Debug)]
+pub struct CommandQueue {
+    pub addr: u64,
+    pub capacity: u32,
+    /// Where overlays go and how big they can be
+    pub overlay: Option<(u64, u64)>,
+
+    write: u32,
+    /// Submitted and not yet seen finished, by slot
+    outstanding: BTreeMap<WorkId, u32>,
+    /// Finished items are kept until they are taken, items that are never collected pile up here
+    completed: HashMap<WorkId, Completion>,
+    /// The item running the overlay currently in L1
+    overlay_owner: Option<WorkId>,
+}
+
+impl CommandQueue {
+    /// Find the queue in `data`, `regs` must be the tile it was loaded to.
+    pub fn new(regs: &mut dyn TileRegisters, data: &KernelData) -> Result<Self, RuntimeError> {
+        let symbol = data.symbol(COMMAND_QUEUE)?;
+        let capacity = symbol.size.saturating_sub(HEADER_SIZE) / COMMAND_SIZE;
+        if capacity == 0 {
+            return Err(RuntimeError::QueueTooSmall(symbol.size));
+        }
+
+        let overlay = data.symbol(OVERLAY).ok().map(|v| (v.addr, v.size));
+        let write = regs.read32(symbol.addr + WRITE_OFFSET);
+
+        Ok(Self {
+            addr: symbol.addr,
+            capacity: capacity as u32,
+            overlay,
+            write,
+            outstanding: BTreeMap::new(),
+            completed: HashMap::new(),
+            overlay_owner: None,
+        })
+    }
+
+    fn slot_addr(&self, slot: u32) -> u64 {
+        self.addr + HEADER_SIZE + slot as u64 * COMMAND_SIZE
+    }
+
+    /// Items submitted and not yet collected.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    /// Whether the overlay region can be written, the last overlay has finished.
+    pub fn overlay_free(&self) -> bool {
+        self.overlay_owner.is_none()
+    }
+
+    /// Queue `work`, `None` if every slot is still in use.
+    pub fn try_submit(
+        &mut self,
+        regs: &mut dyn TileRegisters,
+        work: &Work,
+    ) -> Result<Option<WorkId>, RuntimeError> {
+        if work.args().len() > MAX_ARGS {
+            return Err(RuntimeError::TooManyArgs(work.args().len()));
+        }
+        if self.outstanding.len() >= self.capacity as usize {
+            return Ok(None);
+        }
+
+        let id = WorkId(self.write);
+        let slot = self.write % self.capacity;
+        let addr = self.slot_addr(slot);
+        for (index, word) in work.encode(id).into_iter().enumerate() {
+            regs.write32(addr + index as u64 * 4, word);
+        }
+
+        // Only publish the command once it is complete
+        self.write = self.write.wrapping_add(1);
+        regs.write32(self.addr + WRITE_OFFSET, self.write);
+
+        self.outstanding.insert(id, slot);
+        if matches!(work, Work::Overlay { .. }) {
+            self.overlay_owner = Some(id);
+        }
+        Ok(Some(id))
+    }
+
+    /// Collect every item that has finished since the last poll.
+    pub fn poll(&mut self, regs: &mut dyn TileRegisters) {
+        let mut finished = Vec::new();
+        for (id, slot) in &self.outstanding {
+            let addr = self.slot_addr(*slot);
+            let status = regs.read32(addr + STATUS_OFFSET);
+            if matches!(status, STATUS_DONE | STATUS_FAILED) {
+                finished.push(Completion {
+                    id: *id,
+                    ok: status == STATUS_DONE,
+                    result: regs.read32(addr + RESULT_OFFSET),
+                });
+            }
+        }
+
+        for completion in finished {
+            self.outstanding.remove(&completion.id);
+            if self.overlay_owner == Some(completion.id) {
+                self.overlay_owner = None;
+            }
+            self.completed.insert(completion.id, completion);
+        }
+    }
+
+    /// The read counter of the dispatcher.
+    pub fn read_counter(&self, regs: &mut dyn TileRegisters) -> u32 {
+        regs.read32(self.addr + READ_OFFSET)
+    }
+
+    /// Where `id` is at as of the last poll: `Ok(None)` while it is still outstanding.
+    pub fn status(&self, id: WorkId) -> Result<Option<Completion>, RuntimeError> {
+        match self.completed.get(&id) {
+            Some(completion) => Ok(Some(*completion)),
+            None if self.outstanding.contains_key(&id) => Ok(None),
+            None => Err(RuntimeError::Unknown(id)),
+        }
+    }
+
+    /// Hand over the completion of `id`, it is forgotten afterwards.
+    pub fn take(&mut self, id: WorkId) -> Option<Completion> {
+        self.completed.remove(&id)
+    }
+
+    /// Poll and hand over the completion of `id` once it has finished, `Ok(None)` while it is
+    /// still outstanding.
+    pub fn collect(
+        &mut self,
+        regs: &mut dyn TileRegisters,
+        id: WorkId,
+    ) -> Result<Option<Completion>, RuntimeError> {
+        self.poll(regs);
+        let completion = self.status(id)?;
+        if completion.is_some() {
+            self.take(id);
+        }
+
+        Ok(completion)
+    }
+}
+
+/// A dispatcher running on one core and the work sent to it.
+pub struct ResidentRuntime {
+    pub kernel: Kernel,
+    pub queue: CommandQueue,
+    /// How long [`ResidentRuntime::wait`] waits for an item, forever if `None`
+    pub timeout: Option<Duration>,
+}
+
+impl ResidentRuntime {
+    /// Load the dispatcher in `data` onto `core` and start it.
+    pub fn load(
+        mut device: Chip,
+        noc_id: NocId,
+        core: Tile,
+        data: KernelData,
+    ) -> Result<Self, RuntimeError> {
+        // Check for the queue before touching the core
+        data.symbol(COMMAND_QUEUE)?;
+
+        tracing::debug!("{}: loading dispatcher onto {core:?}", device);
+        loader::stop(&mut device, core);
+        data.load_with(&mut device, noc_id, core, Verify::default())?;
+
+        let mut kernel = Kernel::new(device, noc_id, core, data);
+        loader::easy_start(&mut kernel.device, core.addr);
+        if kernel.data.bin.start_sync.is_some() {
+            while !kernel.start_sync() {
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+        }
+
+        Self::from_kernel(kernel)
+    }
+
+    /// Use a dispatcher that was already started, e.g. by [`crate::loader::quick_load`] with
+    /// `no_wait`. Nothing may have been submitted to it yet.
+    pub fn from_kernel(mut kernel: Kernel) -> Result<Self, RuntimeError> {
+        let queue = {
+            let (data, mut regs) = split(&mut kernel);
+            CommandQueue::new(&mut regs, data)?
+        };
+        tracing::debug!(
+            "{:?}: command queue at 0x{:x} with {} slots",
+            kernel.core.addr.n0,
+            queue.addr,
+            queue.capacity
+        );
+
+        Ok(Self {
+            kernel,
+            queue,
+            timeout: None,
+        })
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// The dispatcher has returned, only known if it has `STATE_*` globals.
+    fn exited(&mut self) -> bool {
+        let monitored = RiscId::ALL
+            .into_iter()
+            .any(|risc| self.kernel.data.bin.risc(risc).state.is_some());
+        monitored && self.kernel.all_complete()
+    }
+
+    /// Fail if the dispatcher has panicked or returned.
+    fn check_running(&mut self) -> Result<(), RuntimeError> {
+        if self.kernel.check_panic() {
+            return Err(RuntimeError::Panicked);
+        }
+        if self.exited() {
+            return Err(RuntimeError::Exited);
+        }
+        Ok(())
+    }
+
+    /// Queue `work`, waiting for a free slot if the queue is full.
+    fn submit_work(&mut self, work: &Work) -> Result<WorkId, RuntimeError> {
+        loop {
+            let mut regs = regs(&mut self.kernel);
+            if let Some(id) = self.queue.try_submit(&mut regs, work)? {
+                return Ok(id);
+            }
+            self.queue.poll(&mut regs);
+            if self.queue.outstanding() >= self.queue.capacity as usize {
+                self.check_running()?;
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// Run `function` of the dispatcher with `args`.
+    pub fn submit(&mut self, function: u32, args: &[u32]) -> Result<WorkId, RuntimeError> {
+        self.submit_work(&Work::Call { function, args })
+    }
+
+    /// Copy `code` into the overlay region and run it with `args`. Waits for the previous overlay
+    /// to finish first.
+    pub fn submit_overlay(&mut self, code: &[u8], args: &[u32]) -> Result<WorkId, RuntimeError> {
+        let Some((addr, size)) = self.queue.overlay else {
+            return Err(SymbolError::NotFound(OVERLAY.to_string()).into());
+        };
+        if code.len() as u64 > size {
+            return Err(RuntimeError::OverlayTooLarge {
+                len: code.len(),
+                size,
+            });
+        }
+
+        while !self.queue.overlay_free() {
+            self.queue.poll(&mut regs(&mut self.kernel));
+            if !self.queue.overlay_free() {
+                self.check_running()?;
+                std::thread::sleep(WAIT_POLL_INTERVAL);
+            }
+        }
+
+        let Kernel {
+            device,
+            noc_id,
+            core,
+            ..
+        } = &mut self.kernel;
+        write_aligned(device, *noc_id, *core, addr, code);
+        self.submit_work(&Work::Overlay {
+            addr,
+            len: code.len() as u32,
+            args,
+        })
+    }
+
+    /// Check on `id` without waiting, `None` while it hasn't finished. Once its completion has
+    /// been returned `id` is forgotten.
+    pub fn poll(&mut self, id: WorkId) -> Result<Option<Completion>, RuntimeError> {
+        self.queue.collect(&mut regs(&mut self.kernel), id)
+    }
+
+    /// Wait for `id` to finish, up to [`ResidentRuntime::timeout`].
+    pub fn wait(&mut self, id: WorkId) -> Result<Completion, RuntimeError> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(completion) = self.poll(id)? {
+                return Ok(completion);
+            }
+
+            self.check_running()?;
+            if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                return Err(RuntimeError::Timeout(id));
+            }
+            std::thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+
+    /// Tell the dispatcher to return once the queue drains, and wait for it to.
+    pub fn shutdown(mut self) -> Result<Kernel, RuntimeError> {
+        let id = self.submit_work(&Work::Exit)?;
+        match self.wait(id) {
+            Ok(_) | Err(RuntimeError::Exited) => Ok(self.kernel),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn regs(kernel: &mut Kernel) -> ChipTile<'_> {
+    split(kernel).1
+}
+
+fn split(kernel: &mut Kernel) -> (&KernelData, ChipTile<'_>) {
+    let Kernel {
+        device,
+        noc_id,
+        core,
+        data,
+        ..
+    } = kernel;
+    let regs = ChipTile {
+        chip: device,
+        noc_id: *noc_id,
+        tile: *core,
+    };
+    (data, regs)
+}
@@ -0,0 +1,330 @@
+//! Sampling the `STATE_*` and `POSTCODE_*` globals of running kernels in the background.
+//!
+//! A [`Sampler`] polls a set of tiles from its own thread and chip handle at a fixed rate. Only
+//! changes are kept, so a kernel stuck at one postcode does not fill up its history, and each
+//! RISC keeps at most [`SamplerOptions::history`] of them. The history can be dumped as CSV or
+//! as a trace with one span per postcode.
+
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    chip::noc::{NocId, NocInterface, Tile},
+    kernel::{Kernel, KernelBinData, RiscId, RiscState},
+    program::ProgramHandle,
+    trace::{Phase, Trace, TraceEvent},
+    Chip,
+};
+
+#[derive(Clone, Debug)]
+pub struct SamplerOptions {
+    pub noc_id: NocId,
+    pub interval: Duration,
+    /// Changes kept per RISC, the oldest are dropped first
+    pub history: usize,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            noc_id: NocId::Noc0,
+            interval: Duration::from_millis(1),
+            history: 4096,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn noc_id(mut self, noc_id: NocId) -> Self {
+        self.noc_id = noc_id;
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = history.max(1);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// Time since the sampler started
+    pub time: Duration,
+    pub state: Option<RiscState>,
+    pub postcode: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RiscHistory {
+    pub tile: Tile,
+    pub risc: RiscId,
+    /// Every change seen, oldest first
+    pub samples: VecDeque<Sample>,
+    /// Changes dropped to stay within the history limit
+    pub dropped: usize,
+    /// Time of the last poll, the last sample held until then
+    pub last_poll: Duration,
+
+    state_addr: Option<u64>,
+    postcode_addr: Option<u64>,
+}
+
+impl RiscHistory {
+    fn record(&mut self, sample: Sample, limit: usize) {
+        self.last_poll = sample.time;
+
+        let last = self.samples.back();
+        if last.map(|v| (v.state, v.postcode)) == Some((sample.state, sample.postcode)) {
+            return;
+        }
+
+        if self.samples.len() == limit {
+            self.samples.pop_front();
+            self.dropped += 1;
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// The sampled history of every RISC being watched.
+#[derive(Clone, Debug)]
+pub struct History {
+    pub chip: usize,
+    /// Name of the chip's process in the trace
+    pub name: String,
+    pub riscs: Vec<RiscHistory>,
+}
+
+impl History {
+    /// An empty history for `tiles`, each with the kernel symbols used to find its globals.
+    pub fn new(chip: usize, name: impl Into<String>, tiles: &[(Tile, &KernelBinData)]) -> Self {
+        let riscs = tiles
+            .iter()
+            .flat_map(|(tile, bin)| {
+                RiscId::ALL.into_iter().filter_map(|risc| {
+                    let data = bin.risc(risc);
+                    (data.state.is_some() || data.pc.is_some()).then(|| RiscHistory {
+                        tile: *tile,
+                        risc,
+                        samples: VecDeque::new(),
+                        dropped: 0,
+                        last_poll: Duration::ZERO,
+                        state_addr: data.state,
+                        postcode_addr: data.pc,
+                    })
+                })
+            })
+            .collect();
+
+        Self {
+            chip,
+            name: name.into(),
+            riscs,
+        }
+    }
+
+    /// Record a poll of every RISC, `read` reads a word from a tile.
+    pub fn record(&mut self, time: Duration, limit: usize, read: &mut dyn FnMut(Tile, u64) -> u32) {
+        for risc in &mut self.riscs {
+            let sample = Sample {
+                time,
+                state: risc
+                    .state_addr
+                    .map(|addr| RiscState::from_raw(read(risc.tile, addr))),
+                postcode: risc.postcode_addr.map(|addr| read(risc.tile, addr)),
+            };
+            risc.record(sample, limit);
+        }
+    }
+
+    /// One row per change: `time_us,chip,x,y,risc,state,postcode`. Missing values are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut out = "time_us,chip,x,y,risc,state,postcode\n".to_string();
+        let mut rows = self
+            .riscs
+            .iter()
+            .flat_map(|risc| risc.samples.iter().map(move |sample| (risc, sample)))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|(_, sample)| sample.time);
+
+        for (risc, sample) in rows {
+            let (x, y) = risc.tile.addr.n0;
+            let state = sample
+                .state
+                .map(|v| v.raw().to_string())
+                .unwrap_or_default();
+            let postcode = sample
+                .postcode
+                .map(|v| format!("{v:#x}"))
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{x},{y},{},{state},{postcode}",
+                sample.time.as_micros(),
+                self.chip,
+                risc.risc.name()
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+
+    /// Add the history to `trace`, each sample is a span lasting until the next change.
+    pub fn add_to_trace(&self, trace: &mut Trace) {
+        let pid = self.chip as u64;
+        trace.process(pid, self.name.clone());
+
+        for risc in &self.riscs {
+            let tid = trace.track(pid, risc.tile, risc.risc);
+            let ends = risc
+                .samples
+                .iter()
+                .skip(1)
+                .map(|v| v.time)
+                .chain(std::iter::once(risc.last_poll));
+
+            for (sample, end) in risc.samples.iter().zip(ends) {
+                let name = match (sample.postcode, sample.state) {
+                    (Some(postcode), _) => format!("{postcode:#x}"),
+                    (None, Some(state)) => state.to_string(),
+                    (None, None) => continue,
+                };
+
+                let ts = |time: Duration| time.as_secs_f64() * 1e6;
+                let mut begin =
+                    TraceEvent::new(name.clone(), Phase::Begin, ts(sample.time), pid, tid);
+                if let Some(state) = sample.state {
+                    begin = begin.arg("state", state.to_string());
+                }
+                trace.push(begin);
+                trace.push(TraceEvent::new(name, Phase::End, ts(end), pid, tid));
+            }
+        }
+    }
+
+    pub fn trace(&self) -> Trace {
+        let mut trace = Trace::new();
+        self.add_to_trace(&mut trace);
+        trace
+    }
+}
+
+/// Samples tiles from a background thread until stopped or dropped.
+pub struct Sampler {
+    stop: Arc<AtomicBool>,
+    history: Arc<Mutex<History>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Start sampling `tiles` using `chip`, which is owned by the sampler thread. Use
+    /// [`Chip::dupe`] to sample a chip that is still in use.
+    pub fn spawn(
+        mut chip: Chip,
+        tiles: &[(Tile, &KernelBinData)],
+        options: SamplerOptions,
+    ) -> Self {
+        let history = Arc::new(Mutex::new(History::new(chip.id(), chip.to_string(), tiles)));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (history, stop) = (history.clone(), stop.clone());
+            std::thread::Builder::new()
+                .name("ttx-sampler".to_string())
+                .spawn(move || {
+                    let start = Instant::now();
+                    while !stop.load(Ordering::Relaxed) {
+                        let poll = Instant::now();
+                        history.lock().unwrap().record(
+                            start.elapsed(),
+                            options.history,
+                            &mut |tile, addr| chip.noc_read32(options.noc_id, tile, addr),
+                        );
+                        std::thread::sleep(options.interval.saturating_sub(poll.elapsed()));
+                    }
+                })
+                .unwrap()
+        };
+
+        Self {
+            stop,
+            history,
+            thread: Some(thread),
+        }
+    }
+
+    /// A copy of the history so far.
+    pub fn history(&self) -> History {
+        self.history.lock().unwrap().clone()
+    }
+
+    /// Stop sampling and return the full history.
+    pub fn stop(mut self) -> History {
+        self.join();
+        self.history()
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                tracing::warn!("sampler thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+impl Kernel {
+    /// Sample the kernel's tile in the background with a new handle to its chip.
+    pub fn sample(&mut self, options: SamplerOptions) -> Result<Sampler, String> {
+        let chip = self.device.dupe()?;
+        Ok(Sampler::spawn(
+            chip,
+            &[(self.core, &self.data.bin)],
+            options.noc_id(self.noc_id),
+        ))
+    }
+}
+
+impl ProgramHandle {
+    /// Sample every core of the program in the background with a new handle to its chip.
+    pub fn sample(&mut self, options: SamplerOptions) -> Result<Sampler, String> {
+        let chip = self.device.dupe()?;
+        let tiles = self
+            .groups
+            .iter()
+            .flat_map(|(cores, data)| cores.tiles().iter().map(move |tile| (*tile, &data.bin)))
+            .collect::<Vec<_>>();
+
+        Ok(Sampler::spawn(chip, &tiles, options.noc_id(self.noc_id)))
+    }
+}
@@ -0,0 +1,156 @@
+//! Tensors laid out the way tensix compute expects them.
+//!
+//! Host data is row-major `f32` (or bfloat16). A [`Tensor`] holds it tilized (see [`layout`]) and
+//! packed into a [`DataFormat`] (see [`format`]), one tile after another, ready to be written to
+//! L1 or to an [`InterleavedBuffer`] with one tile per page.
+
+use crate::{
+    allocator::{AllocError, DramAllocator, InterleavedBuffer},
+    chip::noc::{NocId, Tile},
+    kernel::{read_aligned, write_aligned},
+    Chip,
+};
+
+pub mod format;
+pub mod layout;
+
+pub use format::DataFormat;
+use layout::{tile_count, tilize, untilize};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    pub rows: usize,
+    pub cols: usize,
+    pub format: DataFormat,
+    /// Packed tiles
+    pub data: Vec<u8>,
+}
+
+impl Tensor {
+    /// Tilize and pack a row-major `rows` x `cols` tensor, edge tiles are padded with zeros.
+    pub fn from_f32(values: &[f32], rows: usize, cols: usize, format: DataFormat) -> Self {
+        let tilized = tilize(values, rows, cols, 0.0);
+        Self {
+            rows,
+            cols,
+            format,
+            data: format::pack(format, &tilized),
+        }
+    }
+
+    /// Like [`Tensor::from_f32`] for row-major bfloat16 values.
+    pub fn from_bf16(values: &[u16], rows: usize, cols: usize, format: DataFormat) -> Self {
+        let values = values
+            .iter()
+            .map(|v| format::bf16_to_f32(*v))
+            .collect::<Vec<_>>();
+        Self::from_f32(&values, rows, cols, format)
+    }
+
+    /// Already packed tiles, e.g. read back from the device.
+    pub fn from_packed(data: Vec<u8>, rows: usize, cols: usize, format: DataFormat) -> Self {
+        assert_eq!(
+            data.len(),
+            tile_count(rows, cols) * format.tile_bytes(),
+            "data is not {rows}x{cols} packed as {format:?}"
+        );
+        Self {
+            rows,
+            cols,
+            format,
+            data,
+        }
+    }
+
+    pub fn tiles(&self) -> usize {
+        tile_count(self.rows, self.cols)
+    }
+
+    /// The packed bytes of tile `index`.
+    pub fn tile(&self, index: usize) -> &[u8] {
+        let size = self.format.tile_bytes();
+        &self.data[index * size..][..size]
+    }
+
+    /// Unpack and untilize back to row-major.
+    pub fn to_f32(&self) -> Vec<f32> {
+        untilize(
+            &format::unpack(self.format, &self.data),
+            self.rows,
+            self.cols,
+        )
+    }
+
+    pub fn to_bf16(&self) -> Vec<u16> {
+        self.to_f32().into_iter().map(format::f32_to_bf16).collect()
+    }
+
+    /// Convert to another format.
+    pub fn convert(&self, format: DataFormat) -> Self {
+        Self::from_f32(&self.to_f32(), self.rows, self.cols, format)
+    }
+
+    pub fn write_l1(&self, chip: &mut Chip, noc_id: NocId, tile: Tile, addr: u64) {
+        write_aligned(chip, noc_id, tile, addr, &self.data);
+    }
+
+    pub fn read_l1(
+        chip: &mut Chip,
+        noc_id: NocId,
+        tile: Tile,
+        addr: u64,
+        rows: usize,
+        cols: usize,
+        format: DataFormat,
+    ) -> Self {
+        let len = tile_count(rows, cols) * format.tile_bytes();
+        let data = read_aligned(chip, noc_id, tile, addr, len);
+        Self::from_packed(data, rows, cols, format)
+    }
+
+    fn check_buffer(&self, buffer: &InterleavedBuffer) {
+        assert_eq!(
+            buffer.page_size as usize,
+            self.format.tile_bytes(),
+            "{} pages do not hold one {:?} tile",
+            buffer.name,
+            self.format
+        );
+    }
+
+    /// An interleaved buffer sized for the tensor with one tile per page.
+    pub fn alloc_interleaved(
+        &self,
+        dram: &mut DramAllocator,
+        name: &str,
+    ) -> Result<InterleavedBuffer, AllocError> {
+        dram.interleaved(
+            name,
+            self.data.len() as u64,
+            self.format.tile_bytes() as u64,
+        )
+    }
+
+    /// Write the tensor into `buffer`, one tile per page.
+    pub fn write_interleaved(&self, chip: &mut Chip, noc_id: NocId, buffer: &InterleavedBuffer) {
+        self.check_buffer(buffer);
+        buffer.write_all(chip, noc_id, &self.data);
+    }
+
+    pub fn read_interleaved(
+        chip: &mut Chip,
+        noc_id: NocId,
+        buffer: &InterleavedBuffer,
+        rows: usize,
+        cols: usize,
+        format: DataFormat,
+    ) -> Self {
+        let len = tile_count(rows, cols) * format.tile_bytes();
+        let mut data = buffer.read_all(chip, noc_id);
+        data.truncate(len);
+
+        let tensor = Self::from_packed(data, rows, cols, format);
+        tensor.check_buffer(buffer);
+        tensor
+    }
+}
@@ -0,0 +1,227 @@
+//! Packing tilized values into the data formats tensix works on.
+//!
+//! The block float formats split each tile into its 64 face rows of 16 values, which share the
+//! largest of their exponents. A tile is stored as the 64 exponent bytes followed by the values:
+//! a sign bit and a 7 bit magnitude per byte for `Bfp8B`, a sign bit and 3 bit magnitude per
+//! nibble (low nibble first) for `Bfp4B`. Magnitudes are fixed point with the leading one
+//! explicit, so values much smaller than the largest of their row lose precision or become zero.
+
+use super::layout::{FACE_WIDTH, TILE_VALUES};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataFormat {
+    Float32,
+    /// IEEE half precision
+    Float16,
+    /// bfloat16
+    Float16B,
+    Bfp8B,
+    Bfp4B,
+}
+
+/// Exponent bytes at the start of each block float tile
+const EXPONENTS_PER_TILE: usize = TILE_VALUES / FACE_WIDTH;
+
+impl DataFormat {
+    /// Bytes taken by one tile.
+    pub fn tile_bytes(self) -> usize {
+        match self {
+            DataFormat::Float32 => TILE_VALUES * 4,
+            DataFormat::Float16 | DataFormat::Float16B => TILE_VALUES * 2,
+            DataFormat::Bfp8B => EXPONENTS_PER_TILE + TILE_VALUES,
+            DataFormat::Bfp4B => EXPONENTS_PER_TILE + TILE_VALUES / 2,
+        }
+    }
+
+    /// Magnitude bits of a block float value.
+    fn mantissa_bits(self) -> Option<u32> {
+        match self {
+            DataFormat::Bfp8B => Some(7),
+            DataFormat::Bfp4B => Some(3),
+            _ => None,
+        }
+    }
+}
+
+/// Round to the nearest bfloat16, ties to even.
+pub fn f32_to_bf16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    if value.is_nan() {
+        return ((bits >> 16) | 0x40) as u16;
+    }
+    let round = 0x7fff + ((bits >> 16) & 1);
+    (bits.wrapping_add(round) >> 16) as u16
+}
+
+pub fn bf16_to_f32(value: u16) -> f32 {
+    f32::from_bits((value as u32) << 16)
+}
+
+/// Round to the nearest IEEE half, ties to even. Out of range values become infinity.
+pub fn f32_to_fp16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let (half, shift, mantissa) = if exponent <= 0 {
+        // Subnormal, shift the implicit one in
+        if exponent < -10 {
+            return sign;
+        }
+        let shift = (14 - exponent) as u32;
+        let mantissa = mantissa | 0x80_0000;
+        (mantissa >> shift, shift, mantissa)
+    } else {
+        (((exponent as u32) << 10) | (mantissa >> 13), 13, mantissa)
+    };
+
+    let rest = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round = rest > halfway || (rest == halfway && half & 1 == 1);
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + round as u32) as u16
+}
+
+pub fn fp16_to_f32(value: u16) -> f32 {
+    let sign = ((value & 0x8000) as u32) << 16;
+    let exponent = ((value >> 10) & 0x1f) as u32;
+    let mantissa = (value & 0x3ff) as u32;
+
+    match exponent {
+        0 => {
+            let magnitude = mantissa as f32 * 2f32.powi(-24);
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+fn exponent(value: f32) -> u8 {
+    ((value.to_bits() >> 23) & 0xff) as u8
+}
+
+/// Weight of the lowest magnitude bit of a block with `exponent`.
+fn block_scale(exponent: u8, bits: u32) -> f64 {
+    2f64.powi(exponent as i32 - 127 - (bits as i32 - 1))
+}
+
+fn encode_block_value(value: f32, scale: f64, bits: u32) -> u8 {
+    let max = (1u32 << bits) - 1;
+    let magnitude = ((value.abs() as f64 / scale).round() as u32).min(max) as u8;
+    // Keep the sign of values that round to zero off so zero is always +0
+    let sign = (value.is_sign_negative() && magnitude != 0) as u8;
+    (sign << bits) | magnitude
+}
+
+fn decode_block_value(raw: u8, scale: f64, bits: u32) -> f32 {
+    let magnitude = (raw & ((1 << bits) - 1)) as f64 * scale;
+    if raw >> bits & 1 != 0 {
+        -magnitude as f32
+    } else {
+        magnitude as f32
+    }
+}
+
+fn pack_block_tile(tile: &[f32], bits: u32, out: &mut Vec<u8>) {
+    let rows = tile.chunks(FACE_WIDTH).collect::<Vec<_>>();
+    let exponents = rows
+        .iter()
+        .map(|row| row.iter().copied().map(exponent).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+    out.extend(&exponents);
+
+    let values = rows.iter().zip(&exponents).flat_map(|(row, exponent)| {
+        let scale = block_scale(*exponent, bits);
+        row.iter()
+            .map(move |value| encode_block_value(*value, scale, bits))
+    });
+    match bits {
+        7 => out.extend(values),
+        _ => {
+            let values = values.collect::<Vec<_>>();
+            out.extend(values.chunks(2).map(|v| v[0] | (v[1] << 4)));
+        }
+    }
+}
+
+fn unpack_block_tile(tile: &[u8], bits: u32, out: &mut Vec<f32>) {
+    let (exponents, data) = tile.split_at(EXPONENTS_PER_TILE);
+    let values = match bits {
+        7 => data.to_vec(),
+        _ => data.iter().flat_map(|v| [v & 0xf, v >> 4]).collect(),
+    };
+
+    for (row, exponent) in values.chunks(FACE_WIDTH).zip(exponents) {
+        let scale = block_scale(*exponent, bits);
+        out.extend(row.iter().map(|v| decode_block_value(*v, scale, bits)));
+    }
+}
+
+/// Pack tilized `values`, block float formats need whole tiles.
+pub fn pack(format: DataFormat, values: &[f32]) -> Vec<u8> {
+    match format {
+        DataFormat::Float32 => bytemuck::cast_slice(values).to_vec(),
+        DataFormat::Float16 => values
+            .iter()
+            .flat_map(|v| f32_to_fp16(*v).to_le_bytes())
+            .collect(),
+        DataFormat::Float16B => values
+            .iter()
+            .flat_map(|v| f32_to_bf16(*v).to_le_bytes())
+            .collect(),
+        DataFormat::Bfp8B | DataFormat::Bfp4B => {
+            assert!(
+                values.len().is_multiple_of(TILE_VALUES),
+                "{format:?} needs whole tiles"
+            );
+            let bits = format.mantissa_bits().unwrap();
+            let mut out = Vec::with_capacity(values.len() / TILE_VALUES * format.tile_bytes());
+            for tile in values.chunks(TILE_VALUES) {
+                pack_block_tile(tile, bits, &mut out);
+            }
+            out
+        }
+    }
+}
+
+/// Unpack values packed by [`pack`].
+pub fn unpack(format: DataFormat, data: &[u8]) -> Vec<f32> {
+    match format {
+        DataFormat::Float32 => data
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes(v.try_into().unwrap()))
+            .collect(),
+        DataFormat::Float16 => data
+            .chunks_exact(2)
+            .map(|v| fp16_to_f32(u16::from_le_bytes([v[0], v[1]])))
+            .collect(),
+        DataFormat::Float16B => data
+            .chunks_exact(2)
+            .map(|v| bf16_to_f32(u16::from_le_bytes([v[0], v[1]])))
+            .collect(),
+        DataFormat::Bfp8B | DataFormat::Bfp4B => {
+            assert!(
+                data.len().is_multiple_of(format.tile_bytes()),
+                "{format:?} needs whole tiles"
+            );
+            let bits = format.mantissa_bits().unwrap();
+            let mut out = Vec::with_capacity(data.len() / format.tile_bytes() * TILE_VALUES);
+            for tile in data.chunks(format.tile_bytes()) {
+                unpack_block_tile(tile, bits, &mut out);
+            }
+            out
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Converting between row-major and tilized layouts.
+//!
+//! A tile is 32x32 values split into four 16x16 faces: top left, top right, bottom left, bottom
+//! right. Each face is stored row-major and tiles follow each other row-major across the tensor.
+
+pub const TILE_HEIGHT: usize = 32;
+pub const TILE_WIDTH: usize = 32;
+pub const FACE_HEIGHT: usize = 16;
+pub const FACE_WIDTH: usize = 16;
+/// Values per tile
+pub const TILE_VALUES: usize = TILE_HEIGHT * TILE_WIDTH;
+
+/// Number of tiles covering a `rows` x `cols` tensor, padded out to whole tiles.
+pub fn tile_count(rows: usize, cols: usize) -> usize {
+    rows.div_ceil(TILE_HEIGHT) * cols.div_ceil(TILE_WIDTH)
+}
+
+/// Position within the tilized data of the value at `row`, `col`.
+pub fn tilized_index(cols: usize, row: usize, col: usize) -> usize {
+    let tiles_per_row = cols.div_ceil(TILE_WIDTH);
+    let tile = (row / TILE_HEIGHT) * tiles_per_row + col / TILE_WIDTH;
+
+    let (row, col) = (row % TILE_HEIGHT, col % TILE_WIDTH);
+    let face = (row / FACE_HEIGHT) * 2 + col / FACE_WIDTH;
+    let (row, col) = (row % FACE_HEIGHT, col % FACE_WIDTH);
+
+    tile * TILE_VALUES + face * FACE_HEIGHT * FACE_WIDTH + row * FACE_WIDTH + col
+}
+
+/// Tilize a row-major `rows` x `cols` tensor, padding the edge tiles with `pad`.
+pub fn tilize<T: Copy>(data: &[T], rows: usize, cols: usize, pad: T) -> Vec<T> {
+    assert_eq!(data.len(), rows * cols, "data is not {rows}x{cols}");
+
+    let mut out = vec![pad; tile_count(rows, cols) * TILE_VALUES];
+    for row in 0..rows {
+        for col in 0..cols {
+            out[tilized_index(cols, row, col)] = data[row * cols + col];
+        }
+    }
+    out
+}
+
+/// Back to a row-major `rows` x `cols` tensor, dropping the padding.
+pub fn untilize<T: Copy>(data: &[T], rows: usize, cols: usize) -> Vec<T> {
+    assert_eq!(
+        data.len(),
+        tile_count(rows, cols) * TILE_VALUES,
+        "data is not {rows}x{cols} tilized"
+    );
+
+    let mut out = Vec::with_capacity(rows * cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            out.push(data[tilized_index(cols, row, col)]);
+        }
+    }
+    out
+}
@@ -0,0 +1,118 @@
+//! Chrome trace event JSON, viewable in Perfetto or `chrome://tracing`.
+//!
+//! Each chip is a process and each RISC of a tile is a thread within it. Timestamps are in
+//! microseconds.
+
+use std::{collections::HashSet, path::Path};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{chip::noc::Tile, kernel::RiscId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Phase {
+    #[serde(rename = "B")]
+    Begin,
+    #[serde(rename = "E")]
+    End,
+    #[serde(rename = "i")]
+    Instant,
+    #[serde(rename = "C")]
+    Counter,
+    #[serde(rename = "M")]
+    Metadata,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub ph: Phase,
+    pub ts: f64,
+    pub pid: u64,
+    pub tid: u64,
+    /// Scope of an instant event, `t` for its thread
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s: Option<&'static str>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub args: Map<String, Value>,
+}
+
+impl TraceEvent {
+    pub fn new(name: impl Into<String>, ph: Phase, ts: f64, pid: u64, tid: u64) -> Self {
+        Self {
+            name: name.into(),
+            ph,
+            ts,
+            pid,
+            tid,
+            s: (ph == Phase::Instant).then_some("t"),
+            args: Map::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.args.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// The thread id used for `risc` of `tile`
+pub fn track_id(tile: Tile, risc: RiscId) -> u64 {
+    let (x, y) = tile.addr.n0;
+    let risc = RiscId::ALL.iter().position(|v| *v == risc).unwrap();
+    ((x as u64) << 16) | ((y as u64) << 8) | risc as u64
+}
+
+#[derive(Default, Serialize)]
+pub struct Trace {
+    #[serde(rename = "traceEvents")]
+    pub events: Vec<TraceEvent>,
+
+    #[serde(skip)]
+    processes: HashSet<u64>,
+    #[serde(skip)]
+    tracks: HashSet<(u64, u64)>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the process of chip `pid`, only the first name given is kept.
+    pub fn process(&mut self, pid: u64, name: impl Into<String>) {
+        if self.processes.insert(pid) {
+            self.events.push(
+                TraceEvent::new("process_name", Phase::Metadata, 0.0, pid, 0)
+                    .arg("name", name.into()),
+            );
+        }
+    }
+
+    /// The thread of `risc` on `tile`, named the first time it is used.
+    pub fn track(&mut self, pid: u64, tile: Tile, risc: RiscId) -> u64 {
+        let tid = track_id(tile, risc);
+        if self.tracks.insert((pid, tid)) {
+            let (x, y) = tile.addr.n0;
+            self.events.push(
+                TraceEvent::new("thread_name", Phase::Metadata, 0.0, pid, tid)
+                    .arg("name", format!("tile {x},{y} {}", risc.name())),
+            );
+        }
+
+        tid
+    }
+
+    pub fn push(&mut self, event: TraceEvent) {
+        self.events.push(event);
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+}
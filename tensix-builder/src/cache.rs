@@ -0,0 +1,288 @@
+//! Content addressed cache of built kernels.
+//!
+//! Entries are keyed by a hash of everything that goes into a kernel build: the sources of
+//! every local package in the kernel's dependency graph, `Cargo.lock`, the `.cargo/config` files
+//! cargo picks up, the target json and linker script, the kernel name, profile, features,
+//! `RUSTFLAGS` and the toolchain version.
+//! Each entry is a directory named by its key holding the built artifact and a small info file.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{CacheEnable, CargoResult};
+
+const ARTIFACT_NAME: &str = "kernel";
+const INFO_NAME: &str = "info";
+
+pub struct CacheEntry {
+    pub key: String,
+    pub kernel_name: String,
+    pub path: PathBuf,
+    pub bin: bool,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The cache lives next to the kernel builds in the target dir of the calling crate.
+    pub fn default_dir() -> PathBuf {
+        crate::get_target_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("tensix-builder-cache")
+    }
+
+    pub fn from_option(use_cache: &CacheEnable) -> Option<Self> {
+        match use_cache {
+            CacheEnable::Enabled => Some(Self::new(Self::default_dir())),
+            CacheEnable::CustomDir(dir) => Some(Self::new(dir)),
+            CacheEnable::Sccache(_) | CacheEnable::Disabled => None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get(&self, key: &str) -> Option<CargoResult> {
+        let entry = self.read_entry(key)?;
+
+        // Track usage through the mtime of the info file so that prune can drop stale entries
+        if let Ok(file) = std::fs::File::options()
+            .write(true)
+            .open(self.dir.join(key).join(INFO_NAME))
+        {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(CargoResult {
+            path: entry.path,
+            bin: entry.bin,
+        })
+    }
+
+    pub fn insert(
+        &self,
+        key: &str,
+        kernel_name: &str,
+        result: &CargoResult,
+    ) -> std::io::Result<CargoResult> {
+        let entry_dir = self.dir.join(key);
+        std::fs::create_dir_all(&entry_dir)?;
+
+        let path = entry_dir.join(ARTIFACT_NAME);
+        std::fs::copy(&result.path, &path)?;
+        std::fs::write(
+            entry_dir.join(INFO_NAME),
+            format!("kernel_name={kernel_name}\nbin={}\n", result.bin),
+        )?;
+
+        Ok(CargoResult {
+            path,
+            bin: result.bin,
+        })
+    }
+
+    fn read_entry(&self, key: &str) -> Option<CacheEntry> {
+        let entry_dir = self.dir.join(key);
+        let info_path = entry_dir.join(INFO_NAME);
+        let path = entry_dir.join(ARTIFACT_NAME);
+
+        let info = std::fs::read_to_string(&info_path).ok()?;
+        let size = std::fs::metadata(&path).ok()?.len();
+        let last_used = std::fs::metadata(&info_path).ok()?.modified().ok()?;
+
+        let mut kernel_name = String::new();
+        let mut bin = false;
+        for line in info.lines() {
+            match line.split_once('=') {
+                Some(("kernel_name", value)) => kernel_name = value.to_string(),
+                Some(("bin", value)) => bin = value == "true",
+                _ => {}
+            }
+        }
+
+        Some(CacheEntry {
+            key: key.to_string(),
+            kernel_name,
+            path,
+            bin,
+            size,
+            last_used,
+        })
+    }
+
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let mut entries = Vec::new();
+        if let Ok(dir) = std::fs::read_dir(&self.dir) {
+            for entry in dir.flatten() {
+                if let Some(entry) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|key| self.read_entry(key))
+                {
+                    entries.push(entry);
+                }
+            }
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+
+        entries
+    }
+
+    pub fn remove(&self, key: &str) -> std::io::Result<()> {
+        std::fs::remove_dir_all(self.dir.join(key))
+    }
+
+    /// Remove all entries that have not been used within `max_age`, returns the number removed.
+    pub fn prune(&self, max_age: Duration) -> usize {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in self.entries() {
+            let age = now.duration_since(entry.last_used).unwrap_or_default();
+            if age > max_age && self.remove(&entry.key).is_ok() {
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+
+    pub fn clear(&self) -> std::io::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CacheKey(Sha256);
+
+impl CacheKey {
+    pub fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    /// Every input is tagged and length prefixed so that adjacent inputs can't alias.
+    pub fn add(&mut self, label: &str, data: &[u8]) {
+        self.0.update((label.len() as u64).to_le_bytes());
+        self.0.update(label.as_bytes());
+        self.0.update((data.len() as u64).to_le_bytes());
+        self.0.update(data);
+    }
+
+    /// Hash the sources of the crate at `path` and of every local package it depends on.
+    pub fn add_sources<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        self.add_cargo_config(path);
+
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .current_dir(path)
+            .exec();
+
+        match metadata {
+            Ok(metadata) => {
+                let lock = metadata.workspace_root.as_std_path().join("Cargo.lock");
+                if let Ok(lock) = std::fs::read(lock) {
+                    self.add("Cargo.lock", &lock);
+                }
+
+                let target_dir = metadata.target_directory.as_std_path();
+
+                let mut roots = metadata
+                    .packages
+                    .iter()
+                    .filter(|package| package.source.is_none())
+                    .filter_map(|package| package.manifest_path.parent())
+                    .map(|dir| dir.as_std_path().to_path_buf())
+                    .collect::<Vec<_>>();
+                roots.sort();
+                roots.dedup();
+
+                for root in roots {
+                    self.add_dir(&root, &root, target_dir);
+                }
+            }
+            Err(_) => {
+                self.add_dir(path, path, &path.join("target"));
+            }
+        }
+    }
+
+    fn add_dir(&mut self, root: &Path, dir: &Path, target_dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        let mut entries = entries.flatten().map(|v| v.path()).collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            // Cargo config is hashed by `add_cargo_config`, other dot entries are vcs or editor state
+            let hidden = path
+                .file_name()
+                .map(|name| name.to_string_lossy().starts_with('.'))
+                .unwrap_or(false);
+            if hidden || path == target_dir {
+                continue;
+            }
+
+            if path.is_dir() {
+                self.add_dir(root, &path, target_dir);
+            } else if let Ok(data) = std::fs::read(&path) {
+                let name = path.strip_prefix(root).unwrap_or(&path);
+                self.add(&name.to_string_lossy(), &data);
+            }
+        }
+    }
+
+    /// Hash the `.cargo/config` files cargo reads for a build in `path`, they can change the
+    /// rustflags, target and linker of the build.
+    pub fn add_cargo_config<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        for dir in path.ancestors() {
+            for name in ["config", "config.toml"] {
+                let config = dir.join(".cargo").join(name);
+                if let Ok(data) = std::fs::read(&config) {
+                    self.add(&config.to_string_lossy(), &data);
+                }
+            }
+        }
+    }
+
+    pub fn add_toolchain(&mut self) {
+        if let Ok(output) = Command::new("rustc").args(["+nightly", "-vV"]).output() {
+            self.add("rustc", &output.stdout);
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+            .finalize()
+            .iter()
+            .map(|v| format!("{v:02x}"))
+            .collect()
+    }
+}
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,6 +5,10 @@ use std::{
     process::{Command, Stdio},
 };
 
+pub mod cache;
+
+use cache::{BuildCache, CacheKey};
+
 #[derive(Clone)]
 pub enum StandardTarget {
     Grayskull,
@@ -80,6 +84,15 @@ impl TensixTarget {
     }
 }
 
+/// Prefix of the absolute symbol the builder defines in every kernel to record which
+/// target (the `env` field of the target json) it was built for.
+pub const TARGET_MARKER_PREFIX: &str = "__tensix_target_";
+
+fn get_target_env(target_json: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(target_json).ok()?;
+    value.get("env")?.as_str().map(|v| v.to_string())
+}
+
 pub enum CargoProfile {
     Release,
     Debug,
@@ -97,8 +110,12 @@ impl CargoProfile {
 }
 
 pub enum CacheEnable {
+    /// Use the built-in kernel cache stored in the given directory
     CustomDir(PathBuf),
+    /// Use the built-in kernel cache stored under the target dir
     Enabled,
+    /// Run rustc through sccache, optionally setting `SCCACHE_DIR`
+    Sccache(Option<PathBuf>),
     Disabled,
 }
 
@@ -118,7 +135,7 @@ pub struct CargoOptions {
 // Check if we might be running inside a cargo invocation.
 // Will assume that this is true if we can invoke `cargo metadata`
 // If we just append /tensix-builder to it to avoid a deadlock
-fn get_target_dir() -> Option<PathBuf> {
+pub(crate) fn get_target_dir() -> Option<PathBuf> {
     if let Ok(metadata) = cargo_metadata::MetadataCommand::new().exec() {
         Some(metadata.target_directory.as_std_path().to_path_buf())
     } else {
@@ -190,6 +207,7 @@ pub fn invoke_cargo<P: AsRef<Path>>(path: P, options: CargoOptions) -> CargoResu
     let target = options.target.to_string();
 
     let mut linker_path = None;
+    let mut linker_script_data = None;
 
     let target_def_file = match options.target {
         TensixTarget::Standard(standard_target) => {
@@ -268,13 +286,17 @@ pub fn invoke_cargo<P: AsRef<Path>>(path: P, options: CargoOptions) -> CargoResu
             };
 
             let link_file = dir.path().join(format!("{name}.x"));
-            std::fs::write(&link_file, linker_script).unwrap();
+            std::fs::write(&link_file, &linker_script).unwrap();
             linker_path = Some(dir.path());
+            linker_script_data = Some(linker_script);
 
             file
         }
     };
 
+    let target_json = std::fs::read_to_string(&target_def_file).unwrap();
+    let target_env = get_target_env(&target_json);
+
     let build_std = if options.build_std {
         "-Zbuild-std"
     } else {
@@ -310,21 +332,56 @@ pub fn invoke_cargo<P: AsRef<Path>>(path: P, options: CargoOptions) -> CargoResu
         ]);
     }
 
-    let mut kernel_name = options.kernel_name;
+    let mut kernel_name = options.kernel_name.clone();
     if !kernel_name.starts_with('"') || !kernel_name.ends_with('"') {
         kernel_name = format!("\"{kernel_name}\"");
     }
     let mut flags = format!("--cfg kernel_name={}", kernel_name);
+    if let Some(env) = target_env {
+        flags = format!("{flags} -C link-arg=--defsym={TARGET_MARKER_PREFIX}{env}=0");
+    }
+
+    let build_cache = BuildCache::from_option(&options.use_cache);
+    let cache_key = build_cache.as_ref().map(|_| {
+        let mut key = CacheKey::new();
+        key.add_sources(&path);
+        key.add("target", target_json.as_bytes());
+        if let Some(linker_script) = &linker_script_data {
+            key.add("linker_script", linker_script.as_bytes());
+        }
+        key.add("kernel_name", options.kernel_name.as_bytes());
+        key.add("profile", options.profile.to_string().as_bytes());
+        key.add(
+            "features",
+            format!(
+                "lto={} build_std={} default_features={}",
+                options.lto, options.build_std, options.default_features
+            )
+            .as_bytes(),
+        );
+        // The linker search path is a fresh temp dir each time, so hash the flags before it's added
+        key.add("RUSTFLAGS", flags.as_bytes());
+        key.add_toolchain();
+
+        key.finish()
+    });
+
+    if let (Some(cache), Some(key)) = (&build_cache, &cache_key) {
+        if let Some(result) = cache.get(key) {
+            return result;
+        }
+    }
+
     if let Some(linker_path) = linker_path {
         flags = format!("{flags} -L {}", linker_path.display());
     }
     cargo.env("RUSTFLAGS", flags);
 
-    if let CacheEnable::Enabled | CacheEnable::CustomDir(_) = options.use_cache {
+    if let CacheEnable::Sccache(dir) = &options.use_cache {
         cargo.env("RUSTC_WRAPPER", "sccache");
-    }
-    if let CacheEnable::CustomDir(dir) = options.use_cache {
-        cargo.env("SCCACHE_DIR", format!("{}", dir.display()));
+        if let Some(dir) = dir {
+            cargo.env("SCCACHE_DIR", format!("{}", dir.display()));
+        }
     }
 
     if options.lto {
@@ -348,15 +405,27 @@ pub fn invoke_cargo<P: AsRef<Path>>(path: P, options: CargoOptions) -> CargoResu
     let build = build.output().expect("Failed to execute cargo build");
 
     if build.status.success() {
-        get_compiler_artifact(&String::from_utf8(build.stdout).unwrap()).unwrap_or_else(|| {
-            if options.hide_output {
-                eprintln!(
-                    "--- build output ---\n{}",
-                    String::from_utf8(build.stderr).unwrap()
+        let result = get_compiler_artifact(&String::from_utf8(build.stdout).unwrap())
+            .unwrap_or_else(|| {
+                if options.hide_output {
+                    eprintln!(
+                        "--- build output ---\n{}",
+                        String::from_utf8(build.stderr).unwrap()
+                    );
+                }
+                panic!(
+                    "build artifact not found in (supposedly successful) build output (see above)"
                 );
+            });
+
+        if let (Some(cache), Some(key)) = (&build_cache, &cache_key) {
+            match cache.insert(key, &options.kernel_name, &result) {
+                Ok(cached) => return cached,
+                Err(err) => eprintln!("failed to add kernel to the build cache: {err}"),
             }
-            panic!("build artifact not found in (supposedly successful) build output (see above)");
-        })
+        }
+
+        result
     } else {
         if options.hide_output {
             eprintln!(
@@ -0,0 +1,126 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use tensix_builder::{
+    cache::{BuildCache, CacheKey},
+    CargoResult,
+};
+
+fn key(path: &Path) -> String {
+    let mut key = CacheKey::new();
+    key.add_sources(path);
+    key.add("kernel_name", b"test");
+    key.finish()
+}
+
+/// A kernel crate without a manifest, so the key falls back to hashing the directory.
+fn kernel_dir() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+    dir
+}
+
+fn artifact(dir: &Path, data: &str) -> CargoResult {
+    let path = dir.join("built");
+    std::fs::write(&path, data).unwrap();
+    CargoResult { path, bin: true }
+}
+
+#[test]
+fn hit_after_insert() {
+    let kernel = kernel_dir();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = BuildCache::new(cache_dir.path());
+
+    let key = key(kernel.path());
+    assert!(cache.get(&key).is_none());
+
+    let cached = cache
+        .insert(&key, "test", &artifact(kernel.path(), "elf"))
+        .unwrap();
+    let hit = cache.get(&key).unwrap();
+    assert_eq!(hit.path, cached.path);
+    assert!(hit.bin);
+    assert_eq!(std::fs::read_to_string(hit.path).unwrap(), "elf");
+
+    let entries = cache.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].kernel_name, "test");
+
+    cache.clear().unwrap();
+    assert!(cache.get(&key).is_none());
+}
+
+#[test]
+fn source_change_misses() {
+    let kernel = kernel_dir();
+    let before = key(kernel.path());
+    assert_eq!(key(kernel.path()), before);
+
+    std::fs::write(kernel.path().join("src/main.rs"), "fn main() { loop {} }").unwrap();
+    assert_ne!(key(kernel.path()), before);
+}
+
+#[test]
+fn build_output_is_not_hashed() {
+    let kernel = kernel_dir();
+    let before = key(kernel.path());
+
+    std::fs::create_dir_all(kernel.path().join("target")).unwrap();
+    std::fs::write(kernel.path().join("target/kernel"), "elf").unwrap();
+    assert_eq!(key(kernel.path()), before);
+
+    // Only the crate's own target dir is skipped
+    std::fs::create_dir_all(kernel.path().join("src/target")).unwrap();
+    std::fs::write(kernel.path().join("src/target/mod.rs"), "").unwrap();
+    assert_ne!(key(kernel.path()), before);
+}
+
+#[test]
+fn cargo_config_change_misses() {
+    let kernel = kernel_dir();
+    let config = kernel.path().join(".cargo");
+    std::fs::create_dir_all(&config).unwrap();
+    std::fs::write(config.join("config.toml"), "[build]\n").unwrap();
+    let before = key(kernel.path());
+
+    std::fs::write(
+        config.join("config.toml"),
+        "[build]\nrustflags = [\"-Copt-level=1\"]\n",
+    )
+    .unwrap();
+    assert_ne!(key(kernel.path()), before);
+
+    // Config in a parent directory applies as well
+    let nested = kernel.path().join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    let before = key(&nested);
+    std::fs::write(config.join("config.toml"), "[build]\n").unwrap();
+    assert_ne!(key(&nested), before);
+}
+
+#[test]
+fn prune_drops_stale_entries() {
+    let kernel = kernel_dir();
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = BuildCache::new(cache_dir.path());
+
+    let result = artifact(kernel.path(), "elf");
+    cache.insert("stale", "stale", &result).unwrap();
+    cache.insert("fresh", "fresh", &result).unwrap();
+
+    let info = cache_dir.path().join("stale").join("info");
+    std::fs::File::options()
+        .write(true)
+        .open(info)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+        .unwrap();
+
+    assert_eq!(cache.prune(Duration::from_secs(60 * 60)), 1);
+    assert!(cache.get("stale").is_none());
+    assert!(cache.get("fresh").is_some());
+}
@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests, not every test uses all of them.
+#![allow(dead_code)]
+
+use ttx_rs::{
+    chip::noc::Tile,
+    kernel::{KernelBytes, KernelData, Symbol},
+};
+
+/// The NOC grid the tiles below are placed on, a wormhole's
+pub const GRID: (u8, u8) = (10, 12);
+
+/// The tensix at `(x, y)` on NOC0.
+pub fn tile(x: u8, y: u8) -> Tile {
+    Tile::new((x, y), (GRID.0 - x - 1, GRID.1 - y - 1), 16, 16)
+}
+
+/// The DRAM tile at `(x, y)` on NOC0.
+pub fn dram_tile(x: u8, y: u8) -> Tile {
+    Tile::new((x, y), (GRID.0 - x - 1, GRID.1 - y - 1), 32, 16)
+}
+
+/// A kernel made of `writes` with the `(name, addr, size)` symbols.
+pub fn kernel(symbols: &[(&str, u64, u64)], writes: Vec<KernelBytes>) -> KernelData {
+    let symbols = symbols
+        .iter()
+        .map(|(name, addr, size)| (name.to_string(), Symbol::new(*addr, *size)))
+        .collect();
+
+    KernelData::new(writes, symbols)
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use ttx_rs::{
+    kernel::{KernelBytes, KernelData, RiscId, Symbol, SymbolError},
+    program::{args_symbol, CoreProgram, CoreSet, Program, ProgramError, RUNTIME_ARGS},
+};
+
+use common::tile;
+
+fn program(base: u32, symbols: &[(&str, u64, u64)], entry: u64) -> KernelData {
+    KernelData {
+        entry,
+        ..common::kernel(
+            symbols,
+            vec![KernelBytes::new(base, vec![0x13; 0x100], true, 0)],
+        )
+    }
+}
+
+fn brisc() -> KernelData {
+    program(
+        0,
+        &[
+            ("START_SYNC", 0x80, 0),
+            ("STATE_BRISC", 0x90, 0),
+            ("SHARED", 0xa0, 0),
+        ],
+        0,
+    )
+}
+
+fn ncrisc() -> KernelData {
+    program(
+        0x1000,
+        &[
+            ("__ncrisc_start", 0x1000, 0),
+            ("STATE_NCRISC", 0x1090, 0),
+            ("SHARED", 0x10a0, 0),
+        ],
+        0x1000,
+    )
+}
+
+#[test]
+fn entries() {
+    let trisc = program(0x2000, &[], 0x2004);
+    let program = CoreProgram::new()
+        .brisc(brisc())
+        .ncrisc(ncrisc())
+        .trisc1(trisc);
+
+    assert_eq!(
+        program.riscs(),
+        vec![RiscId::Brisc, RiscId::Ncrisc, RiscId::Trisc1]
+    );
+    assert_eq!(program.entry(RiscId::Brisc), Some(0));
+    assert_eq!(program.entry(RiscId::Ncrisc), Some(0x1000));
+    assert_eq!(program.entry(RiscId::Trisc0), None);
+    // Without a start symbol the elf entry is used
+    assert_eq!(program.entry(RiscId::Trisc1), Some(0x2004));
+}
+
+#[test]
+fn overlap_detected() {
+    let late = program(0x80, &[], 0x80);
+    match CoreProgram::new().brisc(brisc()).ncrisc(late).check() {
+        Err(ProgramError::Overlap { a, b, start, end }) => {
+            assert_eq!((a, b), (RiscId::Brisc, RiscId::Ncrisc));
+            assert_eq!((start, end), (0x80, 0x100));
+        }
+        other => panic!("expected overlap, got {other:?}"),
+    }
+
+    // The .bss after BRISC's data runs into NCRISC's program
+    let bss = common::kernel(
+        &[],
+        vec![KernelBytes::new(0, vec![0x13; 0x100], true, 0x40)],
+    );
+    let next = program(0x120, &[], 0x120);
+    match CoreProgram::new().brisc(bss).ncrisc(next).check() {
+        Err(ProgramError::Overlap { start, end, .. }) => assert_eq!((start, end), (0x120, 0x140)),
+        other => panic!("expected a .bss overlap, got {other:?}"),
+    }
+
+    assert!(matches!(
+        CoreProgram::new().check(),
+        Err(ProgramError::Empty)
+    ));
+    assert!(matches!(
+        CoreProgram::new().brisc(ncrisc()).check(),
+        Err(ProgramError::BriscEntry(0x1000))
+    ));
+}
+
+#[test]
+fn merged() {
+    let merged = CoreProgram::new()
+        .brisc(brisc())
+        .ncrisc(ncrisc())
+        .merged()
+        .unwrap();
+
+    assert_eq!(merged.writes.len(), 2);
+    assert_eq!(merged.bin.start_sync, Some(0x80));
+    assert_eq!(merged.bin.risc(RiscId::Brisc).state, Some(0x90));
+    assert_eq!(merged.bin.risc(RiscId::Ncrisc).state, Some(0x1090));
+    assert_eq!(merged.bin.risc(RiscId::Trisc0).state, None);
+
+    // Shared names resolve to BRISC, per RISC lookups to the owning program
+    assert_eq!(merged["SHARED"], 0xa0);
+    assert_eq!(merged.for_risc(Some(RiscId::Ncrisc))["SHARED"], 0x10a0);
+    assert_eq!(merged.for_risc(Some(RiscId::Trisc0))["SHARED"], 0xa0);
+}
+
+#[test]
+fn multicast_rect() {
+    // A 4x2 grid with a missing column at x = 3
+    let tensix = [1, 2, 4, 5]
+        .into_iter()
+        .flat_map(|x| [1, 2].map(|y| tile(x, y)))
+        .collect::<Vec<_>>();
+
+    let block = CoreSet::new([tile(1, 1), tile(2, 1), tile(1, 2), tile(2, 2)]);
+    assert_eq!(block.multicast_rect(&tensix), Some(((1, 1), (2, 2))));
+
+    let wide = CoreSet::new([4, 5].into_iter().flat_map(|x| [1, 2].map(|y| tile(x, y))));
+    assert_eq!(wide.multicast_rect(&tensix), Some(((4, 1), (5, 2))));
+
+    // The gap doesn't hold a tensix, a multicast would write to whatever is there
+    let across = CoreSet::new([tile(2, 1), tile(4, 1)]);
+    assert_eq!(across.multicast_rect(&tensix), None);
+    let across = CoreSet::new([tile(2, 1), tile(3, 1), tile(4, 1)]);
+    assert_eq!(across.multicast_rect(&tensix), None);
+
+    let diagonal = CoreSet::new([tile(1, 1), tile(2, 2)]);
+    assert_eq!(diagonal.multicast_rect(&tensix), None);
+    assert_eq!(CoreSet::from(tile(1, 1)).multicast_rect(&tensix), None);
+}
+
+#[test]
+fn program_groups() {
+    let program = Program::new()
+        .kernel(vec![tile(1, 1), tile(2, 1)], brisc())
+        .kernel(tile(4, 1), brisc());
+    assert!(program.check().is_ok());
+
+    let program = program.kernel(vec![tile(5, 1), tile(2, 1)], brisc());
+    match program.check() {
+        Err(ProgramError::TileConflict { tile: t, a, b }) => {
+            assert_eq!(t, tile(2, 1));
+            assert_eq!((a, b), (0, 2));
+        }
+        other => panic!("expected conflict, got {other:?}"),
+    }
+
+    assert!(matches!(
+        Program::new().kernel(Vec::new(), brisc()).check(),
+        Err(ProgramError::EmptyGroup(0))
+    ));
+    assert!(matches!(
+        Program::new().kernel(tile(1, 1), ncrisc()).check(),
+        Err(ProgramError::BriscEntry(0x1000))
+    ));
+}
+
+#[test]
+fn runtime_args_size() {
+    let mut data = brisc();
+    data.symbols.insert(
+        RUNTIME_ARGS.to_string(),
+        Symbol {
+            addr: 0x200,
+            size: 16,
+            section: Some(".bss".to_string()),
+        },
+    );
+
+    assert_eq!(args_symbol(&data, RUNTIME_ARGS, 12).unwrap().addr, 0x200);
+    assert_eq!(args_symbol(&data, RUNTIME_ARGS, 16).unwrap().addr, 0x200);
+    assert!(matches!(
+        args_symbol(&data, RUNTIME_ARGS, 20),
+        Err(SymbolError::SizeMismatch {
+            size: 16,
+            expected: 20,
+            ..
+        })
+    ));
+    assert!(matches!(
+        args_symbol(&brisc(), RUNTIME_ARGS, 4),
+        Err(SymbolError::NotFound(_))
+    ));
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::collections::HashMap;
+
+use ttx_rs::{
+    allocator::{AllocError, DramAllocator},
+    chip::noc::{NocAddress, Tile},
+};
+
+use common::dram_tile;
+
+fn channels(count: u8) -> Vec<Tile> {
+    (0..count).map(|v| dram_tile(0, v)).collect()
+}
+
+#[test]
+fn interleaved_pages() {
+    let mut dram = DramAllocator::new(channels(3), 0x10_0000);
+    // Channel 1 has something in the way, the buffer has to go above it on every channel
+    dram.alloc(1, "other", 0x1000, 0).unwrap();
+
+    let buffer = dram.interleaved("buffer", 7 * 1024 + 100, 1024).unwrap();
+    assert_eq!(buffer.pages(), 8);
+    assert_eq!(buffer.addr, 0x1000);
+    assert_eq!(buffer.args(), [0x1000, 1024, 3]);
+    for channel in 0..3 {
+        assert_eq!(dram.get(channel, "buffer"), Some(0x1000));
+    }
+
+    let (tile, addr) = buffer.page_address(4);
+    assert_eq!(tile, channels(3)[1]);
+    assert_eq!(addr, 0x1000 + 1024);
+    assert_eq!(buffer.page_address(8 - 1).0, channels(3)[1]);
+
+    // Round trip through fake channels
+    let data = (0..buffer.size).map(|v| v as u8).collect::<Vec<_>>();
+    let mut memory = HashMap::<(NocAddress, u64), Vec<u8>>::new();
+    buffer.scatter(&data, &mut |tile, addr, page| {
+        memory.insert((tile.addr, addr), page.to_vec());
+    });
+    assert_eq!(memory.len(), 8);
+    assert_eq!(memory[&(channels(3)[2].addr, 0x1000)], data[2048..3072]);
+
+    let read = buffer.gather(&mut |tile, addr, page| {
+        page.copy_from_slice(&memory[&(tile.addr, addr)]);
+    });
+    assert_eq!(read, data);
+
+    assert!(matches!(
+        dram.interleaved("buffer", 1024, 1024),
+        Err(AllocError::Exists(_))
+    ));
+    assert!(matches!(
+        dram.interleaved("odd", 1024, 100),
+        Err(AllocError::PageSize(100))
+    ));
+
+    dram.free("buffer").unwrap();
+    assert_eq!(dram.get(0, "buffer"), None);
+    assert_eq!(dram.interleaved("next", 1024, 1024).unwrap().addr, 0x1000);
+    assert!(matches!(dram.free("buffer"), Err(AllocError::NotFound(_))));
+}
+
+#[test]
+fn interleaved_out_of_memory() {
+    let mut dram = DramAllocator::new(channels(2), 0x3000);
+    dram.reserve(0, "firmware", 0, 0x1000).unwrap();
+
+    // 4 pages per channel only fit above the reserved region on both
+    assert_eq!(
+        dram.interleaved("a", 8 * 0x400, 0x400).unwrap().addr,
+        0x1000
+    );
+    assert!(matches!(
+        dram.interleaved("b", 12 * 0x400, 0x400),
+        Err(AllocError::OutOfMemory { .. })
+    ));
+    assert_eq!(dram.alloc(1, "small", 0x100, 0).unwrap(), 0);
+
+    let mut none = DramAllocator::new(Vec::new(), 0x3000);
+    assert!(matches!(
+        none.interleaved("a", 0x400, 0x400),
+        Err(AllocError::NoChannels(name)) if name == "a"
+    ));
+}
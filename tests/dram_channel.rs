@@ -0,0 +1,73 @@
+mod common;
+
+use ttx_rs::chip::{
+    dram::{hops, DramChannel, PortTable},
+    noc::NocId,
+};
+
+use common::{tile, GRID};
+
+/// Wormhole's grid, NoC 1 coordinates are flipped
+
+fn channel0(grid: Option<(u8, u8)>) -> DramChannel {
+    DramChannel {
+        index: 0,
+        ports: vec![tile(0, 0), tile(0, 1), tile(0, 11)],
+        grid,
+    }
+}
+
+#[test]
+fn hop_distance() {
+    // NoC 0 only goes east and south, so going one tile west takes the long way around
+    assert_eq!(hops(GRID, NocId::Noc0, tile(1, 1), tile(2, 1)), 1);
+    assert_eq!(hops(GRID, NocId::Noc0, tile(2, 1), tile(1, 1)), 9);
+    assert_eq!(hops(GRID, NocId::Noc1, tile(2, 1), tile(1, 1)), 1);
+    assert_eq!(hops(GRID, NocId::Noc1, tile(1, 1), tile(1, 3)), 10);
+}
+
+#[test]
+fn nearest_port() {
+    let channel = channel0(Some(GRID));
+
+    assert_eq!(
+        channel.distances(NocId::Noc0, tile(1, 1)),
+        Some(vec![20, 9, 19])
+    );
+    assert_eq!(channel.nearest_port(NocId::Noc0, tile(1, 1)), 1);
+    assert_eq!(channel.nearest_port(NocId::Noc1, tile(1, 1)), 1);
+
+    // The best port depends on the NoC
+    assert_eq!(channel.nearest(NocId::Noc0, tile(1, 10)), tile(0, 11));
+    assert_eq!(channel.nearest(NocId::Noc1, tile(1, 10)), tile(0, 1));
+
+    // Without a grid the first port is always used
+    let translated = channel0(None);
+    assert_eq!(translated.distances(NocId::Noc0, tile(1, 10)), None);
+    assert_eq!(translated.nearest_port(NocId::Noc0, tile(1, 10)), 0);
+}
+
+#[test]
+fn port_table() {
+    let channels = [
+        channel0(Some(GRID)),
+        DramChannel {
+            index: 1,
+            ports: vec![tile(5, 0), tile(5, 1), tile(5, 11)],
+            grid: Some(GRID),
+        },
+    ];
+    let cores = [tile(1, 1), tile(1, 10)];
+    let table = PortTable::new(&channels, NocId::Noc0, &cores);
+
+    assert_eq!(table.ports(tile(1, 1)), Some(&[tile(0, 1), tile(5, 1)][..]));
+    assert_eq!(
+        table.ports(tile(1, 10)),
+        Some(&[tile(0, 11), tile(5, 11)][..])
+    );
+    assert_eq!(table.ports(tile(2, 2)), None);
+
+    let args = table.args(tile(1, 10)).unwrap();
+    assert_eq!(args[0], Into::<u32>::into(tile(0, 11).addr));
+    assert_eq!(args[0] & 0xffff, 11 << 8);
+}
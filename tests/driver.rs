@@ -0,0 +1,126 @@
+#![cfg(feature = "async")]
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use ttx_rs::driver::{AsyncWait, Driver};
+
+#[derive(Default)]
+struct CountWaker(AtomicUsize);
+
+impl Wake for CountWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn poll<T>(wait: &mut AsyncWait<T>, waker: &Arc<CountWaker>) -> Poll<T> {
+    let waker = Waker::from(waker.clone());
+    Pin::new(wait).poll(&mut Context::from_waker(&waker))
+}
+
+#[test]
+fn completes_after_polls() {
+    let driver = Driver::new();
+    let mut polls = 0;
+    let mut wait = driver.wait_with(
+        move || {
+            polls += 1;
+            (polls == 3).then_some(polls)
+        },
+        || panic!("cancelled a finished wait"),
+    );
+
+    let waker = Arc::new(CountWaker::default());
+    assert_eq!(poll(&mut wait, &waker), Poll::Pending);
+
+    assert_eq!(driver.poll(), 1);
+    assert_eq!(driver.poll(), 1);
+    assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+
+    assert_eq!(driver.poll(), 0);
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    assert_eq!(poll(&mut wait, &waker), Poll::Ready(3));
+    drop(wait);
+
+    // Nothing left to poll or cancel
+    assert_eq!(driver.poll(), 0);
+}
+
+#[test]
+fn drop_cancels() {
+    let driver = Driver::new();
+    let (cancelled, rx) = mpsc::channel();
+    let wait = driver.wait_with(|| None::<()>, move || cancelled.send(()).unwrap());
+    let other = driver.wait_with(|| None::<()>, || ());
+
+    assert_eq!(driver.poll(), 2);
+    assert!(rx.try_recv().is_err());
+
+    wait.cancel();
+    assert_eq!(driver.poll(), 1);
+    rx.try_recv().unwrap();
+
+    drop(other);
+    assert_eq!(driver.poll(), 0);
+
+    // A finished wait isn't cancelled by dropping it
+    let (cancelled, rx) = mpsc::channel();
+    let done = driver.wait_with(|| Some(1), move || cancelled.send(()).unwrap());
+    assert_eq!(driver.poll(), 0);
+    drop(done);
+    assert!(rx.try_recv().is_err());
+    assert_eq!(driver.poll(), 0);
+}
+
+#[test]
+fn spawned_driver_shuts_down() {
+    let driver = Driver::spawn(Duration::from_millis(1));
+    let (done, rx) = mpsc::channel();
+    let mut polls = 0;
+    let mut wait = driver.wait_with(
+        move || {
+            polls += 1;
+            (polls == 5).then_some(polls)
+        },
+        || (),
+    );
+    // The wait keeps the driver polling once the handle is gone
+    drop(driver);
+
+    let waker = Arc::new(CountWaker::default());
+    while poll(&mut wait, &waker).is_pending() {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // A dropped wait is cancelled by the thread and its task is released
+    let driver = Driver::spawn(Duration::from_millis(1));
+    let held = Arc::new(());
+    let task = held.clone();
+    let pending = driver.wait_with(
+        move || {
+            let _ = &task;
+            None::<()>
+        },
+        move || done.send(()).unwrap(),
+    );
+    drop(driver);
+    drop(pending);
+
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    for _ in 0..500 {
+        if Arc::strong_count(&held) == 1 {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the cancelled task was never dropped");
+}
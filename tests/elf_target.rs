@@ -0,0 +1,96 @@
+use goblin::elf::{header, Elf};
+use tensix_builder::TARGET_MARKER_PREFIX;
+use ttx_rs::{
+    loader::{check_elf_target, ElfTargetError},
+    Arch,
+};
+
+/// A bare rv32 elf header without sections, so without a target marker.
+fn elf_header(machine: u16, flags: u32) -> Vec<u8> {
+    let mut elf = vec![0x7f, b'E', b'L', b'F', 1, 1, 1];
+    elf.resize(16, 0);
+    elf.extend(2u16.to_le_bytes());
+    elf.extend(machine.to_le_bytes());
+    elf.extend(1u32.to_le_bytes());
+    // entry, phoff, shoff
+    elf.extend([0; 12]);
+    elf.extend(flags.to_le_bytes());
+    for value in [52u16, 32, 0, 40, 0, 0] {
+        elf.extend(value.to_le_bytes());
+    }
+    elf
+}
+
+/// An rv32 elf whose symbol table holds the target marker tensix-builder defines for `env`.
+fn elf_with_marker(env: &str) -> Vec<u8> {
+    let mut elf = elf_header(header::EM_RISCV, 0);
+
+    let strtab = format!("\0{TARGET_MARKER_PREFIX}{env}\0").into_bytes();
+    let mut symtab = vec![0; 16];
+    // name, value, size, info, other, shndx
+    symtab.extend(1u32.to_le_bytes());
+    symtab.extend([0; 8]);
+    symtab.extend([0x10, 0]);
+    symtab.extend(0xfff1u16.to_le_bytes());
+
+    let symtab_offset = elf.len() as u32;
+    elf.extend(&symtab);
+    let strtab_offset = elf.len() as u32;
+    elf.extend(&strtab);
+
+    let shoff = elf.len() as u32;
+    elf[32..36].copy_from_slice(&shoff.to_le_bytes());
+    elf[48..50].copy_from_slice(&3u16.to_le_bytes());
+    // null, .symtab linked to .strtab, .strtab
+    elf.extend([0; 40]);
+    let sections = [
+        [0, 2, 0, 0, symtab_offset, symtab.len() as u32, 2, 1, 4, 16],
+        [0, 3, 0, 0, strtab_offset, strtab.len() as u32, 0, 0, 1, 0],
+    ];
+    for section in sections {
+        elf.extend(section.iter().flat_map(|v| v.to_le_bytes()));
+    }
+    elf
+}
+
+#[test]
+fn unknown_arch_is_an_error() {
+    let elf = elf_header(header::EM_RISCV, 0);
+    let bin = Elf::parse(&elf).unwrap();
+
+    assert!(check_elf_target(&bin, Arch::Wormhole).is_ok());
+    assert!(matches!(
+        check_elf_target(&bin, Arch::Unknown(7)),
+        Err(ElfTargetError::UnknownArch(_))
+    ));
+}
+
+#[test]
+fn rejects_other_machines_and_hard_float() {
+    let elf = elf_header(header::EM_X86_64, 0);
+    assert!(matches!(
+        check_elf_target(&Elf::parse(&elf).unwrap(), Arch::Wormhole),
+        Err(ElfTargetError::Machine(header::EM_X86_64))
+    ));
+
+    let elf = elf_header(header::EM_RISCV, 0x4);
+    assert!(matches!(
+        check_elf_target(&Elf::parse(&elf).unwrap(), Arch::Wormhole),
+        Err(ElfTargetError::FloatAbi(0x4))
+    ));
+}
+
+#[test]
+fn target_marker() {
+    let elf = elf_with_marker("blackhole");
+    let bin = Elf::parse(&elf).unwrap();
+
+    assert!(check_elf_target(&bin, Arch::Blackhole).is_ok());
+    match check_elf_target(&bin, Arch::Wormhole) {
+        Err(ElfTargetError::Arch { expected, found }) => {
+            assert_eq!(expected, Arch::Wormhole);
+            assert_eq!(found, "blackhole");
+        }
+        other => panic!("expected an arch mismatch, got {other:?}"),
+    }
+}
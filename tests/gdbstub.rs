@@ -0,0 +1,245 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread::JoinHandle,
+};
+
+use ttx_rs::{
+    gdbstub::{
+        packet::{encode, from_hex, to_hex, Incoming, PacketReader, INTERRUPT},
+        DebugTarget, GdbServer,
+    },
+    kernel::RiscId,
+};
+
+const EBREAK: u32 = 0x0010_0073;
+
+/// A tile whose RISCs execute one instruction each time they are polled, doing nothing but moving
+/// the pc until they hit an `ebreak`
+struct SimCore {
+    memory: Vec<u8>,
+    registers: [[u32; 33]; 5],
+    halted: [bool; 5],
+}
+
+impl SimCore {
+    fn new() -> Self {
+        Self {
+            memory: vec![0; 0x1000],
+            registers: [[0; 33]; 5],
+            halted: [false; 5],
+        }
+    }
+
+    fn index(risc: RiscId) -> usize {
+        RiscId::ALL.iter().position(|v| *v == risc).unwrap()
+    }
+
+    fn word(&self, addr: u32) -> u32 {
+        let addr = addr as usize;
+        u32::from_le_bytes(self.memory[addr..addr + 4].try_into().unwrap())
+    }
+}
+
+impl DebugTarget for SimCore {
+    fn riscs(&self) -> Vec<RiscId> {
+        RiscId::ALL.to_vec()
+    }
+
+    fn halt(&mut self, risc: RiscId) {
+        self.halted[Self::index(risc)] = true;
+    }
+
+    fn resume(&mut self, risc: RiscId) {
+        self.halted[Self::index(risc)] = false;
+    }
+
+    fn step(&mut self, risc: RiscId) {
+        self.registers[Self::index(risc)][32] += 4;
+    }
+
+    fn halted(&mut self, risc: RiscId) -> bool {
+        let index = Self::index(risc);
+        if !self.halted[index] {
+            let pc = self.registers[index][32];
+            if self.word(pc) == EBREAK {
+                self.halted[index] = true;
+            } else {
+                // Spin on the last word instead of running off the end
+                self.registers[index][32] = (pc + 4).min(self.memory.len() as u32 - 4);
+            }
+        }
+        self.halted[index]
+    }
+
+    fn read_register(&mut self, risc: RiscId, reg: usize) -> u32 {
+        self.registers[Self::index(risc)][reg]
+    }
+
+    fn write_register(&mut self, risc: RiscId, reg: usize, value: u32) {
+        if reg != 0 {
+            self.registers[Self::index(risc)][reg] = value;
+        }
+    }
+
+    fn memory_size(&self) -> u64 {
+        self.memory.len() as u64
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Vec<u8> {
+        self.memory[addr as usize..addr as usize + len].to_vec()
+    }
+
+    fn write_memory(&mut self, addr: u64, data: &[u8]) {
+        self.memory[addr as usize..addr as usize + data.len()].copy_from_slice(data);
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    reader: PacketReader,
+}
+
+impl Client {
+    fn recv(&mut self) -> String {
+        let mut buf = [0; 4096];
+        loop {
+            match self.reader.next() {
+                Some(Incoming::Packet(data)) => {
+                    self.stream.write_all(b"+").unwrap();
+                    return String::from_utf8(data).unwrap();
+                }
+                Some(Incoming::Ack) => continue,
+                Some(other) => panic!("unexpected {other:?}"),
+                None => {}
+            }
+            let len = self.stream.read(&mut buf).unwrap();
+            assert_ne!(len, 0, "server closed the connection");
+            self.reader.push(&buf[..len]);
+        }
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.stream.write_all(&encode(packet.as_bytes())).unwrap();
+        self.recv()
+    }
+}
+
+fn connect(core: SimCore) -> (Client, JoinHandle<SimCore>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut server = GdbServer::new(core).elf("/tmp/kernel.elf");
+        server.serve(&listener).unwrap();
+        server.into_target()
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let client = Client {
+        stream,
+        reader: PacketReader::new(),
+    };
+    (client, server)
+}
+
+#[test]
+fn gdb_session() {
+    let (mut gdb, server) = connect(SimCore::new());
+
+    assert!(gdb.request("qSupported:swbreak+").contains("swbreak+"));
+    assert_eq!(gdb.request("?"), "T05thread:1;");
+    assert_eq!(gdb.request("qfThreadInfo"), "m1,2,3,4,5");
+    assert_eq!(gdb.request("qsThreadInfo"), "l");
+    assert_eq!(
+        gdb.request("qXfer:exec-file:read::0,1000"),
+        "l/tmp/kernel.elf"
+    );
+    assert!(gdb
+        .request("qXfer:features:read:target.xml:0,1000")
+        .contains("riscv:rv32"));
+
+    // Memory
+    assert_eq!(gdb.request("M100,4:deadbeef"), "OK");
+    assert_eq!(gdb.request("m100,4"), "deadbeef");
+
+    // Registers of thread 2, NCRISC
+    assert_eq!(gdb.request("Hg2"), "OK");
+    assert_eq!(gdb.request("P5=78563412"), "OK");
+    assert_eq!(gdb.request("p5"), "78563412");
+    let regs = from_hex(&gdb.request("g")).unwrap();
+    assert_eq!(regs.len(), 33 * 4);
+    assert_eq!(&regs[20..24], &0x1234_5678u32.to_le_bytes());
+
+    // Breakpoint at 0x40, BRISC runs into it first from 0
+    assert_eq!(gdb.request("Hg1"), "OK");
+    assert_eq!(gdb.request("P20=20000000"), "OK");
+    for thread in 2..=5 {
+        assert_eq!(gdb.request(&format!("Hg{thread}")), "OK");
+        assert_eq!(gdb.request("P20=00080000"), "OK");
+    }
+    assert_eq!(gdb.request("Z0,40,4"), "OK");
+    assert_eq!(gdb.request("m40,4"), "00000000");
+    assert_eq!(gdb.request("c"), "T05thread:1;swbreak:;");
+    assert_eq!(gdb.request("qC"), "QC1");
+    assert_eq!(gdb.request("Hg1"), "OK");
+    assert_eq!(gdb.request("p20"), "40000000");
+
+    // Step over the breakpoint
+    assert_eq!(gdb.request("z0,40,4"), "OK");
+    assert_eq!(gdb.request("s"), "T05thread:1;");
+    assert_eq!(gdb.request("p20"), "44000000");
+
+    assert_eq!(gdb.request("D"), "OK");
+    let core = server.join().unwrap();
+    assert!(core.halted.iter().all(|v| !v));
+    assert_eq!(core.word(0x40), 0);
+}
+
+#[test]
+fn gdb_interrupt() {
+    let (mut gdb, server) = connect(SimCore::new());
+
+    gdb.request("QStartNoAckMode");
+    assert_eq!(gdb.request("Z0,80,4"), "OK");
+
+    // The RISCs start past the breakpoint and never reach it
+    for thread in 1..=5 {
+        assert_eq!(gdb.request(&format!("Hg{thread}")), "OK");
+        assert_eq!(gdb.request("P20=00010000"), "OK");
+    }
+    gdb.stream.write_all(&encode(b"vCont;c")).unwrap();
+    gdb.stream.write_all(&[INTERRUPT]).unwrap();
+    assert_eq!(gdb.recv(), "T02thread:5;");
+
+    let pc = from_hex(&gdb.request("p20")).unwrap();
+    assert!(u32::from_le_bytes(pc.try_into().unwrap()) >= 0x100);
+    assert_eq!(gdb.request("m80,4"), to_hex(&[0; 4]));
+
+    assert_eq!(gdb.request("D"), "OK");
+    let core = server.join().unwrap();
+    assert_eq!(core.word(0x80), 0);
+}
+
+#[test]
+fn gdb_bad_memory_packets() {
+    let mut core = SimCore::new();
+    core.memory = vec![0; 0x10000];
+    let (mut gdb, server) = connect(core);
+
+    // Capped to what fits in a packet rather than allocating 4GB
+    assert_eq!(gdb.request("m0,ffffffff").len(), 0x4000);
+    // Past the end of memory or overflowing
+    assert_eq!(gdb.request("mfff0,20"), "E01");
+    assert_eq!(gdb.request("mffffffffffffffff,10"), "E01");
+    assert_eq!(gdb.request("Mfffe,4:00000000"), "E01");
+    assert_eq!(gdb.request("Z0,fffe,4"), "E01");
+    assert_eq!(
+        gdb.request("qXfer:features:read:target.xml:10,ffffffffffffffff"),
+        "E01"
+    );
+    assert_eq!(gdb.request("mfffc,4"), "00000000");
+
+    assert_eq!(gdb.request("D"), "OK");
+    server.join().unwrap();
+}
@@ -9,7 +9,8 @@ use ttx_rs::{
         noc::{NocId, NocInterface, Tile},
         Chip,
     },
-    kernel::{Kernel, KernelData},
+    kernel::{Kernel, KernelData, KernelOutcome, Verify},
+    parallel::ParallelOptions,
 };
 
 #[ctor::ctor]
@@ -128,7 +129,7 @@ fn build_test(chip: &mut Chip, noc_id: NocId, tile: Tile, file: &str, wait: bool
         None,
     );
 
-    chip.load_kernel(kernel_data, noc_id, tile, wait)
+    chip.load_kernel(kernel_data, noc_id, tile, wait).unwrap()
 }
 
 #[allow(unused)]
@@ -148,7 +149,8 @@ fn build_tests(chip: &mut Chip, tiles: Option<Vec<Tile>>, file: &str, wait: bool
         None,
     );
 
-    chip.load_kernels(&mut kernel_data, tiles, wait);
+    chip.load_kernels(&mut kernel_data, tiles, wait, Verify::default())
+        .unwrap();
 
     kernel_data
 }
@@ -230,6 +232,51 @@ fn panic() {
     }
 }
 
+#[test]
+fn parallel_load() {
+    for id in PciDevice::scan() {
+        let mut chip = if let Ok(chip) = chip::open(id) {
+            chip
+        } else {
+            continue;
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        write_cargo_toml(&dir);
+        write_main(
+            &dir.path().join("src").join("main.rs"),
+            core::stringify! {
+                use tensix_std::entry;
+
+                #[entry(brisc)]
+                unsafe fn entry() {
+
+                }
+            },
+        );
+
+        let mut kernel_data = chip::loader::build_kernel(
+            "test",
+            chip.arch(),
+            chip::loader::LoadOptions::new(dir.path()).hide_output(),
+            None,
+        );
+
+        let tiles = chip.tensix_tiles();
+        chip.load_kernels_parallel(&kernel_data, tiles.clone(), &ParallelOptions::new())
+            .unwrap();
+
+        for tile in tiles {
+            let outcome = kernel_data.wait_until(&mut chip, NocId::Noc1, tile, None, None);
+            assert!(
+                matches!(outcome, KernelOutcome::Completed),
+                "{tile:?}: {outcome:?}"
+            );
+        }
+    }
+}
+
 #[test]
 fn noc_test() {
     for id in PciDevice::scan() {
@@ -0,0 +1,174 @@
+use ttx_rs::kernel::{DebugInfo, InspectError, KernelData, PanicReport, RiscId, Value};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum Mode {
+    Idle = 0,
+    Running = 3,
+    Faulted = 0x80,
+}
+
+#[repr(C)]
+struct Config {
+    id: u32,
+    scale: f32,
+    enabled: bool,
+    mode: Mode,
+    offsets: [i16; 3],
+}
+
+#[no_mangle]
+#[used]
+static INSPECT_CONFIG: Config = Config {
+    id: 7,
+    scale: 1.5,
+    enabled: true,
+    mode: Mode::Running,
+    offsets: [-1, 0, 2],
+};
+
+#[repr(C)]
+struct Queue {
+    head: &'static u32,
+    mode: Mode,
+}
+
+static QUEUE_HEAD: u32 = 5;
+
+#[no_mangle]
+#[used]
+static INSPECT_QUEUE: Queue = Queue {
+    head: &QUEUE_HEAD,
+    mode: Mode::Faulted,
+};
+
+fn config_bytes() -> &'static [u8] {
+    unsafe {
+        std::slice::from_raw_parts(
+            (&INSPECT_CONFIG as *const Config).cast::<u8>(),
+            size_of::<Config>(),
+        )
+    }
+}
+
+// The test binary carries its own DWARF, decode one of its globals as if it lived in L1
+#[test]
+fn inspect_global() {
+    let elf = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let debug = DebugInfo::parse(&elf).expect("test binary has no debug info");
+
+    let global = debug.global("INSPECT_CONFIG").unwrap();
+    assert_eq!(global.size, size_of::<Config>() as u64);
+    assert!(global.type_name.ends_with("Config"));
+
+    let value = debug.decode(global.ty, config_bytes(), 0, 0, &mut |_, len| vec![0; len]);
+    let Value::Struct { fields, .. } = &value else {
+        panic!("expected a struct, got {value}");
+    };
+
+    let field = |name: &str| &fields.iter().find(|v| v.0 == name).unwrap().1;
+    assert_eq!(field("id"), &Value::UInt(7));
+    assert_eq!(field("scale"), &Value::Float(1.5));
+    assert_eq!(field("enabled"), &Value::Bool(true));
+    assert!(matches!(field("mode"), Value::Enum { variant: Some(v), .. } if v == "Running"));
+    assert_eq!(
+        field("offsets"),
+        &Value::Array(vec![Value::Int(-1), Value::Int(0), Value::Int(2)])
+    );
+}
+
+#[test]
+fn inspect_pointer() {
+    let elf = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let mut data = KernelData::default();
+    assert!(matches!(
+        data.inspect("INSPECT_QUEUE", 0, &mut |_, len| vec![0; len]),
+        Err(InspectError::NoDebugInfo)
+    ));
+    assert!(data.attach_debug_info(&elf));
+
+    // Lay the queue out in a fake L1 with `head` pointing at 0x100
+    let global = data
+        .debug
+        .as_ref()
+        .unwrap()
+        .global("INSPECT_QUEUE")
+        .unwrap();
+    let (queue_addr, queue_size) = (global.addr, global.size as usize);
+    let mut queue = vec![0; queue_size];
+    queue[..size_of::<usize>()].copy_from_slice(&0x100usize.to_le_bytes());
+    queue[size_of::<usize>()] = Mode::Faulted as u8;
+
+    let mut reads = Vec::new();
+    let inspection = data
+        .inspect("INSPECT_QUEUE", 0x1000, &mut |addr, len| {
+            reads.push((addr, len));
+            match addr {
+                _ if addr == queue_addr => queue.clone(),
+                0x100 => 9u32.to_le_bytes()[..len].to_vec(),
+                _ => vec![0; len],
+            }
+        })
+        .unwrap();
+    assert_eq!(reads, [(queue_addr, queue_size), (0x100, 4)]);
+
+    let Value::Struct { fields, .. } = &inspection.value else {
+        panic!("expected a struct, got {}", inspection.value);
+    };
+    let field = |name: &str| &fields.iter().find(|v| v.0 == name).unwrap().1;
+    assert!(matches!(
+        field("head"),
+        Value::Pointer { addr: 0x100, target: Some(target), .. } if **target == Value::UInt(9)
+    ));
+    // Unsigned enum values with the top bit set still match their variant
+    assert!(matches!(
+        field("mode"),
+        Value::Enum { variant: Some(v), value: 0x80, .. } if v == "Faulted"
+    ));
+
+    assert!(matches!(
+        data.inspect("MISSING", 0x1000, &mut |_, len| vec![0; len]),
+        Err(InspectError::NotFound(_))
+    ));
+}
+
+#[no_mangle]
+#[inline(never)]
+extern "C" fn panic_target(value: u32) -> u32 {
+    std::hint::black_box(value) * 3
+}
+
+#[test]
+fn symbolize_panic() {
+    std::hint::black_box(panic_target(1));
+
+    let elf = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let bin = goblin::elf::Elf::parse(&elf).unwrap();
+    let pc = bin
+        .syms
+        .iter()
+        .find(|sym| bin.strtab.get_at(sym.st_name) == Some("panic_target"))
+        .unwrap()
+        .st_value;
+
+    let mut data = KernelData::default();
+    assert!(data.attach_debug_info(&elf));
+
+    let mut report = PanicReport {
+        risc: Some(RiscId::Brisc),
+        file: "src/main.rs".to_string(),
+        line: 1,
+        message: "test".to_string(),
+        pc: pc as u32,
+        sp: 0,
+        frames: Vec::new(),
+    };
+    // No L1 to read the stack from, only the pc is symbolized
+    report.symbolize(&data, 0, &mut |_, len| vec![0; len]);
+
+    let frame = report.frames.last().unwrap();
+    assert_eq!(frame.function.as_deref(), Some("panic_target"));
+    assert!(frame.file.as_deref().unwrap().ends_with("kernel_debug.rs"));
+    assert!(report.to_string().contains("panic_target"));
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::collections::HashMap;
+
+use luwen::luwen_core::Arch;
+use ttx_rs::kernel::{
+    diagnose::{DebugRegisters, DiagnoseError, TileRegisters},
+    Diagnosis, KernelData, RiscId, RiscState,
+};
+
+const REGS: DebugRegisters = DebugRegisters::WORMHOLE;
+
+/// A tile whose RISCs step through a list of pcs each time they are sampled
+struct FakeTile {
+    words: HashMap<u64, u32>,
+    pcs: HashMap<u32, Vec<u32>>,
+    signal: u32,
+    /// Debug registers of the halted RISCs
+    halted: Vec<u32>,
+    debug_reg: u32,
+}
+
+impl TileRegisters for FakeTile {
+    fn read32(&mut self, addr: u64) -> u32 {
+        if addr == REGS.dbg_rd_data {
+            let pcs = self.pcs.get_mut(&(self.signal & 0xffff)).unwrap();
+            let pc = pcs[0];
+            pcs.rotate_left(1);
+            return pc;
+        }
+        if addr == REGS.risc_dbg_status1 {
+            return self.halted.contains(&self.debug_reg) as u32;
+        }
+        self.words.get(&addr).copied().unwrap_or(0)
+    }
+
+    fn write32(&mut self, addr: u64, value: u32) {
+        if addr == REGS.dbg_bus_cntl {
+            if value != 0 {
+                self.signal = value;
+            }
+        } else if addr == REGS.risc_dbg_cntl0 {
+            if value != 0 {
+                self.debug_reg = value & 0xffff;
+            }
+        } else {
+            self.words.insert(addr, value);
+        }
+    }
+}
+
+fn kernel() -> KernelData {
+    common::kernel(
+        &[("STATE_BRISC", 0x10, 4), ("POSTCODE_BRISC", 0x20, 4)],
+        Vec::new(),
+    )
+}
+
+#[test]
+fn diagnose_hang() {
+    let noc = |noc: u64, counter: u64| {
+        REGS.noc_regs + noc * REGS.noc_id_offset + REGS.noc_status_offset + counter * 4
+    };
+    let words = [
+        // Everything but BRISC, NCRISC and TRISC2 held in reset
+        (REGS.soft_reset, 0b011 << 12),
+        (0x10, 1),
+        (0x20, 0xbeef),
+        // NOC0: 3 reads sent, 1 answered
+        (noc(0, 0x5), 3),
+        (noc(0, 0x2), 1),
+        // NOC1: writes all acked
+        (noc(1, 0x8), 7),
+        (noc(1, 0x1), 7),
+    ];
+
+    let mut tile = FakeTile {
+        words: words.into_iter().collect(),
+        // Keyed by debug bus signal, BRISC spins in a small loop while NCRISC moves around
+        pcs: [
+            (19, vec![0x100, 0x104, 0x108]),
+            (27, vec![0x2000, 0x3000]),
+            (21, vec![0]),
+            (23, vec![0]),
+            (25, vec![0x4000]),
+        ]
+        .into_iter()
+        .collect(),
+        signal: 0,
+        // TRISC2 stopped on an ebreak
+        halted: vec![3 * 8],
+        debug_reg: 0,
+    };
+    let at = common::tile(1, 1);
+
+    let diagnosis = Diagnosis::collect(&mut tile, &REGS, at, &kernel(), 6);
+
+    let brisc = &diagnosis.riscs[0];
+    assert_eq!(brisc.risc, RiscId::Brisc);
+    assert!(!brisc.in_reset);
+    assert!(brisc.spinning());
+    assert_eq!(brisc.pcs.len(), 6);
+    assert_eq!(brisc.state, Some(RiscState::Running(1)));
+    assert_eq!(brisc.postcode, Some(0xbeef));
+
+    let ncrisc = &diagnosis.riscs[1];
+    assert_eq!(ncrisc.risc, RiscId::Ncrisc);
+    assert!(!ncrisc.spinning());
+
+    let trisc0 = &diagnosis.riscs[2];
+    assert!(trisc0.in_reset);
+    assert!(!trisc0.spinning());
+
+    let trisc2 = &diagnosis.riscs[4];
+    assert_eq!(trisc2.risc, RiscId::Trisc2);
+    assert!(trisc2.halted);
+    assert!(!trisc2.spinning());
+    assert!(!diagnosis.riscs.iter().take(4).any(|v| v.halted));
+
+    assert_eq!(diagnosis.nocs[0].reads_outstanding(), 2);
+    assert!(!diagnosis.nocs[0].idle());
+    assert!(diagnosis.nocs[1].idle());
+
+    let report = diagnosis.to_string();
+    assert!(report.contains("spinning at 0x00000108"), "{report}");
+    assert!(
+        report.contains("NOC0: 2 reads, 0 writes, 0 atomics outstanding"),
+        "{report}"
+    );
+    assert!(report.contains("NOC1: idle"), "{report}");
+    assert!(report.contains("TRISC2: halted at 0x00004000"), "{report}");
+}
+
+#[test]
+fn unchecked_arch() {
+    assert_eq!(
+        DebugRegisters::for_arch(Arch::Wormhole).unwrap(),
+        DebugRegisters::WORMHOLE
+    );
+    for arch in [Arch::Grayskull, Arch::Blackhole] {
+        assert!(matches!(
+            DebugRegisters::for_arch(arch),
+            Err(DiagnoseError::UncheckedArch(found)) if found == arch
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use ttx_rs::kernel::{BuildInfo, ImageError, KernelBytes, KernelData, LogFormats, Symbol};
+
+fn test_kernel() -> KernelData {
+    let symbols = [
+        ("START_SYNC", 0x4000),
+        ("STATE_BRISC", 0x4010),
+        ("NOC_BUFFER", 0x4040),
+    ]
+    .into_iter()
+    .map(|(name, addr)| {
+        (
+            name.to_string(),
+            Symbol {
+                addr,
+                size: 4,
+                section: Some(".bss".to_string()),
+            },
+        )
+    })
+    .collect();
+    let writes = vec![
+        KernelBytes::new(0, vec![0x13, 0, 0, 0, 0x6f, 0, 0, 0], false, 0),
+        KernelBytes::new(0x4000, vec![0xca; 0x80], true, 0x40),
+    ];
+
+    KernelData {
+        target: Some("wormhole".to_string()),
+        build: Some(BuildInfo {
+            kernel_name: "test".to_string(),
+            profile: "release".to_string(),
+            lto: false,
+            build_std: false,
+            default_features: true,
+            elf: None,
+        }),
+        log_formats: LogFormats(HashMap::from([(0, "value {=u32:x}".to_string())])),
+        ..KernelData::new(writes, symbols)
+    }
+}
+
+#[test]
+fn image_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kernel.ttx");
+
+    let kernel = test_kernel();
+    kernel.save(&path).unwrap();
+
+    let opened = KernelData::open(&path).unwrap();
+    assert_eq!(opened.symbols, kernel.symbols);
+    assert_eq!(opened.target, kernel.target);
+    assert_eq!(opened.build, kernel.build);
+    assert_eq!(opened.log_formats, kernel.log_formats);
+    assert_eq!(opened.writes.len(), kernel.writes.len());
+    for (a, b) in opened.writes.iter().zip(kernel.writes.iter()) {
+        assert_eq!(a.addr, b.addr);
+        assert_eq!(a.data.0, b.data.0);
+        assert_eq!((a.writable, a.zeroed), (b.writable, b.zeroed));
+    }
+    assert_eq!(opened.bin.start_sync, Some(0x4000));
+    assert_eq!(opened.bin.brisc_state.state, Some(0x4010));
+}
+
+#[test]
+fn image_corruption_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("kernel.ttx");
+
+    test_kernel().save(&path).unwrap();
+
+    let mut image = std::fs::read(&path).unwrap();
+    let last = image.len() - 8;
+    image[last] ^= 0xff;
+    std::fs::write(&path, &image).unwrap();
+
+    assert!(matches!(
+        KernelData::open(&path),
+        Err(ImageError::Checksum { .. })
+    ));
+
+    std::fs::write(&path, b"not an image").unwrap();
+    assert!(matches!(KernelData::open(&path), Err(ImageError::BadMagic)));
+}
@@ -0,0 +1,87 @@
+use ttx_rs::kernel::{
+    log::{drain_buffer, format_record},
+    LogFormats, LogLevel, RiscId, Symbol,
+};
+
+fn record(id: u16, level: u8, args: &[u32]) -> Vec<u8> {
+    let header = id as u32 | (level as u32) << 16 | (args.len() as u32) << 24;
+    std::iter::once(header)
+        .chain(args.iter().copied())
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+#[test]
+fn format_placeholders() {
+    assert_eq!(format_record("plain {{text}}", &[]), "plain {text}");
+    assert_eq!(format_record("{} {:x} {:#x}", &[10, 255, 16]), "10 ff 0x10");
+    assert_eq!(format_record("{=i32}", &[(-5i32) as u32]), "-5");
+    assert_eq!(format_record("{=f32}", &[1.5f32.to_bits()]), "1.5");
+    assert_eq!(format_record("{=bool}", &[1]), "true");
+    assert_eq!(
+        format_record("{=u64:x}", &[0x89abcdef, 0x1234567]),
+        "123456789abcdef"
+    );
+    assert_eq!(format_record("{} {}", &[1]), "1 <missing>");
+}
+
+#[test]
+fn decode_records() {
+    let formats = LogFormats::parse(b"hello\0x = {}, y = {=i32}\0");
+
+    let mut data = record(0, 2, &[]);
+    data.extend(record(6, 3, &[7, (-1i32) as u32]));
+    // Truncated record, dropped
+    data.extend(&record(6, 3, &[1, 2])[..8]);
+
+    let records = formats.decode(RiscId::Ncrisc, &data);
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].message, "hello");
+    assert_eq!(records[0].level, LogLevel::Info);
+    assert_eq!(records[1].message, "x = 7, y = -1");
+    assert_eq!(records[1].level, LogLevel::Warn);
+    assert_eq!(records[1].risc, RiscId::Ncrisc);
+}
+
+#[test]
+fn drain_wrapped_buffer() {
+    // 32 byte ring where both the pointers and the pending data wrap
+    let size = 32u32;
+    let read = u32::MAX - 7;
+    let write = read.wrapping_add(12);
+    let mut l1 = vec![0u8; 16 + size as usize];
+    l1[0..4].copy_from_slice(&write.to_le_bytes());
+    l1[4..8].copy_from_slice(&read.to_le_bytes());
+    l1[8..12].copy_from_slice(&size.to_le_bytes());
+
+    let pending = [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let start = (read % size) as usize;
+    let first = size as usize - start;
+    l1[16 + start..].copy_from_slice(&pending[..first]);
+    l1[16..16 + pending.len() - first].copy_from_slice(&pending[first..]);
+
+    let symbol = Symbol::new(0, l1.len() as u64);
+    let (data, read_ptr) = drain_buffer(&symbol, &mut |addr, len| {
+        l1[addr as usize..][..len].to_vec()
+    });
+    assert_eq!(data, pending);
+    assert_eq!(read_ptr, Some(write));
+}
+
+#[test]
+fn drain_garbage_header() {
+    // The header claims a bigger ring than the symbol holds
+    let mut l1 = [0u8; 16 + 32];
+    l1[0..4].copy_from_slice(&64u32.to_le_bytes());
+    l1[8..12].copy_from_slice(&(1u32 << 30).to_le_bytes());
+
+    let mut reads = Vec::new();
+    let symbol = Symbol::new(0, l1.len() as u64);
+    let (data, read_ptr) = drain_buffer(&symbol, &mut |addr, len| {
+        reads.push((addr, len));
+        l1[addr as usize..][..len].to_vec()
+    });
+    assert!(data.is_empty());
+    assert_eq!(read_ptr, None);
+    assert_eq!(reads, [(0, 12)]);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use serde_json::Value;
+use ttx_rs::{
+    kernel::{
+        profile::{read_buffer, MARKER_SIZE},
+        LogFormats, MarkerKind, ProfileBuffer, RiscId, Symbol,
+    },
+    profile::{ProfileError, Profiler, TileProfile},
+};
+
+use common::tile;
+
+fn marker(id: u16, kind: u8, cycles: u64) -> Vec<u8> {
+    [
+        id as u32 | (kind as u32) << 16,
+        cycles as u32,
+        (cycles >> 32) as u32,
+    ]
+    .into_iter()
+    .flat_map(|v| v.to_le_bytes())
+    .collect()
+}
+
+/// A profile buffer holding `markers` with room for `size`, `count` markers having been recorded
+fn buffer(count: u32, size: u32, markers: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = [count, size, 0, 0]
+        .into_iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+    data.extend(markers.concat());
+    data.resize(16 + size as usize * MARKER_SIZE, 0);
+    data
+}
+
+#[test]
+fn read_markers() {
+    let names = LogFormats::parse(b"compute\0tick\0");
+    let memory = buffer(
+        5,
+        3,
+        &[
+            marker(0, 0, 0x1_0000_0010),
+            marker(8, 2, 0x1_0000_0020),
+            marker(0, 1, 0x1_0000_0030),
+        ],
+    );
+    let mut read = |addr: u64, len: usize| memory[(addr - 0x100) as usize..][..len].to_vec();
+
+    let symbol = Symbol::new(0x100, memory.len() as u64);
+    let buffer = read_buffer(RiscId::Trisc1, &names, &symbol, &mut read);
+    assert_eq!(buffer.dropped, 2);
+    assert_eq!(buffer.markers.len(), 3);
+    assert_eq!(buffer.markers[0].name, "compute");
+    assert_eq!(buffer.markers[0].kind, MarkerKind::Begin);
+    assert_eq!(buffer.markers[0].cycles, 0x1_0000_0010);
+    assert_eq!(buffer.markers[1].name, "tick");
+    assert_eq!(buffer.markers[1].kind, MarkerKind::Instant);
+    assert_eq!(buffer.markers[2].kind, MarkerKind::End);
+    assert_eq!(buffer.markers[2].risc, RiscId::Trisc1);
+
+    let empty = self::buffer(0, 3, &[]);
+    let mut read = |addr: u64, len: usize| empty[addr as usize..][..len].to_vec();
+    let buffer = read_buffer(RiscId::Brisc, &names, &Symbol::new(0, 64), &mut read);
+    assert!(buffer.markers.is_empty());
+    assert_eq!(buffer.dropped, 0);
+
+    // A corrupt header can't make the read run past the symbol
+    let corrupt = self::buffer(1000, 1000, &[marker(8, 2, 1), marker(8, 2, 2)]);
+    let mut read = |addr: u64, len: usize| corrupt[addr as usize..][..len].to_vec();
+    let symbol = Symbol::new(0, 16 + 2 * MARKER_SIZE as u64);
+    let buffer = read_buffer(RiscId::Ncrisc, &names, &symbol, &mut read);
+    assert_eq!(buffer.markers.len(), 2);
+    assert_eq!(buffer.markers[1].cycles, 2);
+    assert_eq!(buffer.dropped, 998);
+}
+
+#[test]
+fn chrome_trace() {
+    let names = LogFormats::parse(b"zone\0");
+    let decode = |risc, markers: &[Vec<u8>], dropped| ProfileBuffer {
+        risc,
+        markers: ttx_rs::kernel::profile::decode(risc, &names, &markers.concat()),
+        dropped,
+    };
+
+    let mut profiler = Profiler::new();
+    profiler.profiles.push(TileProfile {
+        chip: 0,
+        tile: tile(1, 2),
+        buffer: decode(
+            RiscId::Brisc,
+            // Out of order, sorted on export
+            &[marker(0, 1, 3000), marker(0, 0, 1000)],
+            0,
+        ),
+    });
+    profiler.profiles.push(TileProfile {
+        chip: 0,
+        tile: tile(1, 3),
+        buffer: decode(RiscId::Ncrisc, &[marker(0, 2, 2000)], 4),
+    });
+    assert!(matches!(
+        profiler.trace(),
+        Err(ProfileError::UnknownChip(0))
+    ));
+
+    profiler.set_aiclk(0, "wormhole[0]", 0);
+    assert!(matches!(profiler.trace(), Err(ProfileError::ZeroAiclk(0))));
+
+    profiler.set_aiclk(0, "wormhole[0]", 1000);
+    let json: Value = serde_json::from_str(&profiler.trace().unwrap().to_json()).unwrap();
+    let events = json["traceEvents"].as_array().unwrap();
+
+    let names = events
+        .iter()
+        .filter(|v| v["ph"] == "M")
+        .map(|v| v["args"]["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["wormhole[0]", "tile 1,2 BRISC", "tile 1,3 NCRISC"]);
+
+    let timeline = events
+        .iter()
+        .filter(|v| v["ph"] != "M")
+        .map(|v| {
+            (
+                v["name"].as_str().unwrap(),
+                v["ph"].as_str().unwrap(),
+                v["ts"].as_f64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        timeline,
+        [
+            ("zone", "B", 0.0),
+            ("zone", "E", 2.0),
+            ("zone", "i", 1.0),
+            ("dropped markers", "i", 1.0),
+        ]
+    );
+
+    // BRISC and NCRISC of different tiles land on different tracks of the same process
+    let tracks = events
+        .iter()
+        .filter(|v| v["ph"] != "M")
+        .map(|v| (v["pid"].as_u64().unwrap(), v["tid"].as_u64().unwrap()))
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(tracks.len(), 2);
+    assert!(tracks.iter().all(|v| v.0 == 0));
+}
@@ -0,0 +1,52 @@
+use ttx_rs::kernel::{CoreStatus, RiscId, RiscState, RiscStatus};
+
+fn status(states: [u32; 5]) -> CoreStatus {
+    CoreStatus {
+        sync: Some(3),
+        riscs: RiscId::ALL
+            .into_iter()
+            .zip(states)
+            .map(|(risc, state)| RiscStatus {
+                risc,
+                state: Some(RiscState::from_raw(state)),
+                postcode: Some(0xc0de),
+                panic: None,
+            })
+            .collect(),
+        unknown_panic: None,
+    }
+}
+
+#[test]
+fn risc_state_decode() {
+    assert_eq!(RiscState::from_raw(0), RiscState::NotStarted);
+    assert_eq!(RiscState::from_raw(1), RiscState::Running(1));
+    assert_eq!(RiscState::from_raw(3), RiscState::Complete(3));
+    assert_eq!(RiscState::from_raw(6), RiscState::Panicked);
+    for raw in 0..8 {
+        assert_eq!(RiscState::from_raw(raw).raw(), raw);
+    }
+}
+
+#[test]
+fn core_status_complete() {
+    assert!(status([3, 0, 0, 0, 0]).all_complete());
+    assert!(status([3, 3, 3, 3, 6]).all_complete());
+    assert!(!status([0, 0, 0, 0, 0]).all_complete());
+    assert!(!status([3, 1, 0, 0, 0]).all_complete());
+
+    assert!(status([6, 0, 0, 0, 0]).any_panicked());
+    assert!(!status([3, 0, 0, 0, 0]).any_panicked());
+}
+
+#[test]
+fn core_status_display() {
+    let status = status([3, 1, 0, 0, 0]);
+    let text = status.to_string();
+    assert!(text.starts_with("SYNC: 3\nBRISC { STATE: complete (3), POSTCODE: c0de }"));
+    assert!(text.contains("NCRISC { STATE: running (1), POSTCODE: c0de }"));
+    assert_eq!(
+        status.risc(RiscId::Trisc1).unwrap().state,
+        Some(RiscState::NotStarted)
+    );
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use ttx_rs::kernel::{KernelData, SymbolError};
+
+fn kernel() -> KernelData {
+    common::kernel(
+        &[
+            ("COUNTER", 0x100, 4),
+            ("TIMESTAMP", 0x108, 8),
+            ("TABLE", 0x200, 24),
+            ("UNALIGNED", 0x302, 4),
+            ("__firmware_end", 0x4000, 0),
+        ],
+        Vec::new(),
+    )
+}
+
+#[test]
+fn addresses_come_from_symbols() {
+    let kernel = kernel();
+    assert_eq!(kernel["TABLE"], 0x200);
+    assert_eq!(kernel.bin.data_start, Some(0x4000));
+}
+
+#[test]
+fn single_values() {
+    let kernel = kernel();
+    assert_eq!(
+        kernel.typed_symbol::<u32>("COUNTER", Some(1)).unwrap().addr,
+        0x100
+    );
+    assert_eq!(
+        kernel
+            .typed_symbol::<u64>("TIMESTAMP", Some(1))
+            .unwrap()
+            .addr,
+        0x108
+    );
+
+    assert!(matches!(
+        kernel.typed_symbol::<u32>("MISSING", Some(1)),
+        Err(SymbolError::NotFound(name)) if name == "MISSING"
+    ));
+    assert!(matches!(
+        kernel.typed_symbol::<u64>("COUNTER", Some(1)),
+        Err(SymbolError::SizeMismatch {
+            size: 4,
+            expected: 8,
+            ..
+        })
+    ));
+    assert!(matches!(
+        kernel.typed_symbol::<u16>("COUNTER", Some(1)),
+        Err(SymbolError::SizeMismatch {
+            size: 4,
+            expected: 2,
+            ..
+        })
+    ));
+    assert!(matches!(
+        kernel.typed_symbol::<u32>("UNALIGNED", Some(1)),
+        Err(SymbolError::Misaligned {
+            addr: 0x302,
+            align: 4,
+            ..
+        })
+    ));
+    // Bytes can be read from anywhere
+    assert!(kernel.typed_symbol::<[u8; 4]>("UNALIGNED", Some(1)).is_ok());
+}
+
+#[test]
+fn slices() {
+    let kernel = kernel();
+    assert_eq!(kernel.typed_symbol::<u32>("TABLE", None).unwrap().size, 24);
+    assert!(kernel.typed_symbol::<u32>("TABLE", Some(6)).is_ok());
+
+    // Writes must cover the whole array
+    assert!(matches!(
+        kernel.typed_symbol::<u32>("TABLE", Some(5)),
+        Err(SymbolError::SizeMismatch {
+            size: 24,
+            expected: 20,
+            ..
+        })
+    ));
+    // Reads must be a whole number of elements
+    assert!(matches!(
+        kernel.typed_symbol::<u64>("COUNTER", None),
+        Err(SymbolError::SizeMismatch { size: 4, .. })
+    ));
+    assert!(matches!(
+        kernel.typed_symbol::<[u32; 5]>("TABLE", None),
+        Err(SymbolError::SizeMismatch { size: 24, .. })
+    ));
+    // Linker defined symbols have no size to read
+    assert!(matches!(
+        kernel.typed_symbol::<u32>("__firmware_end", None),
+        Err(SymbolError::SizeMismatch { size: 0, .. })
+    ));
+    assert!(matches!(
+        kernel.typed_symbol::<()>("TABLE", None),
+        Err(SymbolError::SizeMismatch { .. })
+    ));
+}
@@ -0,0 +1,59 @@
+use ttx_rs::kernel::{
+    verify::{sample_windows, SAMPLE_COUNT, SAMPLE_LEN},
+    KernelBytes, VerifyPolicy,
+};
+
+const POLICIES: [VerifyPolicy; 3] = [
+    VerifyPolicy::Checksum,
+    VerifyPolicy::FullReadback,
+    VerifyPolicy::Sample,
+];
+
+/// A fake L1 holding `memory` at `base`
+fn reader(base: u64, memory: &[u8]) -> impl FnMut(u64, usize) -> Vec<u8> + '_ {
+    move |addr, len| memory[(addr - base) as usize..][..len].to_vec()
+}
+
+#[test]
+fn policies_detect_corruption() {
+    let data = (0..4096u32).map(|v| v as u8).collect::<Vec<_>>();
+    let write = KernelBytes::new(0x1000, data.clone(), false, 0);
+    assert_eq!(write.crc(), crc32fast::hash(&data));
+
+    for policy in POLICIES {
+        assert!(
+            policy.check(&write, &mut reader(0x1000, &data)),
+            "{policy:?}"
+        );
+    }
+
+    // The first and last bytes are always covered by a sample
+    for index in [0, data.len() - 1] {
+        let mut corrupt = data.clone();
+        corrupt[index] ^= 0xff;
+        for policy in POLICIES {
+            assert!(
+                !policy.check(&write, &mut reader(0x1000, &corrupt)),
+                "{policy:?} missed byte {index}"
+            );
+        }
+        assert!(VerifyPolicy::None.check(&write, &mut reader(0x1000, &corrupt)));
+    }
+}
+
+#[test]
+fn sample_layout() {
+    assert_eq!(sample_windows(100), vec![(0, 100)]);
+
+    let len = 10_000;
+    let windows = sample_windows(len);
+    assert_eq!(windows.len(), SAMPLE_COUNT);
+    assert_eq!(windows[0], (0, SAMPLE_LEN));
+
+    let (offset, size) = windows[SAMPLE_COUNT - 1];
+    assert_eq!(offset + size, len);
+    for (offset, size) in windows {
+        assert_eq!(offset % 16, 0);
+        assert!(offset + size <= len);
+    }
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::time::Instant;
+
+use ttx_rs::kernel::{KernelData, KernelOutcome, RiscId, RiscState, WaitState};
+
+const STATE: u64 = 0x100;
+const POSTCODE: u64 = 0x104;
+const PANIC: u64 = 0x120;
+const MESSAGE: u64 = 0x200;
+
+fn kernel(postcode: bool) -> KernelData {
+    let mut symbols = vec![("STATE_BRISC", STATE, 4), ("PANIC_DATA_BRISC", PANIC, 32)];
+    if postcode {
+        symbols.push(("POSTCODE_BRISC", POSTCODE, 4));
+    }
+    common::kernel(&symbols, Vec::new())
+}
+
+/// L1 of a core with only the BRISC state globals set.
+struct Core(Vec<u8>);
+
+impl Core {
+    fn new(state: u32, postcode: u32) -> Self {
+        let mut core = Self(vec![0; 0x1000]);
+        core.write32(STATE, state);
+        core.write32(POSTCODE, postcode);
+        core
+    }
+
+    fn write32(&mut self, addr: u64, value: u32) {
+        self.0[addr as usize..][..4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn check(&self, data: &KernelData, wait: &mut WaitState) -> Option<KernelOutcome> {
+        data.check_wait(wait, self.0.len() as u64, &mut |addr, len| {
+            self.0[addr as usize..][..len].to_vec()
+        })
+    }
+}
+
+#[test]
+fn completes() {
+    let data = kernel(true);
+    let mut wait = WaitState::new(None, None);
+
+    let mut core = Core::new(1, 0xa);
+    assert_eq!(core.check(&data, &mut wait), None);
+
+    core.write32(STATE, RiscState::COMPLETE);
+    assert_eq!(core.check(&data, &mut wait), Some(KernelOutcome::Completed));
+}
+
+#[test]
+fn times_out() {
+    let data = kernel(true);
+    let core = Core::new(1, 0xa);
+
+    let outcome = core.check(&data, &mut WaitState::new(Some(Instant::now()), None));
+    let Some(KernelOutcome::TimedOut {
+        last_states,
+        last_postcodes,
+    }) = outcome
+    else {
+        panic!("expected a timeout, got {outcome:?}");
+    };
+    assert_eq!(last_states[0], (RiscId::Brisc, Some(RiscState::Running(1))));
+    assert_eq!(last_postcodes[0], (RiscId::Brisc, Some(0xa)));
+    assert_eq!(last_states[1], (RiscId::Ncrisc, None));
+
+    // A finished kernel isn't reported as timed out
+    let core = Core::new(RiscState::COMPLETE, 0xa);
+    let outcome = core.check(&data, &mut WaitState::new(Some(Instant::now()), None));
+    assert_eq!(outcome, Some(KernelOutcome::Completed));
+}
+
+#[test]
+fn hangs_without_progress() {
+    let data = kernel(true);
+    let mut wait = WaitState::new(None, Some(2));
+    let mut core = Core::new(1, 0xa);
+
+    // The first poll records the progress, the next counts as one without change
+    assert_eq!(core.check(&data, &mut wait), None);
+    assert_eq!(core.check(&data, &mut wait), None);
+
+    // Progress resets the count
+    core.write32(POSTCODE, 0xb);
+    assert_eq!(core.check(&data, &mut wait), None);
+    assert_eq!(core.check(&data, &mut wait), None);
+    assert!(matches!(
+        core.check(&data, &mut wait),
+        Some(KernelOutcome::Hung { last_postcodes, .. }) if last_postcodes[0].1 == Some(0xb)
+    ));
+
+    // Without postcodes there is nothing to judge progress by
+    let data = kernel(false);
+    let mut wait = WaitState::new(None, Some(2));
+    for _ in 0..5 {
+        assert_eq!(core.check(&data, &mut wait), None);
+    }
+}
+
+#[test]
+fn panics() {
+    let data = kernel(true);
+    let mut core = Core::new(RiscState::PANICKED, 0xa);
+
+    let message = b"index out of bounds";
+    core.0[MESSAGE as usize..][..message.len()].copy_from_slice(message);
+    // filename, line, message, sp, pc and the panicked flag
+    for (offset, value) in [(0, 0), (4, 0), (8, 42), (12, MESSAGE as u32)] {
+        core.write32(PANIC + offset, value);
+    }
+    core.write32(PANIC + 16, message.len() as u32);
+    core.write32(PANIC + 24, 0x40);
+    core.0[PANIC as usize + 28] = 1;
+
+    let outcome = core.check(&data, &mut WaitState::new(Some(Instant::now()), None));
+    let Some(KernelOutcome::Panicked { risc, report }) = outcome else {
+        panic!("expected a panic, got {outcome:?}");
+    };
+    assert_eq!(risc, Some(RiscId::Brisc));
+    assert_eq!(report.message, "index out of bounds");
+    assert_eq!(report.line, 42);
+    assert_eq!(report.pc, 0x40);
+    assert!(KernelOutcome::Panicked { risc, report }.is_finished());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use ttx_rs::{
+    allocator::{AllocError, BlockKind, L1Allocator},
+    kernel::{KernelBytes, KernelData},
+};
+
+use common::tile;
+
+fn kernel(firmware_end: u64, writes: Vec<KernelBytes>) -> KernelData {
+    common::kernel(&[("__firmware_end", firmware_end, 0)], writes)
+}
+
+#[test]
+fn alloc_around_kernel() {
+    let data = kernel(
+        0x1000,
+        vec![
+            KernelBytes::new(0, vec![0; 0x800], false, 0),
+            // .data/.bss past the firmware
+            KernelBytes::new(0x4000, vec![0; 0x100], true, 0x100),
+        ],
+    );
+
+    let mut l1 = L1Allocator::new(tile(1, 1), 0x10000);
+    l1.set_kernel(&data).unwrap();
+    assert_eq!(
+        l1.blocks()
+            .map(|v| (v.kind, v.addr, v.size))
+            .collect::<Vec<_>>(),
+        [
+            (BlockKind::Kernel, 0, 0x1000),
+            (BlockKind::Kernel, 0x4000, 0x200)
+        ]
+    );
+
+    assert_eq!(l1.alloc("in", 0x1000, 0).unwrap(), 0x1000);
+    assert_eq!(l1.alloc("out", 0x3000, 0x1000).unwrap(), 0x5000);
+    // Fits the gap left below the kernel's data
+    assert_eq!(l1.alloc("scratch", 0x20, 0x100).unwrap(), 0x2000);
+    assert!(matches!(
+        l1.alloc("in", 0x10, 0),
+        Err(AllocError::Exists(_))
+    ));
+    assert!(matches!(
+        l1.alloc("huge", 0x10000, 0),
+        Err(AllocError::OutOfMemory { .. })
+    ));
+
+    assert_eq!(
+        l1.args(["in", "out", "scratch"]).unwrap(),
+        [0x1000, 0x5000, 0x2000]
+    );
+    assert!(matches!(l1.args(["missing"]), Err(AllocError::NotFound(_))));
+
+    l1.free("in").unwrap();
+    assert_eq!(l1.get("in"), None);
+    assert_eq!(l1.alloc("again", 0x800, 0).unwrap(), 0x1000);
+}
+
+#[test]
+fn refuse_clobbering_load() {
+    let mut l1 = L1Allocator::new(tile(1, 1), 0x10000);
+    l1.set_kernel(&kernel(0x1000, Vec::new())).unwrap();
+    let buffer = l1.alloc("buffer", 0x100, 0).unwrap();
+    assert_eq!(buffer, 0x1000);
+
+    // A bigger kernel whose bss runs into the buffer
+    let bigger = kernel(
+        0x1000,
+        vec![KernelBytes::new(0xf00, vec![0; 0x100], true, 0x10)],
+    );
+    let err = l1.set_kernel(&bigger).unwrap_err();
+    assert!(
+        matches!(&err, AllocError::Overlap { name, .. } if name == "buffer"),
+        "{err}"
+    );
+    // The previous kernel is still what is loaded
+    assert_eq!(l1.blocks().next().unwrap().size, 0x1000);
+
+    l1.free("buffer").unwrap();
+    l1.set_kernel(&bigger).unwrap();
+    assert_eq!(l1.alloc("buffer", 0x100, 0).unwrap(), 0x1010);
+
+    l1.reserve("mailbox", 0x2000, 0x100).unwrap();
+    assert!(l1.reserve("overlapping", 0x2080, 0x100).is_err());
+    assert_eq!(l1.alloc("next", 0x1000, 0x1000).unwrap(), 0x3000);
+}
@@ -8,7 +8,7 @@ use ttx_rs::{
         self,
         noc::{NocId, NocInterface, Tile},
     },
-    kernel::{Kernel, KernelData},
+    kernel::{Kernel, KernelData, Verify},
     Chip,
 };
 
@@ -169,7 +169,7 @@ fn build_test(chip: &mut Chip, noc_id: NocId, tile: Tile, wait: bool, file: &str
         None,
     );
 
-    chip.load_kernel(kernel_data, noc_id, tile, wait)
+    chip.load_kernel(kernel_data, noc_id, tile, wait).unwrap()
 }
 
 #[allow(unused)]
@@ -189,7 +189,8 @@ fn build_tests(chip: &mut Chip, tiles: Option<Vec<Tile>>, file: &str, wait: bool
         None,
     );
 
-    chip.load_kernels(&mut kernel_data, tiles, wait);
+    chip.load_kernels(&mut kernel_data, tiles, wait, Verify::default())
+        .unwrap();
 
     kernel_data
 }
@@ -0,0 +1,112 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use ttx_rs::{
+    chip::noc::Tile,
+    kernel::KernelData,
+    parallel::{self, FailureKind, TileJob},
+};
+
+use common::tile;
+
+fn tiles(count: u8) -> Vec<Tile> {
+    (0..count).map(|v| tile(1 + v % 8, 1 + v / 8)).collect()
+}
+
+#[test]
+fn failures_in_job_and_tile_order() {
+    let data = KernelData::new(Vec::new(), HashMap::new());
+    let (a, b) = (tiles(12), tiles(7));
+
+    // Later tiles fail first, the order mustn't depend on which worker finishes first
+    let mut jobs = vec![
+        TileJob {
+            chip: 3,
+            data: &data,
+            tiles: &a,
+            handles: vec![0; 4],
+        },
+        TileJob {
+            chip: 1,
+            data: &data,
+            tiles: &b,
+            handles: vec![0; 2],
+        },
+    ];
+    let errors = parallel::run(&mut jobs, |calls, _, index, _| {
+        *calls += 1;
+        std::thread::sleep(Duration::from_millis(20 - index as u64));
+        if index % 3 == 1 {
+            Err(FailureKind::Dupe(format!("tile {index}")))
+        } else {
+            Ok(())
+        }
+    })
+    .unwrap_err();
+
+    assert_eq!(
+        errors
+            .0
+            .iter()
+            .map(|v| (v.job, v.chip, v.index, v.tile))
+            .collect::<Vec<_>>(),
+        [
+            (0, 3, 1, Some(a[1])),
+            (0, 3, 4, Some(a[4])),
+            (0, 3, 7, Some(a[7])),
+            (0, 3, 10, Some(a[10])),
+            (1, 1, 1, Some(b[1])),
+            (1, 1, 4, Some(b[4])),
+        ]
+    );
+    assert!(errors.to_string().starts_with("6 tile(s) failed to load"));
+
+    // Every tile is still visited by exactly one worker
+    assert_eq!(jobs[0].handles.iter().sum::<usize>(), 12);
+    assert_eq!(jobs[1].handles.iter().sum::<usize>(), 7);
+
+    parallel::run(&mut jobs, |_, _, _, _| Ok(())).unwrap();
+}
+
+#[test]
+fn worker_panic_is_a_failure() {
+    let data = KernelData::new(Vec::new(), HashMap::new());
+    let all = tiles(8);
+
+    let mut jobs = vec![TileJob {
+        chip: 0,
+        data: &data,
+        tiles: &all,
+        handles: vec![(); 2],
+    }];
+    let errors = parallel::run(&mut jobs, |_, _, index, _| {
+        if index == 6 {
+            panic!("tile {index} exploded");
+        }
+        Err(FailureKind::Dupe("no tlb".to_string()))
+    })
+    .unwrap_err();
+
+    let failures = errors
+        .0
+        .iter()
+        .map(|v| (v.index, v.tile, v.error.to_string()))
+        .collect::<Vec<_>>();
+    // The first worker reports each tile, the second only its panic, pinned on its first tile
+    assert_eq!(failures.len(), 5);
+    for (index, failure) in failures[..4].iter().enumerate() {
+        assert_eq!(
+            failure,
+            &(
+                index,
+                Some(all[index]),
+                "failed to open a worker handle: no tlb".to_string()
+            )
+        );
+    }
+    assert_eq!(
+        failures[4],
+        (4, None, "worker panicked: tile 6 exploded".to_string())
+    );
+}
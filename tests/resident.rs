@@ -0,0 +1,195 @@
+mod common;
+
+use std::collections::HashMap;
+
+use ttx_rs::{
+    kernel::{diagnose::TileRegisters, KernelData},
+    resident::{
+        CommandQueue, Completion, RuntimeError, Work, WorkId, COMMAND_SIZE, HEADER_SIZE, KIND_CALL,
+        KIND_OVERLAY, STATUS_DONE, STATUS_FAILED, STATUS_QUEUED,
+    },
+};
+
+const QUEUE: u64 = 0x8000;
+const OVERLAY: u64 = 0x9000;
+
+/// A dispatcher that runs queued commands when told to: a call returns `function * 100` plus the
+/// sum of its args, function 0xdead fails and an overlay returns its length
+struct FakeDispatcher {
+    words: HashMap<u64, u32>,
+    capacity: u32,
+}
+
+impl TileRegisters for FakeDispatcher {
+    fn read32(&mut self, addr: u64) -> u32 {
+        self.words.get(&addr).copied().unwrap_or(0)
+    }
+
+    fn write32(&mut self, addr: u64, value: u32) {
+        self.words.insert(addr, value);
+    }
+}
+
+impl FakeDispatcher {
+    fn run(&mut self, count: usize) {
+        for _ in 0..count {
+            let (write, read) = (self.read32(QUEUE), self.read32(QUEUE + 4));
+            if read == write {
+                return;
+            }
+
+            let slot = QUEUE + HEADER_SIZE + (read % self.capacity) as u64 * COMMAND_SIZE;
+            let word = |dispatcher: &mut Self, index: u64| dispatcher.read32(slot + index * 4);
+            assert_eq!(word(self, 3), STATUS_QUEUED);
+
+            let args = (0..word(self, 7) as u64)
+                .map(|v| word(self, 8 + v))
+                .collect::<Vec<_>>();
+            let (status, result) = match (word(self, 1), word(self, 2)) {
+                (KIND_CALL, 0xdead) => (STATUS_FAILED, 1),
+                (KIND_CALL, function) => (STATUS_DONE, function * 100 + args.iter().sum::<u32>()),
+                (KIND_OVERLAY, _) => {
+                    assert_eq!(word(self, 5) as u64, OVERLAY);
+                    (STATUS_DONE, word(self, 6))
+                }
+                _ => (STATUS_DONE, 0),
+            };
+
+            self.write32(slot + 16, result);
+            self.write32(slot + 12, status);
+            self.write32(QUEUE + 4, read + 1);
+        }
+    }
+}
+
+fn dispatcher(capacity: u64) -> KernelData {
+    common::kernel(
+        &[
+            (
+                "COMMAND_QUEUE",
+                QUEUE,
+                HEADER_SIZE + capacity * COMMAND_SIZE,
+            ),
+            ("OVERLAY", OVERLAY, 0x1000),
+        ],
+        Vec::new(),
+    )
+}
+
+#[test]
+fn submit_and_complete() {
+    let mut core = FakeDispatcher {
+        words: HashMap::new(),
+        capacity: 4,
+    };
+    let mut queue = CommandQueue::new(&mut core, &dispatcher(4)).unwrap();
+    assert_eq!(queue.capacity, 4);
+    assert_eq!(queue.overlay, Some((OVERLAY, 0x1000)));
+
+    let call = |function, args: &'static [u32]| Work::Call { function, args };
+    let a = queue
+        .try_submit(&mut core, &call(1, &[2, 3]))
+        .unwrap()
+        .unwrap();
+    let b = queue
+        .try_submit(&mut core, &call(0xdead, &[]))
+        .unwrap()
+        .unwrap();
+    assert_eq!((a, b), (WorkId(0), WorkId(1)));
+    assert_eq!(core.read32(QUEUE), 2);
+
+    // Nothing has run yet
+    queue.poll(&mut core);
+    assert_eq!(queue.status(a).unwrap(), None);
+
+    core.run(1);
+    queue.poll(&mut core);
+    assert_eq!(
+        queue.status(a).unwrap(),
+        Some(Completion {
+            id: a,
+            ok: true,
+            result: 105
+        })
+    );
+    assert_eq!(queue.status(b).unwrap(), None);
+
+    core.run(1);
+    queue.poll(&mut core);
+    assert!(!queue.take(b).unwrap().ok);
+    assert!(matches!(queue.status(b), Err(RuntimeError::Unknown(_))));
+    assert_eq!(queue.outstanding(), 0);
+
+    assert!(matches!(
+        queue.try_submit(&mut core, &call(1, &[0; 9])),
+        Err(RuntimeError::TooManyArgs(9))
+    ));
+}
+
+#[test]
+fn full_queue_wraps() {
+    let mut core = FakeDispatcher {
+        words: HashMap::new(),
+        capacity: 2,
+    };
+    let mut queue = CommandQueue::new(&mut core, &dispatcher(2)).unwrap();
+
+    let work = |function| Work::Call {
+        function,
+        args: &[],
+    };
+    assert!(queue.try_submit(&mut core, &work(1)).unwrap().is_some());
+    assert!(queue.try_submit(&mut core, &work(2)).unwrap().is_some());
+    // Both slots are taken until the dispatcher gets to them
+    assert_eq!(queue.try_submit(&mut core, &work(3)).unwrap(), None);
+
+    core.run(1);
+    queue.poll(&mut core);
+    let third = queue.try_submit(&mut core, &work(3)).unwrap().unwrap();
+    assert_eq!(third, WorkId(2));
+
+    let overlay = Work::Overlay {
+        addr: OVERLAY,
+        len: 0x40,
+        args: &[],
+    };
+    assert_eq!(queue.try_submit(&mut core, &overlay).unwrap(), None);
+    core.run(1);
+    queue.poll(&mut core);
+    let overlay = queue.try_submit(&mut core, &overlay).unwrap().unwrap();
+    assert!(!queue.overlay_free());
+
+    core.run(2);
+    queue.poll(&mut core);
+    assert_eq!(queue.status(third).unwrap().unwrap().result, 300);
+    assert_eq!(queue.status(overlay).unwrap().unwrap().result, 0x40);
+    assert!(queue.overlay_free());
+    assert_eq!(core.read32(QUEUE + 4), 4);
+
+    // Collecting hands each completion over once, so they don't pile up
+    let last = queue.try_submit(&mut core, &work(4)).unwrap().unwrap();
+    assert_eq!(queue.collect(&mut core, last).unwrap(), None);
+    core.run(1);
+    assert_eq!(queue.collect(&mut core, last).unwrap().unwrap().result, 400);
+    assert!(matches!(
+        queue.collect(&mut core, last),
+        Err(RuntimeError::Unknown(_))
+    ));
+    assert_eq!(
+        queue.collect(&mut core, third).unwrap().unwrap().result,
+        300
+    );
+    assert!(matches!(queue.status(third), Err(RuntimeError::Unknown(_))));
+}
+
+#[test]
+fn queue_must_fit_a_command() {
+    let mut core = FakeDispatcher {
+        words: HashMap::new(),
+        capacity: 0,
+    };
+    assert!(matches!(
+        CommandQueue::new(&mut core, &dispatcher(0)),
+        Err(RuntimeError::QueueTooSmall(16))
+    ));
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::{collections::HashMap, time::Duration};
+
+use serde_json::Value;
+use ttx_rs::{
+    kernel::{KernelBinData, RiscId, RiscState},
+    sampler::History,
+};
+
+use common::tile;
+
+fn history(limit: usize, polls: &[(u32, u32)]) -> History {
+    let symbols = [("STATE_BRISC", 0x10), ("POSTCODE_BRISC", 0x20)]
+        .into_iter()
+        .map(|(name, addr)| (name.to_string(), addr))
+        .collect::<HashMap<_, _>>();
+    let bin = KernelBinData::from_symbols(&symbols);
+
+    let mut history = History::new(0, "wormhole[0]", &[(tile(1, 2), &bin)]);
+    for (index, (state, postcode)) in polls.iter().enumerate() {
+        history.record(
+            Duration::from_micros(index as u64 * 10),
+            limit,
+            &mut |_, addr| if addr == 0x10 { *state } else { *postcode },
+        );
+    }
+
+    history
+}
+
+#[test]
+fn keeps_changes() {
+    let history = history(8, &[(1, 0xa), (1, 0xa), (1, 0xb), (1, 0xb), (3, 0xb)]);
+    assert_eq!(history.riscs.len(), 1);
+
+    let risc = &history.riscs[0];
+    assert_eq!(risc.risc, RiscId::Brisc);
+    assert_eq!(risc.last_poll, Duration::from_micros(40));
+    let samples = risc
+        .samples
+        .iter()
+        .map(|v| (v.time.as_micros(), v.state, v.postcode))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        samples,
+        [
+            (0, Some(RiscState::Running(1)), Some(0xa)),
+            (20, Some(RiscState::Running(1)), Some(0xb)),
+            (40, Some(RiscState::Complete(3)), Some(0xb)),
+        ]
+    );
+
+    assert_eq!(
+        history.to_csv(),
+        "time_us,chip,x,y,risc,state,postcode\n\
+         0,0,1,2,BRISC,1,0xa\n\
+         20,0,1,2,BRISC,1,0xb\n\
+         40,0,1,2,BRISC,3,0xb\n"
+    );
+}
+
+#[test]
+fn bounded_history() {
+    let polls = (0..10).map(|v| (1, v)).collect::<Vec<_>>();
+    let history = history(4, &polls);
+
+    let risc = &history.riscs[0];
+    assert_eq!(risc.dropped, 6);
+    assert_eq!(
+        risc.samples.iter().map(|v| v.postcode).collect::<Vec<_>>(),
+        [Some(6), Some(7), Some(8), Some(9)]
+    );
+}
+
+#[test]
+fn trace_spans() {
+    let history = history(8, &[(1, 0xa), (1, 0xb), (1, 0xb)]);
+    let json: Value = serde_json::from_str(&history.trace().to_json()).unwrap();
+
+    let spans = json["traceEvents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|v| v["ph"] != "M")
+        .map(|v| {
+            (
+                v["name"].as_str().unwrap(),
+                v["ph"].as_str().unwrap(),
+                v["ts"].as_f64().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        [
+            ("0xa", "B", 0.0),
+            ("0xa", "E", 10.0),
+            ("0xb", "B", 10.0),
+            ("0xb", "E", 20.0),
+        ]
+    );
+}
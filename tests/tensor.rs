@@ -0,0 +1,161 @@
+mod common;
+
+use std::collections::HashMap;
+
+use ttx_rs::{
+    allocator::DramAllocator,
+    tensor::{
+        format::{bf16_to_f32, f32_to_bf16, f32_to_fp16, fp16_to_f32, pack, unpack},
+        layout::{tilize, tilized_index, untilize, TILE_VALUES},
+        DataFormat, Tensor,
+    },
+};
+
+use common::dram_tile;
+
+/// Deterministic values spread over a few orders of magnitude
+fn values(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|v| {
+            let v = v as f32;
+            (v * 0.37).sin() * (1.0 + (v * 0.011).cos() * 4.0)
+        })
+        .collect()
+}
+
+#[test]
+fn tilize_layout() {
+    // Face order is top left, top right, bottom left, bottom right
+    assert_eq!(tilized_index(32, 0, 0), 0);
+    assert_eq!(tilized_index(32, 0, 16), 256);
+    assert_eq!(tilized_index(32, 16, 0), 512);
+    assert_eq!(tilized_index(32, 17, 17), 768 + 16 + 1);
+    // Second tile of the first tile row
+    assert_eq!(tilized_index(64, 0, 32), 1024);
+    assert_eq!(tilized_index(64, 32, 0), 2048);
+
+    let (rows, cols) = (40, 70);
+    let data = (0..rows * cols).map(|v| v as u32).collect::<Vec<_>>();
+    let tilized = tilize(&data, rows, cols, u32::MAX);
+    assert_eq!(tilized.len(), 2 * 3 * TILE_VALUES);
+    assert_eq!(tilized[1], 1);
+    assert_eq!(tilized[16], cols as u32);
+    assert_eq!(untilize(&tilized, rows, cols), data);
+    // Padding past the last column
+    assert_eq!(tilized[tilized_index(96, 0, 70)], u32::MAX);
+}
+
+#[test]
+fn float_conversions() {
+    for v in [
+        0.0,
+        -0.0,
+        1.0,
+        -2.5,
+        65504.0,
+        6.1035156e-5,
+        5.9604645e-8,
+        0.333_251_95,
+    ] {
+        assert_eq!(fp16_to_f32(f32_to_fp16(v)), v, "{v}");
+        let bf16 = bf16_to_f32(f32_to_bf16(v));
+        assert!((bf16 - v).abs() <= v.abs() / 256.0, "{v}: {bf16}");
+    }
+    assert_eq!(f32_to_fp16(1.0), 0x3c00);
+    assert_eq!(f32_to_fp16(65520.0), 0x7c00);
+    assert_eq!(f32_to_fp16(1e-9), 0);
+    assert!(fp16_to_f32(f32_to_fp16(f32::NAN)).is_nan());
+    // Ties go to even
+    assert_eq!(f32_to_fp16(1.0 + 2f32.powi(-11)), 0x3c00);
+    assert_eq!(f32_to_fp16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+
+    assert_eq!(f32_to_bf16(1.0), 0x3f80);
+    assert_eq!(f32_to_bf16(1.0 + 2f32.powi(-8)), 0x3f80);
+    assert_eq!(f32_to_bf16(1.0 + 3.0 * 2f32.powi(-8)), 0x3f82);
+    assert!(bf16_to_f32(f32_to_bf16(f32::NAN)).is_nan());
+}
+
+/// Round trip `values` through `format`, every value must be within `tolerance` of the largest
+/// magnitude in its face row.
+fn round_trip(format: DataFormat, tolerance: f32) {
+    let (rows, cols) = (64, 96);
+    let values = values(rows * cols);
+    let tensor = Tensor::from_f32(&values, rows, cols, format);
+    assert_eq!(tensor.tiles(), 6);
+    assert_eq!(tensor.data.len(), 6 * format.tile_bytes());
+
+    let back = tensor.to_f32();
+    assert_eq!(back.len(), values.len());
+    for (index, (a, b)) in values.iter().zip(&back).enumerate() {
+        let row = index / cols;
+        let start = index - index % 16;
+        let largest = values[start..start + 16]
+            .iter()
+            .fold(0f32, |max, v| max.max(v.abs()));
+        assert!(
+            (a - b).abs() <= largest * tolerance,
+            "{format:?} row {row}: {a} became {b}"
+        );
+    }
+
+    // Packing what was unpacked is lossless
+    let again = Tensor::from_f32(&back, rows, cols, format);
+    assert_eq!(again.data, tensor.data, "{format:?}");
+}
+
+#[test]
+fn format_round_trips() {
+    round_trip(DataFormat::Float32, 0.0);
+    round_trip(DataFormat::Float16, 1.0 / 1024.0);
+    round_trip(DataFormat::Float16B, 1.0 / 128.0);
+    round_trip(DataFormat::Bfp8B, 1.0 / 64.0);
+    round_trip(DataFormat::Bfp4B, 1.0 / 4.0);
+}
+
+#[test]
+fn block_float_layout() {
+    let mut tile = vec![0.0; TILE_VALUES];
+    tile[0] = 1.0;
+    tile[1] = -0.5;
+    tile[2] = 0.25;
+    tile[16] = 3.0;
+
+    let packed = pack(DataFormat::Bfp8B, &tile);
+    assert_eq!(packed.len(), 64 + 1024);
+    // The first face row shares the exponent of 1.0, the second that of 3.0
+    assert_eq!(&packed[..3], &[127, 128, 0]);
+    assert_eq!(&packed[64..67], &[64, 0x80 | 32, 16]);
+    assert_eq!(packed[64 + 16], 96);
+    assert_eq!(unpack(DataFormat::Bfp8B, &packed)[..3], [1.0, -0.5, 0.25]);
+
+    let packed = pack(DataFormat::Bfp4B, &tile);
+    assert_eq!(packed.len(), 64 + 512);
+    assert_eq!(packed[64], 4 | (0x8 | 2) << 4);
+    assert_eq!(unpack(DataFormat::Bfp4B, &packed)[..3], [1.0, -0.5, 0.25]);
+}
+
+#[test]
+fn tensor_through_interleaved_buffer() {
+    let channels = (0..4).map(|v| dram_tile(0, v)).collect();
+    let mut dram = DramAllocator::new(channels, 0x10_0000);
+
+    let values = values(100 * 50);
+    let bf16 = values.iter().map(|v| f32_to_bf16(*v)).collect::<Vec<_>>();
+    let tensor = Tensor::from_bf16(&bf16, 100, 50, DataFormat::Bfp8B);
+    let buffer = tensor.alloc_interleaved(&mut dram, "tensor").unwrap();
+    assert_eq!(buffer.pages(), tensor.tiles() as u64);
+
+    let mut memory = HashMap::new();
+    buffer.scatter(&tensor.data, &mut |tile, addr, page| {
+        memory.insert((tile.addr, addr), page.to_vec());
+    });
+    // Tile 5 is the second page on the second channel
+    let (tile, addr) = buffer.page_address(5);
+    assert_eq!(memory[&(tile.addr, addr)], tensor.tile(5));
+
+    let data =
+        buffer.gather(&mut |tile, addr, page| page.copy_from_slice(&memory[&(tile.addr, addr)]));
+    let read = Tensor::from_packed(data, 100, 50, DataFormat::Bfp8B);
+    assert_eq!(read, tensor);
+    assert_eq!(read.convert(DataFormat::Float16B).to_bf16().len(), 100 * 50);
+}